@@ -0,0 +1,275 @@
+//! 自由トゥートの承認キュー
+//!
+//! `FREE_TOOT_APPROVAL=true` のとき、生成した自由トゥートはすぐに投稿せず、
+//! モデレーター宛ての direct 投稿で確認を頼む。確認用の投稿への返信
+//! （`ok` / `ng` / `retry`）かお気に入りで結果を決め、SQLite の `pending_posts` に残す。
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use crate::config::BotConfig;
+use crate::conversation_store::{ConversationStore, PendingPost};
+use crate::free_toot_task::{do_free_toot, finish_free_toot};
+use crate::mastodon::Status;
+use crate::publisher::{publish_direct, publish_reply};
+use crate::util::strip_html;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Approve,
+    Reject,
+    Regenerate,
+}
+
+impl Decision {
+    fn state(self) -> &'static str {
+        match self {
+            Decision::Approve => "approved",
+            Decision::Reject => "rejected",
+            Decision::Regenerate => "regenerated",
+        }
+    }
+}
+
+/// 確認を頼む（ドライランなら記録だけしてキューには入れない）
+pub async fn submit_for_approval(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    text: &str,
+    topic_id: Option<&str>,
+) -> Result<()> {
+    let expires_at = Utc::now().timestamp() + cfg.approval_expiry.as_secs() as i64;
+    let request = review_request_text(cfg, text, expires_at);
+
+    match publish_direct(client, cfg, conv_store, &request).await? {
+        Some(review_id) => {
+            let id = conv_store.create_pending_post(text, topic_id, &review_id, expires_at).await?;
+            println!("[approval] queued #{id} (review status {review_id})");
+        }
+        None => println!("[approval] dry run: not queued"),
+    }
+
+    Ok(())
+}
+
+/// 確認用の投稿へのモデレーターの返信なら処理して true（通常の返信はしない）
+pub async fn handle_review_reply(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    conv_store: &Arc<ConversationStore>,
+    status: &Status,
+    acct: &str,
+) -> Result<bool> {
+    if !cfg.free_toot_approval || !is_moderator(cfg, acct) {
+        return Ok(false);
+    }
+    let Some(review_id) = status.in_reply_to_id.as_deref() else {
+        return Ok(false);
+    };
+    let Some(pending) = conv_store.find_pending_post(review_id).await? else {
+        return Ok(false);
+    };
+
+    let message = match parse_decision(&status.content, cfg.approval_regenerate_on_reject) {
+        Some(decision) => decide(client, cfg, conv_store, &pending, decision, acct).await?,
+        None => "ok（投稿）/ ng（却下）/ retry（作り直し）のどれかで返信してください".to_string(),
+    };
+    publish_reply(client, cfg, conv_store, status, acct, &message).await?;
+
+    Ok(true)
+}
+
+/// 確認用の投稿をモデレーターがお気に入りしたら承認
+pub async fn handle_review_favourite(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    conv_store: &Arc<ConversationStore>,
+    review_status: &Status,
+    acct: &str,
+) -> Result<()> {
+    if !cfg.free_toot_approval || !is_moderator(cfg, acct) {
+        return Ok(());
+    }
+    let Some(pending) = conv_store.find_pending_post(&review_status.id).await? else {
+        return Ok(());
+    };
+
+    let message = decide(client, cfg, conv_store, &pending, Decision::Approve, acct).await?;
+    publish_reply(client, cfg, conv_store, review_status, acct, &message).await
+}
+
+/// 期限切れの承認待ちを片付ける
+pub async fn expire_pending_posts(conv_store: &ConversationStore) {
+    match conv_store.expire_pending_posts(Utc::now().timestamp()).await {
+        Ok(0) => {}
+        Ok(n) => println!("[approval] {n} pending post(s) expired"),
+        Err(e) => eprintln!("[approval] Failed to expire pending posts: {:?}", e),
+    }
+}
+
+/// 結果を記録して実行し、モデレーターへの返事を返す
+async fn decide(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    conv_store: &Arc<ConversationStore>,
+    pending: &PendingPost,
+    decision: Decision,
+    acct: &str,
+) -> Result<String> {
+    if pending.expires_at <= Utc::now().timestamp() {
+        conv_store.resolve_pending_post(pending.id, "expired", acct).await?;
+        return Ok("期限切れなので投稿しませんでした".to_string());
+    }
+    // 別のモデレーターが先に決めていたら何もしない
+    if !conv_store.resolve_pending_post(pending.id, decision.state(), acct).await? {
+        return Ok("この候補はもう処理済みです".to_string());
+    }
+    println!("[approval] #{} {} by @{acct}", pending.id, decision.state());
+
+    Ok(match decision {
+        Decision::Approve => {
+            finish_free_toot(client, cfg, conv_store, &pending.text, pending.topic_id.as_deref())
+                .await?;
+            "投稿しました".to_string()
+        }
+        Decision::Reject => "却下しました".to_string(),
+        Decision::Regenerate => {
+            spawn_regeneration(client, cfg, conv_store, pending.id);
+            "作り直します。新しい候補ができたら送ります".to_string()
+        }
+    })
+}
+
+/// 新しい候補を別タスクで作る（OpenAI の呼び出しと作り直しの間、通知の処理を止めないように）
+fn spawn_regeneration(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    conv_store: &Arc<ConversationStore>,
+    pending_id: i64,
+) {
+    let client = client.clone();
+    let cfg = cfg.clone();
+    let conv_store = Arc::clone(conv_store);
+
+    tokio::spawn(async move {
+        if let Err(e) = do_free_toot(&client, &cfg, &conv_store).await {
+            eprintln!("[approval] Failed to regenerate #{pending_id}: {:?}", e);
+        }
+    });
+}
+
+/// 返信の本文（HTML）から判断を読む。メンションは無視して最初の語で決める
+pub fn parse_decision(content: &str, regenerate_on_reject: bool) -> Option<Decision> {
+    let plain = strip_html(content);
+    let word = plain.split_whitespace().find(|w| !w.starts_with('@'))?;
+    let word = word.trim_matches(|c: char| c.is_ascii_punctuation() || "。、！？".contains(c));
+
+    match word.to_lowercase().as_str() {
+        "ok" | "承認" | "👍" => Some(Decision::Approve),
+        "ng" | "却下" if regenerate_on_reject => Some(Decision::Regenerate),
+        "ng" | "却下" => Some(Decision::Reject),
+        "retry" | "やり直し" | "再生成" => Some(Decision::Regenerate),
+        _ => None,
+    }
+}
+
+fn is_moderator(cfg: &BotConfig, acct: &str) -> bool {
+    let acct = acct.trim_start_matches('@');
+    cfg.approval_moderators.iter().any(|m| m.eq_ignore_ascii_case(acct))
+}
+
+fn review_request_text(cfg: &BotConfig, text: &str, expires_at: i64) -> String {
+    let mentions: Vec<String> = cfg.approval_moderators.iter().map(|m| format!("@{m}")).collect();
+    let deadline = DateTime::<Utc>::from_timestamp(expires_at, 0)
+        .map(|t| t.with_timezone(&cfg.timezone).format("%m/%d %H:%M").to_string())
+        .unwrap_or_default();
+    let ng = if cfg.approval_regenerate_on_reject { "作り直し" } else { "却下" };
+
+    format!(
+        "{}\n自由トゥートの候補です（{deadline} まで）。\n返信で ok（投稿）/ ng（{ng}）/ retry（作り直し）、お気に入りでも承認できます。\n\n{}",
+        mentions.join(" "),
+        text.trim()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockHttpServer, test_config};
+
+    fn approval_config() -> BotConfig {
+        let mut cfg = test_config();
+        cfg.free_toot_approval = true;
+        cfg.approval_moderators = vec!["alice".into(), "bob@remote.example".into()];
+        cfg
+    }
+
+    #[test]
+    fn parses_decisions_after_mentions() {
+        assert_eq!(parse_decision("<p>@mekamopo OK!</p>", false), Some(Decision::Approve));
+        assert_eq!(parse_decision("<p>@bot 承認</p>", false), Some(Decision::Approve));
+        assert_eq!(parse_decision("ng", false), Some(Decision::Reject));
+        assert_eq!(parse_decision("ng。", true), Some(Decision::Regenerate));
+        assert_eq!(parse_decision("@bot retry please", false), Some(Decision::Regenerate));
+        assert_eq!(parse_decision("@bot いいね", false), None);
+        assert_eq!(parse_decision("@bot", false), None);
+    }
+
+    #[test]
+    fn matches_moderators_case_insensitively() {
+        let cfg = approval_config();
+
+        assert!(is_moderator(&cfg, "Alice"));
+        assert!(is_moderator(&cfg, "bob@remote.example"));
+        assert!(!is_moderator(&cfg, "bob"));
+    }
+
+    #[test]
+    fn review_request_mentions_moderators_and_quotes_text() {
+        let cfg = approval_config();
+
+        let text = review_request_text(&cfg, " 今日もいい天気 ", 0);
+
+        assert!(text.starts_with("@alice @bob@remote.example\n"), "{text}");
+        assert!(text.contains("ng（却下）"));
+        assert!(text.ends_with("\n\n今日もいい天気"));
+    }
+
+    #[tokio::test]
+    async fn approving_posts_and_records_history() {
+        let server = MockHttpServer::respond("200 OK", r#"{"id":"1"}"#);
+        let mut cfg = approval_config();
+        cfg.mastodon_base = server.base_url().to_string();
+        let store = Arc::new(ConversationStore::new(":memory:").unwrap());
+        let expires_at = Utc::now().timestamp() + 60;
+        let id = store.create_pending_post("draft", None, "review-1", expires_at).await.unwrap();
+        let pending = store.find_pending_post("review-1").await.unwrap().unwrap();
+
+        let message =
+            decide(&reqwest::Client::new(), &cfg, &store, &pending, Decision::Approve, "alice")
+                .await
+                .unwrap();
+
+        assert_eq!(message, "投稿しました");
+        assert_eq!(store.recent_free_toots(1).await.unwrap(), vec!["draft".to_string()]);
+        assert!(!store.resolve_pending_post(id, "rejected", "bob").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn expired_posts_are_not_published() {
+        let cfg = approval_config();
+        let store = Arc::new(ConversationStore::new(":memory:").unwrap());
+        store.create_pending_post("draft", None, "review-1", 0).await.unwrap();
+        let pending = store.find_pending_post("review-1").await.unwrap().unwrap();
+
+        let message =
+            decide(&reqwest::Client::new(), &cfg, &store, &pending, Decision::Approve, "alice")
+                .await
+                .unwrap();
+
+        assert_eq!(message, "期限切れなので投稿しませんでした");
+        assert!(store.recent_free_toots(1).await.unwrap().is_empty());
+    }
+}
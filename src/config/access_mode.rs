@@ -0,0 +1,43 @@
+use anyhow::bail;
+use serde::Deserialize;
+use std::{fmt::Display, str::FromStr};
+
+/// 誰のメンションに返信するか
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum AccessMode {
+    /// 誰にでも返信する
+    Open,
+    /// 許可リスト（ファイル / Mastodon のリスト）のアカウントだけ
+    Allowlist,
+    /// bot がフォローしているアカウントだけ
+    Following,
+    /// 同じインスタンスのアカウントだけ
+    Local,
+}
+
+impl FromStr for AccessMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "open" => Ok(Self::Open),
+            "allowlist" => Ok(Self::Allowlist),
+            "following" => Ok(Self::Following),
+            "local" => Ok(Self::Local),
+            other => bail!("unknown ACCESS_MODE: {other}"),
+        }
+    }
+}
+
+impl Display for AccessMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            AccessMode::Open => "open",
+            AccessMode::Allowlist => "allowlist",
+            AccessMode::Following => "following",
+            AccessMode::Local => "local",
+        };
+
+        write!(f, "{}", s)
+    }
+}
@@ -1,5 +1,10 @@
-use crate::config::{Redacted, Visibility, env_parsing};
+use crate::config::{
+    AccessMode, CwMode, FunctionTool, ModelTable, Redacted, SearchClassifier, Visibility,
+    env_parsing,
+};
+use crate::scheduler::{FreeTootSchedule, QuietHours};
 use anyhow::Result;
+use chrono_tz::Tz;
 use serde::Deserialize;
 use std::time::Duration;
 
@@ -14,6 +19,14 @@ pub struct BotConfig {
     /// リプライ用（ベースモデル）
     #[serde(default = "default_reply_model")]
     pub openai_reply_model: String,
+    /// 返信のモデルが失敗したときに順に試すモデル
+    pub reply_fallback_models: Vec<String>,
+    /// 自由トゥートのモデルが失敗したときに順に試すモデル
+    pub free_toot_fallback_models: Vec<String>,
+    /// 空・オウム返しのリトライが失敗したときに順に試すモデル
+    pub retry_fallback_models: Vec<String>,
+    /// モデルごとの対応機能（`MODELS_PATH` と組み込みの表）
+    pub model_capabilities: ModelTable,
 
     pub openai_api_key: String,
 
@@ -22,7 +35,33 @@ pub struct BotConfig {
     pub prompts_path: String,       // 例: config/prompts.json
     pub bot_db_path: String,        // 例: bot_state.sqlite
 
+    /// 自由トゥートの時間帯判定や CurrentTime 指示に使うタイムゾーン
+    pub timezone: Tz,
     pub free_toot_interval: Duration,
+    /// cron 式または HH:MM のリスト。なければ `free_toot_interval` ごと
+    #[serde(skip)]
+    pub free_toot_schedule: Option<FreeTootSchedule>,
+    #[serde(skip)]
+    pub free_toot_quiet_hours: Option<QuietHours>,
+    /// 予定時刻に足すランダムな遅延の上限
+    pub free_toot_jitter: Duration,
+    /// 類似チェックに使う直近の自由トゥート件数（0 でチェックしない）
+    pub free_toot_history_size: usize,
+    /// これ以上似ていたら作り直す（0.0〜1.0）
+    pub free_toot_similarity_threshold: f32,
+    pub free_toot_max_attempts: usize,
+    /// プロンプトに「繰り返さないで」と渡す直近の件数（0 で渡さない）
+    pub free_toot_avoid_recent_in_prompt: usize,
+    /// 自由トゥートの話題キュー（JSON / CSV）
+    pub topics_path: Option<String>,
+
+    /// 新着記事を紹介する RSS / Atom フィード
+    pub feed_urls: Vec<String>,
+    pub feed_poll_interval: Duration,
+    /// 1 回の巡回で 1 フィードから投稿する記事数の上限
+    pub feed_max_posts_per_poll: usize,
+    /// 記事の URL をドメイン表記にせずそのまま付ける
+    pub feed_keep_links: bool,
     pub reply_temperature: f32,
     pub free_toot_temperature: f32,
 
@@ -30,9 +69,71 @@ pub struct BotConfig {
     pub mastodon_char_limit: usize,
 
     pub reply_min_interval: Duration,
+    /// これより古い `last_response_id` は使わず、会話ログから始め直す
+    pub conversation_max_age: Duration,
 
     // Tools
     pub enable_web_search: bool,
+    /// 検索を強制するかをモデルに判定させるか
+    pub search_classifier: SearchClassifier,
+    /// 検索の要否の判定に使うモデル
+    pub search_classifier_model: String,
+    /// 返信でモデルが呼べる関数ツール（空なら使わない）
+    pub function_tools: Vec<FunctionTool>,
+    /// 関数ツールを呼んで答えを返すやり取りの上限回数
+    pub function_tool_max_rounds: usize,
+
+    /// CW 付きの投稿への返信に付ける CW
+    pub reply_cw_mode: CwMode,
+    /// 返信を構造化出力（JSON スキーマ）で受け取る
+    pub reply_structured_output: bool,
+    /// センシティブな話題ではモデルに CW を付けさせる（構造化出力の `content_warning`）
+    pub reply_model_cw: bool,
+    /// Web 検索の出典 URL を返信の末尾に付ける数（0 ならドメイン表記だけ）
+    pub reply_source_links: usize,
+
+    /// 自由トゥートをモデレーターの承認を経てから投稿する
+    pub free_toot_approval: bool,
+    /// 承認を頼むアカウント（`user` / `user@example.com`）
+    pub approval_moderators: Vec<String>,
+    /// この時間内に決まらなかった承認待ちは破棄
+    pub approval_expiry: Duration,
+    /// `ng` で作り直すか（false なら却下だけ）
+    pub approval_regenerate_on_reject: bool,
+
+    /// 誰のメンションに返信するか
+    pub access_mode: AccessMode,
+    /// `allowlist` モードの許可リストのファイル（1 行に 1 アカウント）
+    pub allowlist_path: Option<String>,
+    /// `allowlist` モードで許可する Mastodon のリストの ID
+    pub allowlist_list_id: Option<String>,
+    /// Mastodon のリストを読み直す間隔
+    pub allowlist_refresh: Duration,
+    /// 許可されていないアカウントに 1 度だけお知らせを返信する
+    pub access_denied_notice: bool,
+
+    /// 1 つのスレッドで bot が返信する回数の上限（0 で無制限）
+    pub loop_max_thread_replies: usize,
+    /// 同じアカウントへ `loop_account_window` の間に返信する回数の上限（0 で無制限）
+    pub loop_max_account_replies: usize,
+    pub loop_account_window: Duration,
+    /// 打ち切ったスレッドに 1 度だけお知らせを返信する
+    pub loop_closing_notice: bool,
+
+    /// プロフィールにあればオプトアウトとみなすタグ（小文字）
+    pub nobot_tags: Vec<String>,
+    /// プロフィールを確かめた結果をキャッシュする時間
+    pub nobot_cache_ttl: Duration,
+
+    /// 生成だけして投稿しない（投稿内容はログと SQLite に残す）
+    pub dry_run: bool,
+}
+
+/// カンマ区切りのリスト（空の要素は捨てる）
+fn parse_list(value: Option<String>) -> Vec<String> {
+    value
+        .map(|s| s.split(',').map(str::trim).filter(|v| !v.is_empty()).map(String::from).collect())
+        .unwrap_or_default()
 }
 
 fn default_reply_model() -> String {
@@ -49,6 +150,14 @@ impl BotConfig {
         let openai_reply_model =
             env_parsing::opt("OPENAI_REPLY_MODEL").unwrap_or_else(default_reply_model);
         let openai_api_key = env_parsing::must("OPENAI_API_KEY")?;
+        let reply_fallback_models = parse_list(env_parsing::opt("OPENAI_REPLY_FALLBACK_MODELS"));
+        let free_toot_fallback_models =
+            parse_list(env_parsing::opt("OPENAI_FREE_TOOT_FALLBACK_MODELS"));
+        let retry_fallback_models = match env_parsing::opt("OPENAI_RETRY_FALLBACK_MODELS") {
+            Some(models) => parse_list(Some(models)),
+            None => reply_fallback_models.clone(),
+        };
+        let model_capabilities = ModelTable::load(env_parsing::opt("MODELS_PATH").as_deref())?;
 
         let streaming_base_url = env_parsing::opt("MASTODON_STREAMING_URL")
             .unwrap_or_else(|| env_parsing::default_streaming_ws(&mastodon_base));
@@ -57,8 +166,29 @@ impl BotConfig {
         let bot_db_path =
             env_parsing::opt("BOT_DB_PATH").unwrap_or_else(|| "bot_state.sqlite".into());
 
+        let timezone: Tz = env_parsing::parse_str("BOT_TIMEZONE", "Asia/Tokyo")?;
+
         let free_toot_interval: u64 = env_parsing::parse("FREE_TOOT_INTERVAL_SECS", 3600)?;
         let free_toot_interval = Duration::from_secs(free_toot_interval);
+        let free_toot_schedule: Option<FreeTootSchedule> =
+            env_parsing::parse_opt("FREE_TOOT_SCHEDULE")?;
+        let free_toot_quiet_hours: Option<QuietHours> =
+            env_parsing::parse_opt("FREE_TOOT_QUIET_HOURS")?;
+        let free_toot_jitter: u64 = env_parsing::parse("FREE_TOOT_JITTER_SECS", 0)?;
+        let free_toot_jitter = Duration::from_secs(free_toot_jitter);
+        let free_toot_history_size: usize = env_parsing::parse("FREE_TOOT_HISTORY_SIZE", 20)?;
+        let free_toot_similarity_threshold: f32 =
+            env_parsing::parse("FREE_TOOT_SIMILARITY_THRESHOLD", 0.6)?;
+        let free_toot_max_attempts: usize = env_parsing::parse("FREE_TOOT_MAX_ATTEMPTS", 3)?;
+        let free_toot_avoid_recent_in_prompt: usize =
+            env_parsing::parse("FREE_TOOT_AVOID_RECENT_IN_PROMPT", 0)?;
+        let topics_path = env_parsing::opt("TOPICS_PATH");
+
+        let feed_urls = parse_list(env_parsing::opt("FEED_URLS"));
+        let feed_poll_interval: u64 = env_parsing::parse("FEED_POLL_INTERVAL_SECS", 1800)?;
+        let feed_poll_interval = Duration::from_secs(feed_poll_interval);
+        let feed_max_posts_per_poll: usize = env_parsing::parse("FEED_MAX_POSTS_PER_POLL", 1)?;
+        let feed_keep_links: bool = env_parsing::parse("FEED_KEEP_LINKS", true)?;
 
         let reply_temperature: f32 = env_parsing::parse("REPLY_TEMPERATURE", 0.7)?;
         let free_toot_temperature: f32 = env_parsing::parse("FREE_TOOT_TEMPERATURE", 0.8)?;
@@ -69,25 +199,131 @@ impl BotConfig {
 
         let reply_min_interval_ms: u64 = env_parsing::parse("REPLY_MIN_INTERVAL_MS", 3000)?;
         let reply_min_interval = Duration::from_millis(reply_min_interval_ms);
+        // OpenAI が応答を保存しておく期間（30 日）
+        let conversation_max_age: u64 = env_parsing::parse("CONVERSATION_MAX_AGE_SECS", 2_592_000)?;
+        let conversation_max_age = Duration::from_secs(conversation_max_age);
 
         let enable_web_search: bool = env_parsing::parse("ENABLE_WEB_SEARCH", false)?;
+        let search_classifier: SearchClassifier =
+            env_parsing::parse_str("SEARCH_CLASSIFIER", "off")?;
+        let search_classifier_model =
+            env_parsing::opt("SEARCH_CLASSIFIER_MODEL").unwrap_or_else(|| openai_model.clone());
+        let dry_run: bool = env_parsing::parse("DRY_RUN", false)?;
+        let function_tools = parse_list(env_parsing::opt("FUNCTION_TOOLS"))
+            .iter()
+            .map(|name| name.parse())
+            .collect::<Result<Vec<FunctionTool>>>()?;
+        let function_tool_max_rounds: usize = env_parsing::parse("FUNCTION_TOOL_MAX_ROUNDS", 4)?;
+
+        let reply_cw_mode: CwMode = env_parsing::parse_str("REPLY_CW_MODE", "inherit")?;
+        let reply_structured_output: bool = env_parsing::parse("REPLY_STRUCTURED_OUTPUT", true)?;
+        let reply_model_cw: bool = env_parsing::parse("REPLY_MODEL_CW", false)?;
+        if reply_model_cw && !reply_structured_output {
+            anyhow::bail!("REPLY_MODEL_CW=true requires REPLY_STRUCTURED_OUTPUT=true");
+        }
+        let reply_source_links: usize = env_parsing::parse("REPLY_SOURCE_LINKS", 0)?;
+        if reply_source_links > 2 {
+            anyhow::bail!("REPLY_SOURCE_LINKS must be 0, 1 or 2");
+        }
+
+        let access_mode: AccessMode = env_parsing::parse_str("ACCESS_MODE", "open")?;
+        let allowlist_path = env_parsing::opt("ALLOWLIST_PATH");
+        let allowlist_list_id = env_parsing::opt("ALLOWLIST_LIST_ID");
+        if access_mode == AccessMode::Allowlist
+            && allowlist_path.is_none()
+            && allowlist_list_id.is_none()
+        {
+            anyhow::bail!("ACCESS_MODE=allowlist requires ALLOWLIST_PATH or ALLOWLIST_LIST_ID");
+        }
+        let allowlist_refresh: u64 = env_parsing::parse("ALLOWLIST_REFRESH_SECS", 600)?;
+        let allowlist_refresh = Duration::from_secs(allowlist_refresh);
+        let access_denied_notice: bool = env_parsing::parse("ACCESS_DENIED_NOTICE", false)?;
+
+        let loop_max_thread_replies: usize = env_parsing::parse("LOOP_MAX_THREAD_REPLIES", 20)?;
+        let loop_max_account_replies: usize = env_parsing::parse("LOOP_MAX_ACCOUNT_REPLIES", 10)?;
+        let loop_account_window: u64 = env_parsing::parse("LOOP_ACCOUNT_WINDOW_SECS", 600)?;
+        let loop_account_window = Duration::from_secs(loop_account_window);
+        let loop_closing_notice: bool = env_parsing::parse("LOOP_CLOSING_NOTICE", false)?;
+
+        let nobot_tags =
+            parse_list(env_parsing::opt("NOBOT_TAGS").or_else(|| Some("#nobot,#noai".into())))
+                .into_iter()
+                .map(|tag| tag.to_lowercase())
+                .collect();
+        let nobot_cache_ttl: u64 = env_parsing::parse("NOBOT_CACHE_SECS", 86400)?;
+        let nobot_cache_ttl = Duration::from_secs(nobot_cache_ttl);
+
+        let free_toot_approval: bool = env_parsing::parse("FREE_TOOT_APPROVAL", false)?;
+        let approval_moderators = parse_list(env_parsing::opt("APPROVAL_MODERATORS"))
+            .into_iter()
+            .map(|acct| acct.trim_start_matches('@').to_string())
+            .collect::<Vec<_>>();
+        if free_toot_approval && approval_moderators.is_empty() {
+            anyhow::bail!("FREE_TOOT_APPROVAL=true requires APPROVAL_MODERATORS");
+        }
+        let approval_expiry: u64 = env_parsing::parse("APPROVAL_EXPIRY_SECS", 86400)?;
+        let approval_expiry = Duration::from_secs(approval_expiry);
+        let approval_regenerate_on_reject: bool =
+            env_parsing::parse("APPROVAL_REGENERATE_ON_REJECT", false)?;
 
         Ok(Self {
             mastodon_base,
             mastodon_access_token: mastodon_token,
             openai_model,
             openai_reply_model,
+            reply_fallback_models,
+            free_toot_fallback_models,
+            retry_fallback_models,
+            model_capabilities,
             openai_api_key,
             streaming_base_url,
             prompts_path,
             bot_db_path,
+            timezone,
             free_toot_interval,
+            free_toot_schedule,
+            free_toot_quiet_hours,
+            free_toot_jitter,
+            free_toot_history_size,
+            free_toot_similarity_threshold,
+            free_toot_max_attempts,
+            free_toot_avoid_recent_in_prompt,
+            topics_path,
+            feed_urls,
+            feed_poll_interval,
+            feed_max_posts_per_poll,
+            feed_keep_links,
             reply_temperature,
             free_toot_temperature,
             visibility,
             mastodon_char_limit,
             reply_min_interval,
+            conversation_max_age,
             enable_web_search,
+            search_classifier,
+            search_classifier_model,
+            function_tools,
+            function_tool_max_rounds,
+            reply_cw_mode,
+            reply_structured_output,
+            reply_model_cw,
+            reply_source_links,
+            access_mode,
+            allowlist_path,
+            allowlist_list_id,
+            allowlist_refresh,
+            access_denied_notice,
+            loop_max_thread_replies,
+            loop_max_account_replies,
+            loop_account_window,
+            loop_closing_notice,
+            nobot_tags,
+            nobot_cache_ttl,
+            free_toot_approval,
+            approval_moderators,
+            approval_expiry,
+            approval_regenerate_on_reject,
+            dry_run,
         })
     }
 
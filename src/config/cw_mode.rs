@@ -0,0 +1,39 @@
+use anyhow::bail;
+use serde::Deserialize;
+use std::{fmt::Display, str::FromStr};
+
+/// CW 付きの投稿に返信するとき、返信の CW をどうするか
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum CwMode {
+    /// 元の投稿と同じ CW を付ける
+    Inherit,
+    /// 元の CW の頭に `re: ` を付ける
+    Prefix,
+    /// CW を引き継がない
+    Never,
+}
+
+impl FromStr for CwMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "inherit" => Ok(Self::Inherit),
+            "prefix" => Ok(Self::Prefix),
+            "never" => Ok(Self::Never),
+            other => bail!("unknown REPLY_CW_MODE: {other}"),
+        }
+    }
+}
+
+impl Display for CwMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            CwMode::Inherit => "inherit",
+            CwMode::Prefix => "prefix",
+            CwMode::Never => "never",
+        };
+
+        write!(f, "{}", s)
+    }
+}
@@ -19,6 +19,15 @@ where
     }
 }
 
+pub fn parse_opt<T: FromStr>(key: &str) -> Result<Option<T>>
+where
+    <T as FromStr>::Err: Display,
+{
+    opt(key)
+        .map(|s| s.parse::<T>().map_err(|e| anyhow!("failed to parse {key}='{s}': {e}")))
+        .transpose()
+}
+
 pub fn parse_str<T: FromStr>(key: &str, default: &str) -> Result<T>
 where
     <T as FromStr>::Err: Display,
@@ -0,0 +1,48 @@
+use anyhow::bail;
+use serde::Deserialize;
+use std::{fmt::Display, str::FromStr};
+
+/// モデルに渡す組み込みの関数ツール
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum FunctionTool {
+    /// 指定したタイムゾーンの現在時刻
+    CurrentTime,
+    /// Mastodon のアカウントのプロフィール
+    LookupAccount,
+    /// URL から投稿を取得
+    FetchStatus,
+    /// bot 自身の過去の投稿を探す
+    SearchOwnPosts,
+}
+
+impl FunctionTool {
+    /// モデルに見せる関数名
+    pub fn name(self) -> &'static str {
+        match self {
+            FunctionTool::CurrentTime => "current_time",
+            FunctionTool::LookupAccount => "lookup_account",
+            FunctionTool::FetchStatus => "fetch_status",
+            FunctionTool::SearchOwnPosts => "search_own_posts",
+        }
+    }
+}
+
+impl FromStr for FunctionTool {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "current_time" => Ok(Self::CurrentTime),
+            "lookup_account" => Ok(Self::LookupAccount),
+            "fetch_status" => Ok(Self::FetchStatus),
+            "search_own_posts" => Ok(Self::SearchOwnPosts),
+            other => bail!("unknown FUNCTION_TOOLS entry: {other}"),
+        }
+    }
+}
+
+impl Display for FunctionTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -1,8 +1,18 @@
+mod access_mode;
 mod bot_config;
+mod cw_mode;
 mod env_parsing;
+mod function_tool;
+mod model_capabilities;
 mod redacted;
+mod search_classifier;
 mod visibility;
 
+pub use access_mode::AccessMode;
 pub use bot_config::BotConfig;
+pub use cw_mode::CwMode;
+pub use function_tool::FunctionTool;
+pub use model_capabilities::ModelTable;
 pub use redacted::Redacted;
+pub use search_classifier::SearchClassifier;
 pub use visibility::Visibility;
@@ -0,0 +1,228 @@
+//! モデルごとの対応機能（`MODELS_PATH` の JSON と組み込みの定義）
+//!
+//! Responses API へのリクエストは、呼び出すモデルをこの表で引いて組み立てる。
+
+use anyhow::{Context, Result, bail};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+const REASONING_EFFORTS: [&str; 4] = ["minimal", "low", "medium", "high"];
+const VERBOSITIES: [&str; 3] = ["low", "medium", "high"];
+
+static BUILTIN: Lazy<ModelTable> = Lazy::new(ModelTable::builtin);
+
+/// モデルが対応している機能
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ModelCapabilities {
+    /// `temperature` を送れる
+    pub temperature: bool,
+    /// `tools`（Web 検索・関数ツール）を送れる
+    pub tools: bool,
+    /// 推論モデルの `reasoning.effort`（minimal / low / medium / high）
+    pub reasoning_effort: Option<String>,
+    /// `text.verbosity`（low / medium / high）
+    pub verbosity: Option<String>,
+    /// 出力トークンの上限（推論トークンを含む）。指定すると呼び出しごとの既定値を置き換える
+    pub max_output_tokens: Option<u32>,
+    /// 画像を入力できる
+    pub vision: bool,
+}
+
+impl Default for ModelCapabilities {
+    fn default() -> Self {
+        Self {
+            temperature: true,
+            tools: true,
+            reasoning_effort: None,
+            verbosity: None,
+            max_output_tokens: None,
+            vision: false,
+        }
+    }
+}
+
+/// 表の 1 行。`model` は完全一致か、末尾の `*` で前方一致
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModelEntry {
+    pub model: String,
+    #[serde(flatten)]
+    pub capabilities: ModelCapabilities,
+}
+
+impl ModelEntry {
+    fn matches(&self, model: &str) -> bool {
+        match self.model.strip_suffix('*') {
+            Some(prefix) => model.starts_with(prefix),
+            None => model == self.model,
+        }
+    }
+}
+
+/// モデルの対応機能の表（先頭から評価し、最初に一致した行を使う）
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModelTable {
+    entries: Vec<ModelEntry>,
+}
+
+impl ModelTable {
+    /// 組み込みの表（推論モデルは temperature を送らず reasoning.effort を使う）
+    pub fn builtin() -> Self {
+        let reasoning = |model: &str, verbosity: Option<&str>| ModelEntry {
+            model: model.to_string(),
+            capabilities: ModelCapabilities {
+                temperature: false,
+                reasoning_effort: Some("low".into()),
+                verbosity: verbosity.map(str::to_string),
+                max_output_tokens: Some(2048),
+                vision: true,
+                ..Default::default()
+            },
+        };
+        let vision = |model: &str| ModelEntry {
+            model: model.to_string(),
+            capabilities: ModelCapabilities { vision: true, ..Default::default() },
+        };
+
+        Self {
+            entries: vec![
+                reasoning("gpt-5*", Some("low")),
+                reasoning("o1*", None),
+                reasoning("o3*", None),
+                reasoning("o4*", None),
+                vision("gpt-4o*"),
+                vision("gpt-4.1*"),
+            ],
+        }
+    }
+
+    /// `MODELS_PATH` の表を組み込みの表の前に足す
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let mut table = Self::builtin();
+        if let Some(path) = path {
+            let data = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read MODELS_PATH {path}"))?;
+            let custom =
+                Self::parse(&data).with_context(|| format!("invalid MODELS_PATH {path}"))?;
+            table.entries.splice(0..0, custom.entries);
+        }
+
+        Ok(table)
+    }
+
+    /// JSON の表を読む（組み込みの表は足さない）
+    pub(crate) fn parse(data: &str) -> Result<Self> {
+        let entries: Vec<ModelEntry> = serde_json::from_str(data)?;
+        for entry in &entries {
+            let caps = &entry.capabilities;
+            if let Some(effort) = &caps.reasoning_effort
+                && !REASONING_EFFORTS.contains(&effort.as_str())
+            {
+                bail!("{}: unknown reasoning_effort {effort:?}", entry.model);
+            }
+            if let Some(verbosity) = &caps.verbosity
+                && !VERBOSITIES.contains(&verbosity.as_str())
+            {
+                bail!("{}: unknown verbosity {verbosity:?}", entry.model);
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// 表にないモデルは既定値（temperature・tools あり、推論なし）
+    ///
+    /// ファインチューン（`ft:gpt-5-mini:org::id`）は、そのものの行がなければ元のモデル名で引く
+    pub fn lookup(&self, model: &str) -> ModelCapabilities {
+        self.find(model)
+            .or_else(|| base_model(model).and_then(|base| self.find(base)))
+            .map(|entry| entry.capabilities.clone())
+            .unwrap_or_default()
+    }
+
+    fn find(&self, model: &str) -> Option<&ModelEntry> {
+        self.entries.iter().find(|entry| entry.matches(model))
+    }
+
+    /// プロセス全体で共有する組み込みの表
+    pub fn builtin_ref() -> &'static ModelTable {
+        &BUILTIN
+    }
+}
+
+/// `ft:<元のモデル>:<組織>:<名前>:<id>` の元のモデル
+fn base_model(model: &str) -> Option<&str> {
+    let rest = model.strip_prefix("ft:")?;
+    Some(rest.split(':').next().unwrap_or(rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_table_treats_reasoning_models_specially() {
+        let table = ModelTable::builtin();
+
+        let gpt5 = table.lookup("gpt-5-mini");
+        assert!(!gpt5.temperature);
+        assert_eq!(gpt5.reasoning_effort.as_deref(), Some("low"));
+
+        assert!(table.lookup("gpt-4o-mini").vision);
+        assert_eq!(table.lookup("my-fine-tune"), ModelCapabilities::default());
+    }
+
+    #[test]
+    fn custom_entries_take_precedence_and_match_exactly_or_by_prefix() {
+        let custom = ModelTable::parse(
+            r#"[
+                {"model": "ft:gpt-4.1-mini:acme", "temperature": false, "max_output_tokens": 300},
+                {"model": "gpt-5-nano", "reasoning_effort": "minimal", "tools": false}
+            ]"#,
+        )
+        .unwrap();
+        let mut table = ModelTable::builtin();
+        table.entries.splice(0..0, custom.entries);
+
+        let ft = table.lookup("ft:gpt-4.1-mini:acme");
+        assert!(!ft.temperature);
+        assert_eq!(ft.max_output_tokens, Some(300));
+        assert!(ft.tools);
+
+        let nano = table.lookup("gpt-5-nano");
+        assert_eq!(nano.reasoning_effort.as_deref(), Some("minimal"));
+        assert!(!nano.tools);
+        // 省略したキーは既定値（組み込みの gpt-5* 行は使わない）
+        assert!(nano.temperature);
+
+        assert_eq!(table.lookup("gpt-5").reasoning_effort.as_deref(), Some("low"));
+    }
+
+    #[test]
+    fn fine_tunes_use_their_base_model_unless_listed() {
+        let table = ModelTable::builtin();
+
+        let ft = table.lookup("ft:gpt-5-mini-2025-08-07:acme::abc123");
+        assert!(!ft.temperature);
+        assert_eq!(ft.reasoning_effort.as_deref(), Some("low"));
+        assert!(table.lookup("ft:gpt-4o-mini-2024-07-18:acme:bot:xyz").vision);
+        assert_eq!(table.lookup("ft:my-model:acme::1"), ModelCapabilities::default());
+
+        let custom =
+            ModelTable::parse(r#"[{"model": "ft:gpt-5-mini*", "temperature": true}]"#).unwrap();
+        assert!(custom.lookup("ft:gpt-5-mini:acme::abc").temperature);
+    }
+
+    #[test]
+    fn rejects_unknown_effort_and_verbosity() {
+        assert!(ModelTable::parse(r#"[{"model": "x", "reasoning_effort": "max"}]"#).is_err());
+        assert!(ModelTable::parse(r#"[{"model": "x", "verbosity": "loud"}]"#).is_err());
+    }
+
+    #[test]
+    fn load_without_path_is_builtin() {
+        let table = ModelTable::load(None).unwrap();
+        assert!(!table.lookup("o3-mini").temperature);
+        assert!(ModelTable::load(Some("/nonexistent/models.json")).is_err());
+    }
+}
@@ -10,14 +10,56 @@ impl std::fmt::Debug for Redacted<'_> {
             .field("mastodon_token", &mask(&c.mastodon_access_token))
             .field("openai_model", &c.openai_model)
             .field("openai_api_key", &mask(&c.openai_api_key))
+            .field("reply_fallback_models", &c.reply_fallback_models)
+            .field("free_toot_fallback_models", &c.free_toot_fallback_models)
+            .field("retry_fallback_models", &c.retry_fallback_models)
+            .field("model_capabilities", &c.model_capabilities)
             .field("streaming_base_url", &c.streaming_base_url)
             .field("prompts_path", &c.prompts_path)
             .field("bot_db_path", &c.bot_db_path)
+            .field("timezone", &c.timezone)
             .field("free_toot_interval_secs", &c.free_toot_interval.as_secs())
+            .field("free_toot_schedule", &c.free_toot_schedule)
+            .field("free_toot_quiet_hours", &c.free_toot_quiet_hours)
+            .field("free_toot_jitter_secs", &c.free_toot_jitter.as_secs())
+            .field("free_toot_history_size", &c.free_toot_history_size)
+            .field("free_toot_similarity_threshold", &c.free_toot_similarity_threshold)
+            .field("free_toot_max_attempts", &c.free_toot_max_attempts)
+            .field("free_toot_avoid_recent_in_prompt", &c.free_toot_avoid_recent_in_prompt)
+            .field("topics_path", &c.topics_path)
+            .field("feed_urls", &c.feed_urls)
+            .field("feed_poll_interval_secs", &c.feed_poll_interval.as_secs())
+            .field("feed_max_posts_per_poll", &c.feed_max_posts_per_poll)
+            .field("feed_keep_links", &c.feed_keep_links)
             .field("reply_temperature", &c.reply_temperature)
             .field("free_toot_temperature", &c.free_toot_temperature)
             .field("visibility", &c.visibility)
             .field("reply_min_interval_ms", &c.reply_min_interval.as_millis())
+            .field("conversation_max_age_secs", &c.conversation_max_age.as_secs())
+            .field("search_classifier", &c.search_classifier)
+            .field("search_classifier_model", &c.search_classifier_model)
+            .field("function_tools", &c.function_tools)
+            .field("function_tool_max_rounds", &c.function_tool_max_rounds)
+            .field("reply_cw_mode", &c.reply_cw_mode)
+            .field("reply_structured_output", &c.reply_structured_output)
+            .field("reply_model_cw", &c.reply_model_cw)
+            .field("reply_source_links", &c.reply_source_links)
+            .field("access_mode", &c.access_mode)
+            .field("allowlist_path", &c.allowlist_path)
+            .field("allowlist_list_id", &c.allowlist_list_id)
+            .field("allowlist_refresh", &c.allowlist_refresh)
+            .field("access_denied_notice", &c.access_denied_notice)
+            .field("loop_max_thread_replies", &c.loop_max_thread_replies)
+            .field("loop_max_account_replies", &c.loop_max_account_replies)
+            .field("loop_account_window", &c.loop_account_window)
+            .field("loop_closing_notice", &c.loop_closing_notice)
+            .field("nobot_tags", &c.nobot_tags)
+            .field("nobot_cache_ttl", &c.nobot_cache_ttl)
+            .field("free_toot_approval", &c.free_toot_approval)
+            .field("approval_moderators", &c.approval_moderators)
+            .field("approval_expiry", &c.approval_expiry)
+            .field("approval_regenerate_on_reject", &c.approval_regenerate_on_reject)
+            .field("dry_run", &c.dry_run)
             .finish()
     }
 }
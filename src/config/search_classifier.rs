@@ -0,0 +1,39 @@
+use anyhow::bail;
+use serde::Deserialize;
+use std::{fmt::Display, str::FromStr};
+
+/// 返信で Web 検索を強制するかをモデルに判定させるか
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum SearchClassifier {
+    /// prompts の `search_rules` だけで決める
+    Off,
+    /// ルールに一致したときだけ、本当に検索が要るかをモデルに確かめる
+    Confirm,
+    /// すべてのメンションでモデルに判定させる
+    Always,
+}
+
+impl FromStr for SearchClassifier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "confirm" => Ok(Self::Confirm),
+            "always" => Ok(Self::Always),
+            other => bail!("unknown SEARCH_CLASSIFIER: {other}"),
+        }
+    }
+}
+
+impl Display for SearchClassifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SearchClassifier::Off => "off",
+            SearchClassifier::Confirm => "confirm",
+            SearchClassifier::Always => "always",
+        };
+
+        write!(f, "{}", s)
+    }
+}
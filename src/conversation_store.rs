@@ -1,6 +1,7 @@
 use anyhow::{Context, Result, anyhow};
-use rusqlite::{Connection, params};
+use rusqlite::{Connection, OptionalExtension, params};
 use std::{
+    collections::HashSet,
     path::{Path, PathBuf},
     sync::mpsc,
     thread,
@@ -18,17 +19,208 @@ struct DbWorker {
     sender: mpsc::Sender<DbCommand>,
 }
 
+/// スレッドの直前の応答と、それを返したモデル
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreviousResponse {
+    pub id: String,
+    /// モデルを記録する前に保存した応答では `None`
+    pub model: Option<String>,
+}
+
+impl PreviousResponse {
+    /// `model` に `previous_response_id` として渡せるか（別のモデルには会話を引き継がない）
+    pub fn continues_with(&self, model: &str) -> bool {
+        self.model.as_deref().is_none_or(|m| m == model)
+    }
+}
+
+/// 承認待ちの投稿
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingPost {
+    pub id: i64,
+    pub text: String,
+    /// 話題キューから作った投稿なら、その話題の ID
+    pub topic_id: Option<String>,
+    pub expires_at: i64,
+}
+
+struct NewPendingPost {
+    text: String,
+    topic_id: Option<String>,
+    review_status_id: String,
+    created_at: i64,
+    expires_at: i64,
+}
+
+struct DryRunPost {
+    kind: String,
+    in_reply_to_id: Option<String>,
+    visibility: String,
+    text: String,
+    created_at: i64,
+}
+
 enum DbCommand {
     GetPreviousResponseId {
         thread_key: String,
-        reply: mpsc::Sender<Result<Option<String>>>,
+        updated_after: i64,
+        reply: mpsc::Sender<Result<Option<PreviousResponse>>>,
+    },
+    ForgetResponseId {
+        thread_key: String,
+        reply: mpsc::Sender<Result<()>>,
     },
     UpsertLastResponseId {
         thread_key: String,
         response_id: String,
+        model: String,
+        updated_at: i64,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    GetThreadPersona {
+        thread_key: String,
+        reply: mpsc::Sender<Result<Option<String>>>,
+    },
+    SetThreadPersona {
+        thread_key: String,
+        persona: String,
+        updated_at: i64,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    ResetThread {
+        thread_key: String,
+        reset_status_id: String,
+        reset_at: i64,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    GetThreadReset {
+        thread_key: String,
+        reply: mpsc::Sender<Result<Option<String>>>,
+    },
+    RememberThreadAccount {
+        thread_key: String,
+        acct: String,
         updated_at: i64,
         reply: mpsc::Sender<Result<()>>,
     },
+    ForgetAccount {
+        acct: String,
+        reply: mpsc::Sender<Result<usize>>,
+    },
+    AddOptOut {
+        acct: String,
+        source: String,
+        created_at: i64,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    RemoveOptOut {
+        acct: String,
+        source: String,
+        reply: mpsc::Sender<Result<bool>>,
+    },
+    OptOutSources {
+        acct: String,
+        reply: mpsc::Sender<Result<Vec<String>>>,
+    },
+    ListOptOuts {
+        source: String,
+        reply: mpsc::Sender<Result<Vec<String>>>,
+    },
+    GetCachedNobot {
+        acct: String,
+        checked_after: i64,
+        reply: mpsc::Sender<Result<Option<bool>>>,
+    },
+    CacheNobot {
+        acct: String,
+        nobot: bool,
+        checked_at: i64,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    MarkAccessNotice {
+        acct: String,
+        noticed_at: i64,
+        reply: mpsc::Sender<Result<bool>>,
+    },
+    RecordBotReply {
+        thread_key: String,
+        acct: String,
+        replied_at: i64,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    CountThreadReplies {
+        thread_key: String,
+        reply: mpsc::Sender<Result<usize>>,
+    },
+    CountAccountReplies {
+        acct: String,
+        since: i64,
+        reply: mpsc::Sender<Result<usize>>,
+    },
+    CloseThread {
+        thread_key: String,
+        closed_at: i64,
+        reply: mpsc::Sender<Result<bool>>,
+    },
+    GetLastRun {
+        name: String,
+        reply: mpsc::Sender<Result<Option<i64>>>,
+    },
+    SetLastRun {
+        name: String,
+        ran_at: i64,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    AddFreeToot {
+        text: String,
+        posted_at: i64,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    RecentFreeToots {
+        limit: usize,
+        reply: mpsc::Sender<Result<Vec<String>>>,
+    },
+    SeenFeedEntries {
+        feed_url: String,
+        reply: mpsc::Sender<Result<HashSet<String>>>,
+    },
+    MarkFeedEntriesSeen {
+        feed_url: String,
+        entry_ids: Vec<String>,
+        seen_at: i64,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    RecordDryRunPost {
+        post: DryRunPost,
+        reply: mpsc::Sender<Result<()>>,
+    },
+    CreatePendingPost {
+        post: NewPendingPost,
+        reply: mpsc::Sender<Result<i64>>,
+    },
+    FindPendingPost {
+        review_status_id: String,
+        reply: mpsc::Sender<Result<Option<PendingPost>>>,
+    },
+    ResolvePendingPost {
+        id: i64,
+        state: String,
+        decided_by: String,
+        decided_at: i64,
+        reply: mpsc::Sender<Result<bool>>,
+    },
+    ExpirePendingPosts {
+        now: i64,
+        reply: mpsc::Sender<Result<usize>>,
+    },
+    DoneTopics {
+        reply: mpsc::Sender<Result<HashSet<String>>>,
+    },
+    MarkTopicDone {
+        topic_id: String,
+        done_at: i64,
+        reply: mpsc::Sender<Result<()>>,
+    },
 }
 
 impl ConversationStore {
@@ -38,14 +230,413 @@ impl ConversationStore {
         Ok(Self { worker })
     }
 
-    pub async fn get_previous_response_id(&self, thread_key: &str) -> Result<Option<String>> {
-        self.worker.get_previous_response_id(thread_key.to_string()).await
+    /// `updated_after` より後に保存したスレッドの直前の応答（古い応答は OpenAI 側で消えている）
+    pub async fn get_previous_response_id(
+        &self,
+        thread_key: &str,
+        updated_after: i64,
+    ) -> Result<Option<PreviousResponse>> {
+        self.worker.get_previous_response_id(thread_key.to_string(), updated_after).await
+    }
+
+    /// OpenAI 側で見つからなくなった応答を忘れる（ペルソナやリセット位置はそのまま）
+    pub async fn forget_response_id(&self, thread_key: &str) -> Result<()> {
+        let thread_key = thread_key.to_string();
+        self.worker
+            .request("forget_response_id", move |reply| DbCommand::ForgetResponseId {
+                thread_key,
+                reply,
+            })
+            .await
+    }
+
+    /// スレッドの最後の応答と、それを返したモデルを記録する
+    pub async fn upsert_last_response_id(
+        &self,
+        thread_key: &str,
+        response_id: &str,
+        model: &str,
+    ) -> Result<()> {
+        let updated_at = unix_timestamp_seconds();
+        self.worker
+            .upsert_last_response_id(
+                thread_key.to_string(),
+                response_id.to_string(),
+                model.to_string(),
+                updated_at,
+            )
+            .await
+    }
+
+    /// スレッドに割り当て済みのペルソナ名
+    pub async fn get_thread_persona(&self, thread_key: &str) -> Result<Option<String>> {
+        let thread_key = thread_key.to_string();
+        self.worker
+            .request("get_thread_persona", move |reply| DbCommand::GetThreadPersona {
+                thread_key,
+                reply,
+            })
+            .await
+    }
+
+    pub async fn set_thread_persona(&self, thread_key: &str, persona: &str) -> Result<()> {
+        let thread_key = thread_key.to_string();
+        let persona = persona.to_string();
+        let updated_at = unix_timestamp_seconds();
+        self.worker
+            .request("set_thread_persona", move |reply| DbCommand::SetThreadPersona {
+                thread_key,
+                persona,
+                updated_at,
+                reply,
+            })
+            .await
+    }
+
+    /// スレッドの会話状態とループ対策の記録（返信回数・打ち切り）を消し、`reset_status_id` より前の文脈を使わないようにする
+    pub async fn reset_thread(&self, thread_key: &str, reset_status_id: &str) -> Result<()> {
+        let thread_key = thread_key.to_string();
+        let reset_status_id = reset_status_id.to_string();
+        let reset_at = unix_timestamp_seconds();
+        self.worker
+            .request("reset_thread", move |reply| DbCommand::ResetThread {
+                thread_key,
+                reset_status_id,
+                reset_at,
+                reply,
+            })
+            .await
+    }
+
+    /// 最後にリセットを指示した status id
+    pub async fn get_thread_reset(&self, thread_key: &str) -> Result<Option<String>> {
+        let thread_key = thread_key.to_string();
+        self.worker
+            .request("get_thread_reset", move |reply| DbCommand::GetThreadReset {
+                thread_key,
+                reply,
+            })
+            .await
     }
 
-    pub async fn upsert_last_response_id(&self, thread_key: &str, response_id: &str) -> Result<()> {
+    /// スレッドで話した相手を記録する（`forget_account` 用）
+    pub async fn remember_thread_account(&self, thread_key: &str, acct: &str) -> Result<()> {
+        let thread_key = thread_key.to_string();
+        let acct = acct.to_string();
         let updated_at = unix_timestamp_seconds();
         self.worker
-            .upsert_last_response_id(thread_key.to_string(), response_id.to_string(), updated_at)
+            .request("remember_thread_account", move |reply| DbCommand::RememberThreadAccount {
+                thread_key,
+                acct,
+                updated_at,
+                reply,
+            })
+            .await
+    }
+
+    /// そのアカウントと話したスレッドの会話状態とペルソナを消し、消したスレッド数を返す
+    ///
+    /// `/reset` の位置（`thread_resets`）は残す。消すとリセット前の発言がまた文脈に戻ってしまう
+    pub async fn forget_account(&self, acct: &str) -> Result<usize> {
+        let acct = acct.to_string();
+        self.worker
+            .request("forget_account", move |reply| DbCommand::ForgetAccount { acct, reply })
+            .await
+    }
+
+    /// オプトアウトを記録（`source` は `command` / `admin`）
+    pub async fn add_opt_out(&self, acct: &str, source: &str) -> Result<()> {
+        let acct = acct.to_string();
+        let source = source.to_string();
+        let created_at = unix_timestamp_seconds();
+        self.worker
+            .request("add_opt_out", move |reply| DbCommand::AddOptOut {
+                acct,
+                source,
+                created_at,
+                reply,
+            })
+            .await
+    }
+
+    /// その `source` のオプトアウトを取り消す。なければ false
+    pub async fn remove_opt_out(&self, acct: &str, source: &str) -> Result<bool> {
+        let acct = acct.to_string();
+        let source = source.to_string();
+        self.worker
+            .request("remove_opt_out", move |reply| DbCommand::RemoveOptOut { acct, source, reply })
+            .await
+    }
+
+    /// アカウントのオプトアウトの記録元（なければ空）
+    pub async fn opt_out_sources(&self, acct: &str) -> Result<Vec<String>> {
+        let acct = acct.to_string();
+        self.worker
+            .request("opt_out_sources", move |reply| DbCommand::OptOutSources { acct, reply })
+            .await
+    }
+
+    pub async fn list_opt_outs(&self, source: &str) -> Result<Vec<String>> {
+        let source = source.to_string();
+        self.worker
+            .request("list_opt_outs", move |reply| DbCommand::ListOptOuts { source, reply })
+            .await
+    }
+
+    /// `checked_after` より後に確かめたプロフィールの `#nobot` の有無
+    pub async fn get_cached_nobot(&self, acct: &str, checked_after: i64) -> Result<Option<bool>> {
+        let acct = acct.to_string();
+        self.worker
+            .request("get_cached_nobot", move |reply| DbCommand::GetCachedNobot {
+                acct,
+                checked_after,
+                reply,
+            })
+            .await
+    }
+
+    pub async fn cache_nobot(&self, acct: &str, nobot: bool) -> Result<()> {
+        let acct = acct.to_string();
+        let checked_at = unix_timestamp_seconds();
+        self.worker
+            .request("cache_nobot", move |reply| DbCommand::CacheNobot {
+                acct,
+                nobot,
+                checked_at,
+                reply,
+            })
+            .await
+    }
+
+    /// 対象外のお知らせを送ったと記録する。すでに送っていたら false
+    pub async fn mark_access_notice(&self, acct: &str) -> Result<bool> {
+        let acct = acct.to_string();
+        let noticed_at = unix_timestamp_seconds();
+        self.worker
+            .request("mark_access_notice", move |reply| DbCommand::MarkAccessNotice {
+                acct,
+                noticed_at,
+                reply,
+            })
+            .await
+    }
+
+    /// bot が返信したことを記録する（ループ検出用）
+    pub async fn record_bot_reply(&self, thread_key: &str, acct: &str) -> Result<()> {
+        let thread_key = thread_key.to_string();
+        let acct = acct.to_string();
+        let replied_at = unix_timestamp_seconds();
+        self.worker
+            .request("record_bot_reply", move |reply| DbCommand::RecordBotReply {
+                thread_key,
+                acct,
+                replied_at,
+                reply,
+            })
+            .await
+    }
+
+    /// スレッドでの bot の返信数
+    pub async fn count_thread_replies(&self, thread_key: &str) -> Result<usize> {
+        let thread_key = thread_key.to_string();
+        self.worker
+            .request("count_thread_replies", move |reply| DbCommand::CountThreadReplies {
+                thread_key,
+                reply,
+            })
+            .await
+    }
+
+    /// `since` 以降にそのアカウントへ返信した数
+    pub async fn count_account_replies(&self, acct: &str, since: i64) -> Result<usize> {
+        let acct = acct.to_string();
+        self.worker
+            .request("count_account_replies", move |reply| DbCommand::CountAccountReplies {
+                acct,
+                since,
+                reply,
+            })
+            .await
+    }
+
+    /// スレッドを打ち切ったと記録する。すでに打ち切っていたら false
+    pub async fn close_thread(&self, thread_key: &str) -> Result<bool> {
+        let thread_key = thread_key.to_string();
+        let closed_at = unix_timestamp_seconds();
+        self.worker
+            .request("close_thread", move |reply| DbCommand::CloseThread {
+                thread_key,
+                closed_at,
+                reply,
+            })
+            .await
+    }
+
+    /// 定期タスクの前回実行時刻（UNIX 秒）
+    pub async fn get_last_run(&self, name: &str) -> Result<Option<i64>> {
+        let name = name.to_string();
+        self.worker
+            .request("get_last_run", move |reply| DbCommand::GetLastRun { name, reply })
+            .await
+    }
+
+    pub async fn set_last_run(&self, name: &str, ran_at: i64) -> Result<()> {
+        let name = name.to_string();
+        self.worker
+            .request("set_last_run", move |reply| DbCommand::SetLastRun { name, ran_at, reply })
+            .await
+    }
+
+    /// 投稿した自由トゥートを履歴に残す
+    pub async fn add_free_toot(&self, text: &str) -> Result<()> {
+        let text = text.to_string();
+        let posted_at = unix_timestamp_seconds();
+        self.worker
+            .request("add_free_toot", move |reply| DbCommand::AddFreeToot {
+                text,
+                posted_at,
+                reply,
+            })
+            .await
+    }
+
+    /// 直近の自由トゥート（新しい順）
+    pub async fn recent_free_toots(&self, limit: usize) -> Result<Vec<String>> {
+        self.worker
+            .request("recent_free_toots", move |reply| DbCommand::RecentFreeToots { limit, reply })
+            .await
+    }
+
+    /// 記録済みのフィード記事の ID
+    pub async fn seen_feed_entries(&self, feed_url: &str) -> Result<HashSet<String>> {
+        let feed_url = feed_url.to_string();
+        self.worker
+            .request("seen_feed_entries", move |reply| DbCommand::SeenFeedEntries {
+                feed_url,
+                reply,
+            })
+            .await
+    }
+
+    pub async fn mark_feed_entries_seen(
+        &self,
+        feed_url: &str,
+        entry_ids: Vec<String>,
+    ) -> Result<()> {
+        let feed_url = feed_url.to_string();
+        let seen_at = unix_timestamp_seconds();
+        self.worker
+            .request("mark_feed_entries_seen", move |reply| DbCommand::MarkFeedEntriesSeen {
+                feed_url,
+                entry_ids,
+                seen_at,
+                reply,
+            })
+            .await
+    }
+
+    /// ドライランで投稿するはずだった内容を記録
+    pub async fn record_dry_run_post(
+        &self,
+        kind: &str,
+        in_reply_to_id: Option<&str>,
+        visibility: &str,
+        text: &str,
+    ) -> Result<()> {
+        let post = DryRunPost {
+            kind: kind.to_string(),
+            in_reply_to_id: in_reply_to_id.map(str::to_string),
+            visibility: visibility.to_string(),
+            text: text.to_string(),
+            created_at: unix_timestamp_seconds(),
+        };
+        self.worker
+            .request("record_dry_run_post", move |reply| DbCommand::RecordDryRunPost {
+                post,
+                reply,
+            })
+            .await
+    }
+
+    /// 承認待ちの投稿を登録（`review_status_id` はモデレーターに送った確認用の投稿）
+    pub async fn create_pending_post(
+        &self,
+        text: &str,
+        topic_id: Option<&str>,
+        review_status_id: &str,
+        expires_at: i64,
+    ) -> Result<i64> {
+        let post = NewPendingPost {
+            text: text.to_string(),
+            topic_id: topic_id.map(str::to_string),
+            review_status_id: review_status_id.to_string(),
+            created_at: unix_timestamp_seconds(),
+            expires_at,
+        };
+        self.worker
+            .request("create_pending_post", move |reply| DbCommand::CreatePendingPost {
+                post,
+                reply,
+            })
+            .await
+    }
+
+    /// 確認用の投稿に対応する、まだ決まっていない承認待ちの投稿
+    pub async fn find_pending_post(&self, review_status_id: &str) -> Result<Option<PendingPost>> {
+        let review_status_id = review_status_id.to_string();
+        self.worker
+            .request("find_pending_post", move |reply| DbCommand::FindPendingPost {
+                review_status_id,
+                reply,
+            })
+            .await
+    }
+
+    /// 承認待ちの投稿の結果を記録する。すでに決まっていたら false
+    pub async fn resolve_pending_post(
+        &self,
+        id: i64,
+        state: &str,
+        decided_by: &str,
+    ) -> Result<bool> {
+        let state = state.to_string();
+        let decided_by = decided_by.to_string();
+        let decided_at = unix_timestamp_seconds();
+        self.worker
+            .request("resolve_pending_post", move |reply| DbCommand::ResolvePendingPost {
+                id,
+                state,
+                decided_by,
+                decided_at,
+                reply,
+            })
+            .await
+    }
+
+    /// 期限切れの承認待ちを `expired` にして件数を返す
+    pub async fn expire_pending_posts(&self, now: i64) -> Result<usize> {
+        self.worker
+            .request("expire_pending_posts", move |reply| DbCommand::ExpirePendingPosts {
+                now,
+                reply,
+            })
+            .await
+    }
+
+    /// 使用済みの話題の ID
+    pub async fn done_topics(&self) -> Result<HashSet<String>> {
+        self.worker.request("done_topics", |reply| DbCommand::DoneTopics { reply }).await
+    }
+
+    pub async fn mark_topic_done(&self, topic_id: &str) -> Result<()> {
+        let topic_id = topic_id.to_string();
+        let done_at = unix_timestamp_seconds();
+        self.worker
+            .request("mark_topic_done", move |reply| DbCommand::MarkTopicDone {
+                topic_id,
+                done_at,
+                reply,
+            })
             .await
     }
 }
@@ -62,44 +653,54 @@ impl DbWorker {
         Ok(Self { sender: command_sender })
     }
 
-    async fn get_previous_response_id(&self, thread_key: String) -> Result<Option<String>> {
-        let sender = self.sender.clone();
-
-        task::spawn_blocking(move || {
-            let (reply_sender, reply_receiver) = mpsc::channel();
-            sender
-                .send(DbCommand::GetPreviousResponseId { thread_key, reply: reply_sender })
-                .map_err(|_| anyhow!("ConversationStore database worker stopped"))?;
-
-            reply_receiver.recv().context("ConversationStore database worker stopped")?
+    async fn get_previous_response_id(
+        &self,
+        thread_key: String,
+        updated_after: i64,
+    ) -> Result<Option<PreviousResponse>> {
+        self.request("get_previous_response_id", move |reply| DbCommand::GetPreviousResponseId {
+            thread_key,
+            updated_after,
+            reply,
         })
         .await
-        .context("ConversationStore get_previous_response_id task failed")?
     }
 
     async fn upsert_last_response_id(
         &self,
         thread_key: String,
         response_id: String,
+        model: String,
         updated_at: i64,
     ) -> Result<()> {
+        self.request("upsert_last_response_id", move |reply| DbCommand::UpsertLastResponseId {
+            thread_key,
+            response_id,
+            model,
+            updated_at,
+            reply,
+        })
+        .await
+    }
+
+    /// コマンドを DB ワーカーに送り、結果をブロッキングスレッド上で待つ
+    async fn request<T, F>(&self, name: &'static str, build: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(mpsc::Sender<Result<T>>) -> DbCommand + Send + 'static,
+    {
         let sender = self.sender.clone();
 
         task::spawn_blocking(move || {
             let (reply_sender, reply_receiver) = mpsc::channel();
             sender
-                .send(DbCommand::UpsertLastResponseId {
-                    thread_key,
-                    response_id,
-                    updated_at,
-                    reply: reply_sender,
-                })
+                .send(build(reply_sender))
                 .map_err(|_| anyhow!("ConversationStore database worker stopped"))?;
 
             reply_receiver.recv().context("ConversationStore database worker stopped")?
         })
         .await
-        .context("ConversationStore upsert_last_response_id task failed")?
+        .with_context(|| format!("ConversationStore {name} task failed"))?
     }
 }
 
@@ -136,35 +737,252 @@ fn open_connection(path: &Path) -> Result<Connection> {
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 thread_key TEXT NOT NULL UNIQUE,
                 last_response_id TEXT NOT NULL,
+                last_model TEXT,
                 updated_at INTEGER NOT NULL
             );
+
+            CREATE TABLE IF NOT EXISTS thread_personas (
+                thread_key TEXT PRIMARY KEY,
+                persona TEXT NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS thread_resets (
+                thread_key TEXT PRIMARY KEY,
+                reset_status_id TEXT NOT NULL,
+                reset_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS thread_accounts (
+                thread_key TEXT NOT NULL,
+                acct TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (thread_key, acct)
+            );
+
+            CREATE TABLE IF NOT EXISTS opt_outs (
+                acct TEXT NOT NULL,
+                source TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (acct, source)
+            );
+
+            CREATE TABLE IF NOT EXISTS account_profiles (
+                acct TEXT PRIMARY KEY,
+                nobot INTEGER NOT NULL,
+                checked_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS access_notices (
+                acct TEXT PRIMARY KEY,
+                noticed_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS bot_replies (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                thread_key TEXT NOT NULL,
+                acct TEXT NOT NULL,
+                replied_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS bot_replies_thread ON bot_replies (thread_key);
+            CREATE INDEX IF NOT EXISTS bot_replies_acct ON bot_replies (acct, replied_at);
+
+            CREATE TABLE IF NOT EXISTS closed_threads (
+                thread_key TEXT PRIMARY KEY,
+                closed_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS scheduler_state (
+                name TEXT PRIMARY KEY,
+                last_run_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS feed_entries (
+                feed_url TEXT NOT NULL,
+                entry_id TEXT NOT NULL,
+                seen_at INTEGER NOT NULL,
+                PRIMARY KEY (feed_url, entry_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS dry_run_posts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                in_reply_to_id TEXT,
+                visibility TEXT NOT NULL,
+                text TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS pending_posts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                text TEXT NOT NULL,
+                topic_id TEXT,
+                review_status_id TEXT NOT NULL UNIQUE,
+                state TEXT NOT NULL DEFAULT 'pending',
+                created_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL,
+                decided_by TEXT,
+                decided_at INTEGER
+            );
+
+            CREATE TABLE IF NOT EXISTS topics_done (
+                topic_id TEXT PRIMARY KEY,
+                done_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS free_toot_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                text TEXT NOT NULL,
+                posted_at INTEGER NOT NULL
+            );
             "#,
     )
     .context("Failed to init conversations table")?;
 
+    // 古い DB にはモデルの列がない
+    add_column_if_missing(&conn, "conversations", "last_model", "TEXT")?;
+
     Ok(conn)
 }
 
-fn handle_db_command(conn: &Connection, command: DbCommand) {
-    match command {
-        DbCommand::GetPreviousResponseId { thread_key, reply } => {
-            let result = query_previous_response_id(conn, &thread_key);
-            let _ = reply.send(result);
-        }
-        DbCommand::UpsertLastResponseId { thread_key, response_id, updated_at, reply } => {
-            let result = upsert_response_id(conn, &thread_key, &response_id, updated_at);
-            let _ = reply.send(result);
-        }
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"))
+            .with_context(|| format!("Failed to add {table}.{column}"))?;
+    }
+    Ok(())
+}
+
+fn handle_db_command(conn: &Connection, command: DbCommand) {
+    match command {
+        DbCommand::GetPreviousResponseId { thread_key, updated_after, reply } => {
+            let result = query_previous_response_id(conn, &thread_key, updated_after);
+            let _ = reply.send(result);
+        }
+        DbCommand::ForgetResponseId { thread_key, reply } => {
+            let _ = reply.send(delete_response_id(conn, &thread_key));
+        }
+        DbCommand::UpsertLastResponseId { thread_key, response_id, model, updated_at, reply } => {
+            let result = upsert_response_id(conn, &thread_key, &response_id, &model, updated_at);
+            let _ = reply.send(result);
+        }
+        DbCommand::GetThreadPersona { thread_key, reply } => {
+            let _ = reply.send(query_thread_persona(conn, &thread_key));
+        }
+        DbCommand::SetThreadPersona { thread_key, persona, updated_at, reply } => {
+            let _ = reply.send(upsert_thread_persona(conn, &thread_key, &persona, updated_at));
+        }
+        DbCommand::ResetThread { thread_key, reset_status_id, reset_at, reply } => {
+            let _ = reply.send(reset_thread(conn, &thread_key, &reset_status_id, reset_at));
+        }
+        DbCommand::GetThreadReset { thread_key, reply } => {
+            let _ = reply.send(query_thread_reset(conn, &thread_key));
+        }
+        DbCommand::RememberThreadAccount { thread_key, acct, updated_at, reply } => {
+            let _ = reply.send(upsert_thread_account(conn, &thread_key, &acct, updated_at));
+        }
+        DbCommand::ForgetAccount { acct, reply } => {
+            let _ = reply.send(forget_account(conn, &acct));
+        }
+        DbCommand::AddOptOut { acct, source, created_at, reply } => {
+            let _ = reply.send(insert_opt_out(conn, &acct, &source, created_at));
+        }
+        DbCommand::RemoveOptOut { acct, source, reply } => {
+            let _ = reply.send(delete_opt_out(conn, &acct, &source));
+        }
+        DbCommand::OptOutSources { acct, reply } => {
+            let _ = reply.send(query_opt_out_sources(conn, &acct));
+        }
+        DbCommand::ListOptOuts { source, reply } => {
+            let _ = reply.send(query_opt_outs(conn, &source));
+        }
+        DbCommand::GetCachedNobot { acct, checked_after, reply } => {
+            let _ = reply.send(query_cached_nobot(conn, &acct, checked_after));
+        }
+        DbCommand::CacheNobot { acct, nobot, checked_at, reply } => {
+            let _ = reply.send(upsert_nobot(conn, &acct, nobot, checked_at));
+        }
+        DbCommand::MarkAccessNotice { acct, noticed_at, reply } => {
+            let _ = reply.send(insert_access_notice(conn, &acct, noticed_at));
+        }
+        DbCommand::RecordBotReply { thread_key, acct, replied_at, reply } => {
+            let _ = reply.send(insert_bot_reply(conn, &thread_key, &acct, replied_at));
+        }
+        DbCommand::CountThreadReplies { thread_key, reply } => {
+            let _ = reply.send(query_thread_reply_count(conn, &thread_key));
+        }
+        DbCommand::CountAccountReplies { acct, since, reply } => {
+            let _ = reply.send(query_account_reply_count(conn, &acct, since));
+        }
+        DbCommand::CloseThread { thread_key, closed_at, reply } => {
+            let _ = reply.send(insert_closed_thread(conn, &thread_key, closed_at));
+        }
+        DbCommand::GetLastRun { name, reply } => {
+            let _ = reply.send(query_last_run(conn, &name));
+        }
+        DbCommand::SetLastRun { name, ran_at, reply } => {
+            let _ = reply.send(upsert_last_run(conn, &name, ran_at));
+        }
+        DbCommand::AddFreeToot { text, posted_at, reply } => {
+            let _ = reply.send(insert_free_toot(conn, &text, posted_at));
+        }
+        DbCommand::RecentFreeToots { limit, reply } => {
+            let _ = reply.send(query_recent_free_toots(conn, limit));
+        }
+        DbCommand::SeenFeedEntries { feed_url, reply } => {
+            let _ = reply.send(query_seen_feed_entries(conn, &feed_url));
+        }
+        DbCommand::MarkFeedEntriesSeen { feed_url, entry_ids, seen_at, reply } => {
+            let _ = reply.send(insert_feed_entries(conn, &feed_url, &entry_ids, seen_at));
+        }
+        DbCommand::RecordDryRunPost { post, reply } => {
+            let _ = reply.send(insert_dry_run_post(conn, &post));
+        }
+        DbCommand::CreatePendingPost { post, reply } => {
+            let _ = reply.send(insert_pending_post(conn, &post));
+        }
+        DbCommand::FindPendingPost { review_status_id, reply } => {
+            let _ = reply.send(query_pending_post(conn, &review_status_id));
+        }
+        DbCommand::ResolvePendingPost { id, state, decided_by, decided_at, reply } => {
+            let _ = reply.send(update_pending_post(conn, id, &state, &decided_by, decided_at));
+        }
+        DbCommand::ExpirePendingPosts { now, reply } => {
+            let _ = reply.send(expire_pending_posts(conn, now));
+        }
+        DbCommand::DoneTopics { reply } => {
+            let _ = reply.send(query_done_topics(conn));
+        }
+        DbCommand::MarkTopicDone { topic_id, done_at, reply } => {
+            let _ = reply.send(insert_topic_done(conn, &topic_id, done_at));
+        }
     }
 }
 
-fn query_previous_response_id(conn: &Connection, thread_key: &str) -> Result<Option<String>> {
-    let mut stmt =
-        conn.prepare("SELECT last_response_id FROM conversations WHERE thread_key = ?1")?;
-    let mut rows = stmt.query(params![thread_key])?;
+fn query_previous_response_id(
+    conn: &Connection,
+    thread_key: &str,
+    updated_after: i64,
+) -> Result<Option<PreviousResponse>> {
+    let mut stmt = conn.prepare(
+        r#"
+            SELECT last_response_id, last_model FROM conversations
+            WHERE thread_key = ?1 AND updated_at > ?2
+            "#,
+    )?;
+    let mut rows = stmt.query(params![thread_key, updated_after])?;
     if let Some(row) = rows.next()? {
-        let id: String = row.get(0)?;
-        Ok(Some(id))
+        Ok(Some(PreviousResponse { id: row.get(0)?, model: row.get(1)? }))
     } else {
         Ok(None)
     }
@@ -174,17 +992,367 @@ fn upsert_response_id(
     conn: &Connection,
     thread_key: &str,
     response_id: &str,
+    model: &str,
     updated_at: i64,
 ) -> Result<()> {
     conn.execute(
         r#"
-                INSERT INTO conversations (thread_key, last_response_id, updated_at)
-                VALUES (?1, ?2, ?3)
+                INSERT INTO conversations (thread_key, last_response_id, last_model, updated_at)
+                VALUES (?1, ?2, ?3, ?4)
                 ON CONFLICT(thread_key) DO UPDATE SET
                     last_response_id = excluded.last_response_id,
+                    last_model = excluded.last_model,
                     updated_at = excluded.updated_at
                 "#,
-        params![thread_key, response_id, updated_at],
+        params![thread_key, response_id, model, updated_at],
+    )?;
+    Ok(())
+}
+
+fn delete_response_id(conn: &Connection, thread_key: &str) -> Result<()> {
+    conn.execute("DELETE FROM conversations WHERE thread_key = ?1", params![thread_key])?;
+    Ok(())
+}
+
+fn query_thread_persona(conn: &Connection, thread_key: &str) -> Result<Option<String>> {
+    let persona = conn
+        .query_row(
+            "SELECT persona FROM thread_personas WHERE thread_key = ?1",
+            params![thread_key],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(persona)
+}
+
+fn upsert_thread_persona(
+    conn: &Connection,
+    thread_key: &str,
+    persona: &str,
+    updated_at: i64,
+) -> Result<()> {
+    conn.execute(
+        r#"
+                INSERT INTO thread_personas (thread_key, persona, updated_at)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT(thread_key) DO UPDATE SET
+                    persona = excluded.persona,
+                    updated_at = excluded.updated_at
+                "#,
+        params![thread_key, persona, updated_at],
+    )?;
+    Ok(())
+}
+
+fn reset_thread(
+    conn: &Connection,
+    thread_key: &str,
+    reset_status_id: &str,
+    reset_at: i64,
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM conversations WHERE thread_key = ?1", params![thread_key])?;
+    tx.execute("DELETE FROM bot_replies WHERE thread_key = ?1", params![thread_key])?;
+    tx.execute("DELETE FROM closed_threads WHERE thread_key = ?1", params![thread_key])?;
+    tx.execute(
+        r#"
+                INSERT INTO thread_resets (thread_key, reset_status_id, reset_at)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT(thread_key) DO UPDATE SET
+                    reset_status_id = excluded.reset_status_id,
+                    reset_at = excluded.reset_at
+                "#,
+        params![thread_key, reset_status_id, reset_at],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn query_thread_reset(conn: &Connection, thread_key: &str) -> Result<Option<String>> {
+    let reset_status_id = conn
+        .query_row(
+            "SELECT reset_status_id FROM thread_resets WHERE thread_key = ?1",
+            params![thread_key],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(reset_status_id)
+}
+
+fn upsert_thread_account(
+    conn: &Connection,
+    thread_key: &str,
+    acct: &str,
+    updated_at: i64,
+) -> Result<()> {
+    conn.execute(
+        r#"
+                INSERT INTO thread_accounts (thread_key, acct, updated_at)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT(thread_key, acct) DO UPDATE SET updated_at = excluded.updated_at
+                "#,
+        params![thread_key, acct, updated_at],
+    )?;
+    Ok(())
+}
+
+fn forget_account(conn: &Connection, acct: &str) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let threads = "SELECT thread_key FROM thread_accounts WHERE acct = ?1";
+    for table in ["conversations", "thread_personas"] {
+        tx.execute(&format!("DELETE FROM {table} WHERE thread_key IN ({threads})"), params![acct])?;
+    }
+    let forgotten = tx.execute("DELETE FROM thread_accounts WHERE acct = ?1", params![acct])?;
+    tx.commit()?;
+    Ok(forgotten)
+}
+
+fn insert_opt_out(conn: &Connection, acct: &str, source: &str, created_at: i64) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO opt_outs (acct, source, created_at) VALUES (?1, ?2, ?3)",
+        params![acct, source, created_at],
+    )?;
+    Ok(())
+}
+
+fn delete_opt_out(conn: &Connection, acct: &str, source: &str) -> Result<bool> {
+    let deleted = conn
+        .execute("DELETE FROM opt_outs WHERE acct = ?1 AND source = ?2", params![acct, source])?;
+    Ok(deleted > 0)
+}
+
+fn query_opt_out_sources(conn: &Connection, acct: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT source FROM opt_outs WHERE acct = ?1 ORDER BY source")?;
+    let sources = stmt
+        .query_map(params![acct], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(sources)
+}
+
+fn query_opt_outs(conn: &Connection, source: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT acct FROM opt_outs WHERE source = ?1 ORDER BY acct")?;
+    let accts = stmt
+        .query_map(params![source], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(accts)
+}
+
+fn query_cached_nobot(conn: &Connection, acct: &str, checked_after: i64) -> Result<Option<bool>> {
+    let nobot = conn
+        .query_row(
+            "SELECT nobot FROM account_profiles WHERE acct = ?1 AND checked_at > ?2",
+            params![acct, checked_after],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(nobot)
+}
+
+fn upsert_nobot(conn: &Connection, acct: &str, nobot: bool, checked_at: i64) -> Result<()> {
+    conn.execute(
+        r#"
+                INSERT INTO account_profiles (acct, nobot, checked_at)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT(acct) DO UPDATE SET
+                    nobot = excluded.nobot,
+                    checked_at = excluded.checked_at
+                "#,
+        params![acct, nobot, checked_at],
+    )?;
+    Ok(())
+}
+
+fn insert_access_notice(conn: &Connection, acct: &str, noticed_at: i64) -> Result<bool> {
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO access_notices (acct, noticed_at) VALUES (?1, ?2)",
+        params![acct, noticed_at],
+    )?;
+    Ok(inserted > 0)
+}
+
+fn insert_bot_reply(
+    conn: &Connection,
+    thread_key: &str,
+    acct: &str,
+    replied_at: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO bot_replies (thread_key, acct, replied_at) VALUES (?1, ?2, ?3)",
+        params![thread_key, acct, replied_at],
+    )?;
+    Ok(())
+}
+
+fn query_thread_reply_count(conn: &Connection, thread_key: &str) -> Result<usize> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM bot_replies WHERE thread_key = ?1",
+        params![thread_key],
+        |row| row.get(0),
+    )?;
+    Ok(count as usize)
+}
+
+fn query_account_reply_count(conn: &Connection, acct: &str, since: i64) -> Result<usize> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM bot_replies WHERE acct = ?1 AND replied_at >= ?2",
+        params![acct, since],
+        |row| row.get(0),
+    )?;
+    Ok(count as usize)
+}
+
+fn insert_closed_thread(conn: &Connection, thread_key: &str, closed_at: i64) -> Result<bool> {
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO closed_threads (thread_key, closed_at) VALUES (?1, ?2)",
+        params![thread_key, closed_at],
+    )?;
+    Ok(inserted > 0)
+}
+
+fn query_last_run(conn: &Connection, name: &str) -> Result<Option<i64>> {
+    let ran_at = conn
+        .query_row(
+            "SELECT last_run_at FROM scheduler_state WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(ran_at)
+}
+
+fn upsert_last_run(conn: &Connection, name: &str, ran_at: i64) -> Result<()> {
+    conn.execute(
+        r#"
+                INSERT INTO scheduler_state (name, last_run_at)
+                VALUES (?1, ?2)
+                ON CONFLICT(name) DO UPDATE SET last_run_at = excluded.last_run_at
+                "#,
+        params![name, ran_at],
+    )?;
+    Ok(())
+}
+
+fn insert_free_toot(conn: &Connection, text: &str, posted_at: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO free_toot_history (text, posted_at) VALUES (?1, ?2)",
+        params![text, posted_at],
+    )?;
+    Ok(())
+}
+
+fn query_recent_free_toots(conn: &Connection, limit: usize) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT text FROM free_toot_history ORDER BY id DESC LIMIT ?1")?;
+    let texts = stmt
+        .query_map(params![limit as i64], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(texts)
+}
+
+fn query_seen_feed_entries(conn: &Connection, feed_url: &str) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT entry_id FROM feed_entries WHERE feed_url = ?1")?;
+    let ids =
+        stmt.query_map(params![feed_url], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+    Ok(ids)
+}
+
+fn insert_feed_entries(
+    conn: &Connection,
+    feed_url: &str,
+    entry_ids: &[String],
+    seen_at: i64,
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    for entry_id in entry_ids {
+        tx.execute(
+            "INSERT OR IGNORE INTO feed_entries (feed_url, entry_id, seen_at) VALUES (?1, ?2, ?3)",
+            params![feed_url, entry_id, seen_at],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn insert_dry_run_post(conn: &Connection, post: &DryRunPost) -> Result<()> {
+    conn.execute(
+        r#"
+                INSERT INTO dry_run_posts (kind, in_reply_to_id, visibility, text, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                "#,
+        params![post.kind, post.in_reply_to_id, post.visibility, post.text, post.created_at],
+    )?;
+    Ok(())
+}
+
+fn insert_pending_post(conn: &Connection, post: &NewPendingPost) -> Result<i64> {
+    conn.execute(
+        r#"
+                INSERT INTO pending_posts (text, topic_id, review_status_id, created_at, expires_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                "#,
+        params![post.text, post.topic_id, post.review_status_id, post.created_at, post.expires_at],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn query_pending_post(conn: &Connection, review_status_id: &str) -> Result<Option<PendingPost>> {
+    let post = conn
+        .query_row(
+            r#"
+                SELECT id, text, topic_id, expires_at FROM pending_posts
+                WHERE review_status_id = ?1 AND state = 'pending'
+                "#,
+            params![review_status_id],
+            |row| {
+                Ok(PendingPost {
+                    id: row.get(0)?,
+                    text: row.get(1)?,
+                    topic_id: row.get(2)?,
+                    expires_at: row.get(3)?,
+                })
+            },
+        )
+        .optional()?;
+    Ok(post)
+}
+
+fn update_pending_post(
+    conn: &Connection,
+    id: i64,
+    state: &str,
+    decided_by: &str,
+    decided_at: i64,
+) -> Result<bool> {
+    let updated = conn.execute(
+        r#"
+                UPDATE pending_posts SET state = ?2, decided_by = ?3, decided_at = ?4
+                WHERE id = ?1 AND state = 'pending'
+                "#,
+        params![id, state, decided_by, decided_at],
+    )?;
+    Ok(updated > 0)
+}
+
+fn expire_pending_posts(conn: &Connection, now: i64) -> Result<usize> {
+    let expired = conn.execute(
+        r#"
+                UPDATE pending_posts SET state = 'expired', decided_at = ?1
+                WHERE state = 'pending' AND expires_at <= ?1
+                "#,
+        params![now],
+    )?;
+    Ok(expired)
+}
+
+fn query_done_topics(conn: &Connection) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT topic_id FROM topics_done")?;
+    let ids = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+    Ok(ids)
+}
+
+fn insert_topic_done(conn: &Connection, topic_id: &str, done_at: i64) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO topics_done (topic_id, done_at) VALUES (?1, ?2)",
+        params![topic_id, done_at],
     )?;
     Ok(())
 }
@@ -201,7 +1369,7 @@ mod tests {
     async fn returns_none_when_thread_has_no_previous_response() {
         let store = ConversationStore::new(":memory:").unwrap();
 
-        let previous = store.get_previous_response_id("thread-1").await.unwrap();
+        let previous = store.get_previous_response_id("thread-1", 0).await.unwrap();
 
         assert_eq!(previous, None);
     }
@@ -210,21 +1378,25 @@ mod tests {
     async fn upserts_and_reads_previous_response_id() {
         let store = ConversationStore::new(":memory:").unwrap();
 
-        store.upsert_last_response_id("thread-1", "resp-1").await.unwrap();
-        let previous = store.get_previous_response_id("thread-1").await.unwrap();
+        store.upsert_last_response_id("thread-1", "resp-1", "gpt-test").await.unwrap();
+        let previous = store.get_previous_response_id("thread-1", 0).await.unwrap();
 
-        assert_eq!(previous.as_deref(), Some("resp-1"));
+        assert_eq!(
+            previous,
+            Some(PreviousResponse { id: "resp-1".into(), model: Some("gpt-test".into()) })
+        );
     }
 
     #[tokio::test]
     async fn upsert_replaces_existing_response_id_for_thread() {
         let store = ConversationStore::new(":memory:").unwrap();
 
-        store.upsert_last_response_id("thread-1", "resp-1").await.unwrap();
-        store.upsert_last_response_id("thread-1", "resp-2").await.unwrap();
-        let previous = store.get_previous_response_id("thread-1").await.unwrap();
+        store.upsert_last_response_id("thread-1", "resp-1", "gpt-test").await.unwrap();
+        store.upsert_last_response_id("thread-1", "resp-2", "gpt-fallback").await.unwrap();
+        let previous = store.get_previous_response_id("thread-1", 0).await.unwrap().unwrap();
 
-        assert_eq!(previous.as_deref(), Some("resp-2"));
+        assert_eq!(previous.id, "resp-2");
+        assert_eq!(previous.model.as_deref(), Some("gpt-fallback"));
     }
 
     #[tokio::test]
@@ -232,9 +1404,278 @@ mod tests {
         let store = ConversationStore::new(":memory:").unwrap();
         let cloned = store.clone();
 
-        store.upsert_last_response_id("thread-1", "resp-1").await.unwrap();
-        let previous = cloned.get_previous_response_id("thread-1").await.unwrap();
+        store.upsert_last_response_id("thread-1", "resp-1", "gpt-test").await.unwrap();
+        let previous = cloned.get_previous_response_id("thread-1", 0).await.unwrap();
+
+        assert_eq!(previous.map(|p| p.id).as_deref(), Some("resp-1"));
+    }
+
+    #[tokio::test]
+    async fn skips_response_ids_older_than_max_age() {
+        let store = ConversationStore::new(":memory:").unwrap();
+        store.upsert_last_response_id("thread-1", "resp-1", "gpt-test").await.unwrap();
+        let now = unix_timestamp_seconds();
+
+        assert!(store.get_previous_response_id("thread-1", now - 60).await.unwrap().is_some());
+        assert_eq!(store.get_previous_response_id("thread-1", now + 60).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn forget_response_id_keeps_persona() {
+        let store = ConversationStore::new(":memory:").unwrap();
+        store.upsert_last_response_id("thread-1", "resp-1", "gpt-test").await.unwrap();
+        store.set_thread_persona("thread-1", "cheerful").await.unwrap();
+
+        store.forget_response_id("thread-1").await.unwrap();
+
+        assert_eq!(store.get_previous_response_id("thread-1", 0).await.unwrap(), None);
+        assert_eq!(
+            store.get_thread_persona("thread-1").await.unwrap().as_deref(),
+            Some("cheerful")
+        );
+    }
+
+    #[test]
+    fn adds_model_column_to_old_conversations_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+                CREATE TABLE conversations (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    thread_key TEXT NOT NULL UNIQUE,
+                    last_response_id TEXT NOT NULL,
+                    updated_at INTEGER NOT NULL
+                );
+                INSERT INTO conversations (thread_key, last_response_id, updated_at)
+                VALUES ('thread-1', 'resp-old', 0);
+                "#,
+        )
+        .unwrap();
+
+        add_column_if_missing(&conn, "conversations", "last_model", "TEXT").unwrap();
+        add_column_if_missing(&conn, "conversations", "last_model", "TEXT").unwrap();
+
+        let previous = query_previous_response_id(&conn, "thread-1", -1).unwrap().unwrap();
+        assert_eq!(previous, PreviousResponse { id: "resp-old".into(), model: None });
+    }
+
+    #[test]
+    fn previous_response_continues_only_with_the_same_model() {
+        let recorded = PreviousResponse { id: "resp".into(), model: Some("gpt-a".into()) };
+        assert!(recorded.continues_with("gpt-a"));
+        assert!(!recorded.continues_with("gpt-b"));
+
+        // モデルを記録していない古い応答はどのモデルにも渡す
+        let legacy = PreviousResponse { id: "resp".into(), model: None };
+        assert!(legacy.continues_with("gpt-b"));
+    }
+
+    #[tokio::test]
+    async fn stores_persona_per_thread() {
+        let store = ConversationStore::new(":memory:").unwrap();
+
+        assert_eq!(store.get_thread_persona("thread-1").await.unwrap(), None);
+
+        store.set_thread_persona("thread-1", "staff").await.unwrap();
+        store.set_thread_persona("thread-2", "default").await.unwrap();
+        store.set_thread_persona("thread-1", "english").await.unwrap();
+
+        assert_eq!(store.get_thread_persona("thread-1").await.unwrap().as_deref(), Some("english"));
+        assert_eq!(store.get_thread_persona("thread-2").await.unwrap().as_deref(), Some("default"));
+    }
+
+    #[tokio::test]
+    async fn stores_last_run_per_task_name() {
+        let store = ConversationStore::new(":memory:").unwrap();
+
+        assert_eq!(store.get_last_run("free_toot").await.unwrap(), None);
+
+        store.set_last_run("free_toot", 100).await.unwrap();
+        store.set_last_run("free_toot", 200).await.unwrap();
+
+        assert_eq!(store.get_last_run("free_toot").await.unwrap(), Some(200));
+        assert_eq!(store.get_last_run("other").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn returns_recent_free_toots_newest_first() {
+        let store = ConversationStore::new(":memory:").unwrap();
+
+        for text in ["one", "two", "three"] {
+            store.add_free_toot(text).await.unwrap();
+        }
+
+        assert_eq!(store.recent_free_toots(2).await.unwrap(), vec!["three", "two"]);
+        assert!(store.recent_free_toots(0).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn marks_topics_done() {
+        let store = ConversationStore::new(":memory:").unwrap();
+
+        store.mark_topic_done("maint").await.unwrap();
+        store.mark_topic_done("maint").await.unwrap();
+        store.mark_topic_done("weekend").await.unwrap();
+
+        let done = store.done_topics().await.unwrap();
+        assert_eq!(done.len(), 2);
+        assert!(done.contains("maint") && done.contains("weekend"));
+    }
+
+    #[tokio::test]
+    async fn tracks_seen_feed_entries_per_feed() {
+        let store = ConversationStore::new(":memory:").unwrap();
+
+        store.mark_feed_entries_seen("a", vec!["1".into(), "2".into()]).await.unwrap();
+        store.mark_feed_entries_seen("a", vec!["2".into()]).await.unwrap();
+        store.mark_feed_entries_seen("b", vec!["1".into()]).await.unwrap();
+
+        assert_eq!(store.seen_feed_entries("a").await.unwrap().len(), 2);
+        assert_eq!(store.seen_feed_entries("b").await.unwrap().len(), 1);
+        assert!(store.seen_feed_entries("c").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn resolves_pending_posts_once() {
+        let store = ConversationStore::new(":memory:").unwrap();
+
+        let id =
+            store.create_pending_post("draft", Some("maint"), "review-1", 1_000).await.unwrap();
+        let pending = store.find_pending_post("review-1").await.unwrap().unwrap();
+        assert_eq!(
+            pending,
+            PendingPost {
+                id,
+                text: "draft".into(),
+                topic_id: Some("maint".into()),
+                expires_at: 1_000
+            }
+        );
+
+        assert!(store.resolve_pending_post(id, "approved", "mod").await.unwrap());
+        assert!(!store.resolve_pending_post(id, "rejected", "mod").await.unwrap());
+        assert!(store.find_pending_post("review-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn expires_old_pending_posts() {
+        let store = ConversationStore::new(":memory:").unwrap();
+
+        store.create_pending_post("old", None, "review-1", 100).await.unwrap();
+        store.create_pending_post("new", None, "review-2", 300).await.unwrap();
+
+        assert_eq!(store.expire_pending_posts(200).await.unwrap(), 1);
+        assert!(store.find_pending_post("review-1").await.unwrap().is_none());
+        assert!(store.find_pending_post("review-2").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn reset_thread_drops_response_id_and_remembers_reset_status() {
+        let store = ConversationStore::new(":memory:").unwrap();
+        store.upsert_last_response_id("thread-1", "resp_1", "gpt-test").await.unwrap();
+
+        store.reset_thread("thread-1", "status-9").await.unwrap();
+
+        assert_eq!(store.get_previous_response_id("thread-1", 0).await.unwrap(), None);
+        assert_eq!(store.get_thread_reset("thread-1").await.unwrap().as_deref(), Some("status-9"));
+        assert_eq!(store.get_thread_reset("thread-2").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn forget_account_clears_only_that_accounts_threads() {
+        let store = ConversationStore::new(":memory:").unwrap();
+        for (thread, acct) in [("t1", "alice"), ("t2", "alice"), ("t3", "bob")] {
+            store.upsert_last_response_id(thread, "resp", "gpt-test").await.unwrap();
+            store.set_thread_persona(thread, "default").await.unwrap();
+            store.remember_thread_account(thread, acct).await.unwrap();
+        }
+
+        assert_eq!(store.forget_account("alice").await.unwrap(), 2);
+
+        assert_eq!(store.get_previous_response_id("t1", 0).await.unwrap(), None);
+        assert_eq!(store.get_thread_persona("t2").await.unwrap(), None);
+        assert_eq!(
+            store.get_previous_response_id("t3", 0).await.unwrap().map(|p| p.id).as_deref(),
+            Some("resp")
+        );
+        assert_eq!(store.forget_account("alice").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn forget_account_keeps_thread_resets() {
+        let store = ConversationStore::new(":memory:").unwrap();
+        store.remember_thread_account("t1", "alice").await.unwrap();
+        store.reset_thread("t1", "status-9").await.unwrap();
+
+        store.forget_account("alice").await.unwrap();
+
+        assert_eq!(store.get_thread_reset("t1").await.unwrap().as_deref(), Some("status-9"));
+    }
+
+    #[tokio::test]
+    async fn opt_outs_are_tracked_per_source() {
+        let store = ConversationStore::new(":memory:").unwrap();
+
+        store.add_opt_out("alice", "command").await.unwrap();
+        store.add_opt_out("alice", "admin").await.unwrap();
+        store.add_opt_out("alice", "admin").await.unwrap();
+
+        assert_eq!(store.opt_out_sources("alice").await.unwrap(), vec!["admin", "command"]);
+        assert_eq!(store.list_opt_outs("admin").await.unwrap(), vec!["alice"]);
+        assert!(store.remove_opt_out("alice", "command").await.unwrap());
+        assert!(!store.remove_opt_out("alice", "command").await.unwrap());
+        assert_eq!(store.opt_out_sources("alice").await.unwrap(), vec!["admin"]);
+        assert!(store.opt_out_sources("bob").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn nobot_cache_respects_check_time() {
+        let store = ConversationStore::new(":memory:").unwrap();
+        assert_eq!(store.get_cached_nobot("alice", 0).await.unwrap(), None);
+
+        store.cache_nobot("alice", true).await.unwrap();
+
+        assert_eq!(store.get_cached_nobot("alice", 0).await.unwrap(), Some(true));
+        assert_eq!(store.get_cached_nobot("alice", i64::MAX).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn access_notice_is_marked_once() {
+        let store = ConversationStore::new(":memory:").unwrap();
+
+        assert!(store.mark_access_notice("alice").await.unwrap());
+        assert!(!store.mark_access_notice("alice").await.unwrap());
+        assert!(store.mark_access_notice("bob").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn counts_bot_replies_per_thread_and_account() {
+        let store = ConversationStore::new(":memory:").unwrap();
+        store.record_bot_reply("t1", "alice").await.unwrap();
+        store.record_bot_reply("t1", "alice").await.unwrap();
+        store.record_bot_reply("t2", "alice").await.unwrap();
+        store.record_bot_reply("t2", "bob").await.unwrap();
+
+        assert_eq!(store.count_thread_replies("t1").await.unwrap(), 2);
+        assert_eq!(store.count_thread_replies("t3").await.unwrap(), 0);
+        assert_eq!(store.count_account_replies("alice", 0).await.unwrap(), 3);
+        assert_eq!(store.count_account_replies("alice", i64::MAX).await.unwrap(), 0);
+
+        assert!(store.close_thread("t1").await.unwrap());
+        assert!(!store.close_thread("t1").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn reset_thread_reopens_a_closed_thread() {
+        let store = ConversationStore::new(":memory:").unwrap();
+        store.record_bot_reply("t1", "alice").await.unwrap();
+        store.record_bot_reply("t2", "alice").await.unwrap();
+        store.close_thread("t1").await.unwrap();
+
+        store.reset_thread("t1", "status-9").await.unwrap();
 
-        assert_eq!(previous.as_deref(), Some("resp-1"));
+        assert_eq!(store.count_thread_replies("t1").await.unwrap(), 0);
+        assert_eq!(store.count_thread_replies("t2").await.unwrap(), 1);
+        assert!(store.close_thread("t1").await.unwrap());
     }
 }
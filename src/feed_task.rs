@@ -0,0 +1,116 @@
+//! フィードの定期巡回と新着記事の紹介投稿
+
+use anyhow::Result;
+use chrono::Utc;
+use std::{collections::HashSet, sync::Arc};
+use tokio::time::sleep;
+
+use crate::config::BotConfig;
+use crate::conversation_store::ConversationStore;
+use crate::feeds::{Feed, FeedEntry, fetch_feed};
+use crate::openai_api::generate_feed_digest;
+use crate::publisher::publish_status;
+
+pub async fn run_feed_loop(
+    client: &reqwest::Client,
+    config: &BotConfig,
+    conv_store: Arc<ConversationStore>,
+) {
+    loop {
+        for feed_url in &config.feed_urls {
+            if let Err(e) = poll_feed(client, config, &conv_store, feed_url).await {
+                eprintln!("[feed] {feed_url}: {:?}", e);
+            }
+        }
+
+        sleep(config.feed_poll_interval).await;
+    }
+}
+
+async fn poll_feed(
+    client: &reqwest::Client,
+    config: &BotConfig,
+    conv_store: &ConversationStore,
+    feed_url: &str,
+) -> Result<()> {
+    let feed = fetch_feed(client, feed_url).await?;
+    let state_key = feed_state_key(feed_url);
+
+    // 初回は既存の記事を記録するだけ（過去記事をまとめて投稿しない）。
+    // 記事が 0 件のフィードでも次から新着を投稿できるよう、巡回済みの印を別に残す
+    if conv_store.get_last_run(&state_key).await?.is_none() {
+        let ids: Vec<String> = feed.entries.iter().map(|e| e.id.clone()).collect();
+        println!("[feed] {feed_url}: recorded {} existing entries", ids.len());
+        conv_store.mark_feed_entries_seen(feed_url, ids).await?;
+        return conv_store.set_last_run(&state_key, Utc::now().timestamp()).await;
+    }
+
+    let seen = conv_store.seen_feed_entries(feed_url).await?;
+
+    for entry in unseen_entries(&feed, &seen).into_iter().take(config.feed_max_posts_per_poll) {
+        println!("[feed] {feed_url}: new entry {:?}", entry.title);
+        post_entry(client, config, conv_store, &feed, entry).await?;
+        conv_store.mark_feed_entries_seen(feed_url, vec![entry.id.clone()]).await?;
+    }
+
+    Ok(())
+}
+
+async fn post_entry(
+    client: &reqwest::Client,
+    config: &BotConfig,
+    conv_store: &ConversationStore,
+    feed: &Feed,
+    entry: &FeedEntry,
+) -> Result<()> {
+    let text = generate_feed_digest(client, config, &feed.title, entry).await?;
+    println!("[feed] {}", text);
+
+    let link = entry.link.as_deref().filter(|_| config.feed_keep_links);
+    publish_status(client, config, conv_store, &text, link).await
+}
+
+/// 最初の巡回を済ませたかを `scheduler_state` に残すときの名前
+fn feed_state_key(feed_url: &str) -> String {
+    format!("feed:{feed_url}")
+}
+
+/// まだ投稿していない記事を古い順に（フィードは新しい順に並んでいる前提）
+fn unseen_entries<'a>(feed: &'a Feed, seen: &HashSet<String>) -> Vec<&'a FeedEntry> {
+    feed.entries.iter().rev().filter(|e| !seen.contains(&e.id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockHttpServer, test_config};
+
+    fn entry(id: &str) -> FeedEntry {
+        FeedEntry { id: id.into(), title: id.into(), link: None, summary: String::new() }
+    }
+
+    #[tokio::test]
+    async fn first_poll_of_an_empty_feed_marks_it_initialised() {
+        let server = MockHttpServer::respond(
+            "200 OK",
+            r#"<rss version="2.0"><channel><title>t</title></channel></rss>"#,
+        );
+        let store = ConversationStore::new(":memory:").unwrap();
+        let feed_url = server.url("/feed.xml");
+
+        poll_feed(&reqwest::Client::new(), &test_config(), &store, &feed_url).await.unwrap();
+
+        assert!(store.get_last_run(&feed_state_key(&feed_url)).await.unwrap().is_some());
+        assert!(store.seen_feed_entries(&feed_url).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn unseen_entries_are_oldest_first() {
+        let feed = Feed { title: "t".into(), entries: vec![entry("3"), entry("2"), entry("1")] };
+        let seen: HashSet<String> = ["1".to_string()].into();
+
+        let ids: Vec<&str> = unseen_entries(&feed, &seen).iter().map(|e| e.id.as_str()).collect();
+
+        assert_eq!(ids, vec!["2", "3"]);
+    }
+}
@@ -0,0 +1,205 @@
+//! RSS / Atom フィードの取得と最小限のパース
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::Client;
+use roxmltree::{Document, Node};
+
+use crate::util::strip_html;
+
+/// 要約に渡す本文の上限（文字数）
+const MAX_SUMMARY_CHARS: usize = 800;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Feed {
+    pub title: String,
+    /// フィードに書かれている順（たいてい新しい順）
+    pub entries: Vec<FeedEntry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeedEntry {
+    /// 投稿済みの記録に使う ID（guid / id、なければリンクかタイトル）
+    pub id: String,
+    pub title: String,
+    pub link: Option<String>,
+    /// HTML を取り除いた概要
+    pub summary: String,
+}
+
+pub async fn fetch_feed(client: &Client, url: &str) -> Result<Feed> {
+    let body = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("failed to fetch feed {url}"))?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    parse_feed(&body).with_context(|| format!("failed to parse feed {url}"))
+}
+
+/// RSS 2.0 / RSS 1.0 (RDF) / Atom を読む
+pub fn parse_feed(xml: &str) -> Result<Feed> {
+    let doc = Document::parse(xml)?;
+    let root = doc.root_element();
+
+    let (title_parent, entry_name) = match root.tag_name().name() {
+        "feed" => (Some(root), "entry"),
+        "rss" => (child(root, "channel"), "item"),
+        "RDF" => (child(root, "channel"), "item"),
+        other => return Err(anyhow!("unsupported feed root <{other}>")),
+    };
+    let title = title_parent.and_then(|n| child_text(n, "title")).unwrap_or_default();
+
+    let entries = root
+        .descendants()
+        .filter(|n| n.is_element() && n.tag_name().name() == entry_name)
+        .filter_map(parse_entry)
+        .collect();
+
+    Ok(Feed { title, entries })
+}
+
+fn parse_entry(node: Node) -> Option<FeedEntry> {
+    let title = child_text(node, "title").unwrap_or_default();
+    let link = entry_link(node);
+    let summary = ["summary", "description", "content", "encoded"]
+        .iter()
+        .find_map(|name| child_text(node, name))
+        .map(|s| truncate_chars(&strip_html(&s), MAX_SUMMARY_CHARS))
+        .unwrap_or_default();
+
+    let id = child_text(node, "guid")
+        .or_else(|| child_text(node, "id"))
+        .or_else(|| node.attributes().find(|a| a.name() == "about").map(|a| a.value().to_string()))
+        .or_else(|| link.clone())
+        .or_else(|| Some(title.clone()).filter(|t| !t.is_empty()))?;
+
+    Some(FeedEntry { id, title, link, summary })
+}
+
+/// Atom は `<link rel="alternate" href>`、RSS は `<link>` の中身
+fn entry_link(node: Node) -> Option<String> {
+    let links: Vec<Node> =
+        node.children().filter(|n| n.is_element() && n.tag_name().name() == "link").collect();
+
+    let atom = links
+        .iter()
+        .filter(|l| l.attribute("rel").is_none_or(|rel| rel == "alternate"))
+        .find_map(|l| l.attribute("href"));
+    atom.map(str::to_string)
+        .or_else(|| links.iter().find_map(|l| l.text()).map(|t| t.trim().to_string()))
+        .filter(|l| !l.is_empty())
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.is_element() && n.tag_name().name() == name)
+}
+
+/// CDATA や複数のテキストノードもまとめて取る
+fn child_text(node: Node, name: &str) -> Option<String> {
+    let child = child(node, name)?;
+    let text: String =
+        child.descendants().filter(|n| n.is_text()).filter_map(|n| n.text()).collect();
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn truncate_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut out: String = s.chars().take(max).collect();
+    out.push('…');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockHttpServer, closed_local_url};
+
+    const RSS: &str = r#"<?xml version="1.0"?>
+        <rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/">
+          <channel>
+            <title>Instance blog</title>
+            <item>
+              <title>Maintenance on Sunday</title>
+              <link>https://blog.example/maintenance</link>
+              <guid isPermaLink="false">post-2</guid>
+              <description><![CDATA[<p>We will <b>upgrade</b> the server.</p>]]></description>
+            </item>
+            <item>
+              <title>Welcome</title>
+              <link>https://blog.example/welcome</link>
+              <content:encoded>&lt;p&gt;Hello&lt;/p&gt;</content:encoded>
+            </item>
+          </channel>
+        </rss>"#;
+
+    const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+        <feed xmlns="http://www.w3.org/2005/Atom">
+          <title>Project releases</title>
+          <entry>
+            <id>tag:example.org,2025:v1.2.0</id>
+            <title>v1.2.0</title>
+            <link rel="self" href="https://example.org/self"/>
+            <link href="https://example.org/releases/v1.2.0"/>
+            <summary type="html">Bug fixes &amp; speedups</summary>
+          </entry>
+        </feed>"#;
+
+    #[test]
+    fn parses_rss_items() {
+        let feed = parse_feed(RSS).unwrap();
+
+        assert_eq!(feed.title, "Instance blog");
+        assert_eq!(
+            feed.entries[0],
+            FeedEntry {
+                id: "post-2".into(),
+                title: "Maintenance on Sunday".into(),
+                link: Some("https://blog.example/maintenance".into()),
+                summary: "We will upgrade the server.".into(),
+            }
+        );
+        assert_eq!(feed.entries[1].id, "https://blog.example/welcome");
+        assert_eq!(feed.entries[1].summary, "Hello");
+    }
+
+    #[test]
+    fn parses_atom_entries() {
+        let feed = parse_feed(ATOM).unwrap();
+
+        assert_eq!(feed.title, "Project releases");
+        assert_eq!(feed.entries.len(), 1);
+        assert_eq!(feed.entries[0].id, "tag:example.org,2025:v1.2.0");
+        assert_eq!(feed.entries[0].link.as_deref(), Some("https://example.org/releases/v1.2.0"));
+        assert_eq!(feed.entries[0].summary, "Bug fixes & speedups");
+    }
+
+    #[test]
+    fn rejects_non_feed_documents() {
+        assert!(parse_feed("<html><body/></html>").is_err());
+        assert!(parse_feed("not xml").is_err());
+    }
+
+    #[tokio::test]
+    async fn fetches_feed_over_http() {
+        let server = MockHttpServer::respond("200 OK", ATOM);
+
+        let feed = fetch_feed(&Client::new(), &server.url("/releases.atom")).await.unwrap();
+
+        assert_eq!(feed.entries[0].title, "v1.2.0");
+    }
+
+    #[tokio::test]
+    async fn reports_http_errors() {
+        let server = MockHttpServer::respond("404 Not Found", "missing");
+        assert!(fetch_feed(&Client::new(), &server.url("/feed")).await.is_err());
+
+        let err = fetch_feed(&Client::new(), &closed_local_url("/feed")).await.unwrap_err();
+        assert!(err.to_string().contains("failed to fetch feed"));
+    }
+}
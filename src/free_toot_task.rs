@@ -0,0 +1,162 @@
+//! 自由トゥートの定期実行
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use tokio::time::sleep;
+
+use crate::approval::{expire_pending_posts, submit_for_approval};
+use crate::config::BotConfig;
+use crate::conversation_store::ConversationStore;
+use crate::openai_api::generate_free_toot;
+use crate::publisher::publish_status;
+use crate::scheduler::Scheduler;
+use crate::topics::{Topic, load_topics, next_topic};
+
+/// `scheduler_state` に記録するタスク名
+const TASK_NAME: &str = "free_toot";
+
+pub async fn run_free_toot_loop(
+    client: &reqwest::Client,
+    config: &BotConfig,
+    conv_store: Arc<ConversationStore>,
+) {
+    let scheduler = Scheduler::from_config(config);
+
+    loop {
+        let last_run = match conv_store.get_last_run(TASK_NAME).await {
+            Ok(ts) => ts.and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0)),
+            Err(e) => {
+                eprintln!("[free toot] Failed to load last run: {:?}", e);
+                None
+            }
+        };
+
+        let now = Utc::now();
+        let slot = scheduler.next_run(last_run, now);
+        let wake_at = scheduler.with_jitter(slot);
+        println!(
+            "[free toot] Next run at {}",
+            wake_at.with_timezone(&config.timezone).to_rfc3339()
+        );
+        sleep((wake_at - now).to_std().unwrap_or_default()).await;
+
+        if config.free_toot_approval {
+            expire_pending_posts(&conv_store).await;
+        }
+
+        println!("[free toot] Generating…");
+        if let Err(e) = do_free_toot(client, config, &conv_store).await {
+            eprintln!("[free toot] Error: {:?}", e);
+        }
+
+        // 失敗しても記録する（すぐに再試行して連投しないように）。
+        // 遅延や生成にかかった時間を含めると予定が少しずつ後ろにずれるので、予定時刻を記録する
+        if let Err(e) = conv_store.set_last_run(TASK_NAME, slot.timestamp()).await {
+            eprintln!("[free toot] Failed to save last run: {:?}", e);
+        }
+    }
+}
+
+/// 自由トゥートを生成して投稿する（承認制ならモデレーターに確認を頼む）
+pub(crate) async fn do_free_toot(
+    client: &reqwest::Client,
+    config: &BotConfig,
+    conv_store: &ConversationStore,
+) -> Result<()> {
+    let history_len = config.free_toot_history_size.max(config.free_toot_avoid_recent_in_prompt);
+    let history = conv_store.recent_free_toots(history_len).await?;
+
+    let topic = next_queued_topic(config, conv_store).await;
+    if let Some(topic) = &topic {
+        println!("[free toot] using topic {:?}", topic.id);
+    }
+
+    let Some(text) =
+        generate_free_toot(client, config, topic.as_ref().map(|t| t.text.as_str()), &history)
+            .await?
+    else {
+        // 似すぎた下書きしかできなかった（話題は使わずに次回へ回す）
+        return Ok(());
+    };
+
+    println!("[free toot] {}", text);
+
+    let topic_id = topic.as_ref().map(|t| t.id.as_str());
+    if config.free_toot_approval {
+        return submit_for_approval(client, config, conv_store, &text, topic_id).await;
+    }
+
+    finish_free_toot(client, config, conv_store, &text, topic_id).await
+}
+
+/// 投稿して、履歴と使用済みの話題を記録する（`DRY_RUN` なら投稿していないので記録もしない）
+pub(crate) async fn finish_free_toot(
+    client: &reqwest::Client,
+    config: &BotConfig,
+    conv_store: &ConversationStore,
+    text: &str,
+    topic_id: Option<&str>,
+) -> Result<()> {
+    publish_status(client, config, conv_store, text, None).await?;
+    if config.dry_run {
+        return Ok(());
+    }
+
+    if let Some(topic_id) = topic_id
+        && let Err(e) = conv_store.mark_topic_done(topic_id).await
+    {
+        eprintln!("[free toot] Failed to mark topic {:?} done: {:?}", topic_id, e);
+    }
+
+    if let Err(e) = conv_store.add_free_toot(text).await {
+        eprintln!("[free toot] Failed to save history: {:?}", e);
+    }
+
+    Ok(())
+}
+
+/// `TOPICS_PATH` を読み直して未使用の話題を選ぶ。読めなければ自由生成にする
+async fn next_queued_topic(config: &BotConfig, conv_store: &ConversationStore) -> Option<Topic> {
+    let path = config.topics_path.as_deref()?;
+
+    let topics = match load_topics(path) {
+        Ok(topics) => topics,
+        Err(e) => {
+            eprintln!("[free toot] Failed to load topics: {:?}", e);
+            return None;
+        }
+    };
+    let done = match conv_store.done_topics().await {
+        Ok(done) => done,
+        Err(e) => {
+            eprintln!("[free toot] Failed to load done topics: {:?}", e);
+            return None;
+        }
+    };
+
+    let today = Utc::now().with_timezone(&config.timezone).date_naive();
+    next_topic(&topics, today, &done).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{closed_local_url, test_config};
+
+    #[tokio::test]
+    async fn dry_run_keeps_topics_and_history_untouched() {
+        let mut cfg = test_config();
+        cfg.dry_run = true;
+        // 送信しようとすれば接続エラーになる
+        cfg.mastodon_base = closed_local_url("");
+        let store = ConversationStore::new(":memory:").unwrap();
+
+        finish_free_toot(&reqwest::Client::new(), &cfg, &store, "こんにちは", Some("topic-1"))
+            .await
+            .unwrap();
+
+        assert!(store.done_topics().await.unwrap().is_empty());
+        assert!(store.recent_free_toots(10).await.unwrap().is_empty());
+    }
+}
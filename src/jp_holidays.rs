@@ -0,0 +1,171 @@
+//! 日本の祝日（内閣府の「国民の祝日」）をローカルで計算する
+//!
+//! 2007 年以降の制度（振替休日は次の平日、国民の休日あり）に基づく。
+//! 春分・秋分の日は天文計算の近似式を使うので 2099 年まで。
+
+use chrono::{Datelike, NaiveDate, Weekday};
+
+const EQUINOX_FIRST_YEAR: i32 = 1980;
+const SUPPORTED_YEARS: std::ops::RangeInclusive<i32> = 2007..=2099;
+
+/// その日が祝日なら名前を返す（振替休日・国民の休日を含む）
+pub fn holiday_name(date: NaiveDate) -> Option<&'static str> {
+    if !SUPPORTED_YEARS.contains(&date.year()) {
+        return None;
+    }
+    if let Some(name) = national_holiday(date) {
+        return Some(name);
+    }
+    if is_substitute_holiday(date) {
+        return Some("振替休日");
+    }
+    if is_sandwiched_holiday(date) {
+        return Some("国民の休日");
+    }
+    None
+}
+
+/// 法律で日付が決まっている祝日
+fn national_holiday(date: NaiveDate) -> Option<&'static str> {
+    let year = date.year();
+    let (month, day) = (date.month(), date.day());
+
+    // 東京オリンピック・パラリンピックによる移動
+    match (year, month, day) {
+        (2020, 7, 23) | (2021, 7, 22) => return Some("海の日"),
+        (2020, 7, 24) | (2021, 7, 23) => return Some("スポーツの日"),
+        (2020, 8, 10) | (2021, 8, 8) => return Some("山の日"),
+        (2019, 5, 1) => return Some("天皇の即位の日"),
+        (2019, 10, 22) => return Some("即位礼正殿の儀の行われる日"),
+        _ => {}
+    }
+    let olympic_year = matches!(year, 2020 | 2021);
+
+    let name = match (month, day) {
+        (1, 1) => "元日",
+        (1, _) if is_nth_monday(date, 2) => "成人の日",
+        (2, 11) => "建国記念の日",
+        (2, 23) if year >= 2020 => "天皇誕生日",
+        (3, d) if d == spring_equinox_day(year) => "春分の日",
+        (4, 29) => "昭和の日",
+        (5, 3) => "憲法記念日",
+        (5, 4) => "みどりの日",
+        (5, 5) => "こどもの日",
+        (7, _) if !olympic_year && is_nth_monday(date, 3) => "海の日",
+        (8, 11) if year >= 2016 && !olympic_year => "山の日",
+        (9, _) if is_nth_monday(date, 3) => "敬老の日",
+        (9, d) if d == autumn_equinox_day(year) => "秋分の日",
+        (10, _) if !olympic_year && is_nth_monday(date, 2) => {
+            if year >= 2020 {
+                "スポーツの日"
+            } else {
+                "体育の日"
+            }
+        }
+        (11, 3) => "文化の日",
+        (11, 23) => "勤労感謝の日",
+        (12, 23) if year <= 2018 => "天皇誕生日",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// 祝日が日曜なら、その後の最初の祝日でない日が休日になる
+fn is_substitute_holiday(date: NaiveDate) -> bool {
+    let mut day = date;
+    while let Some(prev) = day.pred_opt() {
+        if national_holiday(prev).is_none() {
+            return false;
+        }
+        if prev.weekday() == Weekday::Sun {
+            return true;
+        }
+        day = prev;
+    }
+    false
+}
+
+/// 前日と翌日が祝日の平日は休日になる
+fn is_sandwiched_holiday(date: NaiveDate) -> bool {
+    if date.weekday() == Weekday::Sun {
+        return false;
+    }
+    let (Some(prev), Some(next)) = (date.pred_opt(), date.succ_opt()) else {
+        return false;
+    };
+    national_holiday(prev).is_some() && national_holiday(next).is_some()
+}
+
+fn is_nth_monday(date: NaiveDate, n: u32) -> bool {
+    date.weekday() == Weekday::Mon && (date.day() - 1) / 7 + 1 == n
+}
+
+fn spring_equinox_day(year: i32) -> u32 {
+    equinox_day(20.8431, year)
+}
+
+fn autumn_equinox_day(year: i32) -> u32 {
+    equinox_day(23.2488, year)
+}
+
+fn equinox_day(base: f64, year: i32) -> u32 {
+    let elapsed = year - EQUINOX_FIRST_YEAR;
+    (base + 0.242194 * f64::from(elapsed) - f64::from(elapsed / 4)).floor() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(y: i32, m: u32, d: u32) -> Option<&'static str> {
+        holiday_name(NaiveDate::from_ymd_opt(y, m, d).unwrap())
+    }
+
+    #[test]
+    fn fixed_and_happy_monday_holidays() {
+        assert_eq!(name(2025, 1, 1), Some("元日"));
+        assert_eq!(name(2025, 1, 13), Some("成人の日"));
+        assert_eq!(name(2025, 5, 5), Some("こどもの日"));
+        assert_eq!(name(2025, 7, 21), Some("海の日"));
+        assert_eq!(name(2025, 10, 13), Some("スポーツの日"));
+        assert_eq!(name(2018, 10, 8), Some("体育の日"));
+        assert_eq!(name(2018, 12, 23), Some("天皇誕生日"));
+        assert_eq!(name(2019, 12, 23), None);
+        assert_eq!(name(2025, 1, 2), None);
+    }
+
+    #[test]
+    fn equinox_days() {
+        assert_eq!(name(2025, 3, 20), Some("春分の日"));
+        assert_eq!(name(2024, 9, 22), Some("秋分の日"));
+        assert_eq!(name(2025, 9, 23), Some("秋分の日"));
+        assert_eq!(name(2026, 3, 20), Some("春分の日"));
+    }
+
+    #[test]
+    fn substitute_and_sandwiched_holidays() {
+        // 5/4（日）→ 5/5 も祝日なので 5/6 が振替休日
+        assert_eq!(name(2025, 5, 6), Some("振替休日"));
+        assert_eq!(name(2020, 2, 24), Some("振替休日"));
+        assert_eq!(name(2024, 9, 23), Some("振替休日"));
+        // 敬老の日と秋分の日に挟まれた日
+        assert_eq!(name(2026, 9, 22), Some("国民の休日"));
+        assert_eq!(name(2019, 4, 30), Some("国民の休日"));
+        assert_eq!(name(2019, 5, 2), Some("国民の休日"));
+    }
+
+    #[test]
+    fn olympic_year_moves() {
+        assert_eq!(name(2021, 7, 23), Some("スポーツの日"));
+        assert_eq!(name(2021, 8, 8), Some("山の日"));
+        assert_eq!(name(2021, 8, 9), Some("振替休日"));
+        assert_eq!(name(2021, 10, 11), None);
+        assert_eq!(name(2020, 8, 11), None);
+    }
+
+    #[test]
+    fn unsupported_years_have_no_holidays() {
+        assert_eq!(name(2006, 1, 1), None);
+        assert_eq!(name(2100, 1, 1), None);
+    }
+}
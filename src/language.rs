@@ -0,0 +1,114 @@
+//! 投稿の言語
+//!
+//! Mastodon の言語フィルターに正しく分類されるよう、投稿に `language` を付ける。
+//! 生成した本文の文字種から推定し、推定できなければ返信先の投稿の言語を使う。
+
+/// 文字種から言語を推定する（ISO 639-1）。ラテン文字の言語は見分けられないので None
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let mut counts = ScriptCounts::default();
+    for word in text.split_whitespace() {
+        // メンションや URL は本文の言語と関係ない
+        if word.starts_with('@') || word.starts_with("http://") || word.starts_with("https://") {
+            continue;
+        }
+        word.chars().for_each(|c| counts.add(c));
+    }
+
+    counts.language()
+}
+
+/// 投稿に付ける言語
+pub fn post_language(text: &str, source_language: Option<&str>) -> Option<String> {
+    detect_language(text)
+        .map(str::to_string)
+        .or_else(|| source_language.map(str::trim).filter(|l| !l.is_empty()).map(str::to_string))
+}
+
+#[derive(Default)]
+struct ScriptCounts {
+    kana: usize,
+    han: usize,
+    hangul: usize,
+    cyrillic: usize,
+    greek: usize,
+    thai: usize,
+    arabic: usize,
+    hebrew: usize,
+    latin: usize,
+}
+
+impl ScriptCounts {
+    fn add(&mut self, c: char) {
+        match c {
+            '\u{3040}'..='\u{30ff}' | '\u{ff66}'..='\u{ff9d}' => self.kana += 1,
+            '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' => self.han += 1,
+            '\u{1100}'..='\u{11ff}' | '\u{3130}'..='\u{318f}' | '\u{ac00}'..='\u{d7af}' => {
+                self.hangul += 1
+            }
+            '\u{0400}'..='\u{04ff}' => self.cyrillic += 1,
+            '\u{0370}'..='\u{03ff}' => self.greek += 1,
+            '\u{0e00}'..='\u{0e7f}' => self.thai += 1,
+            '\u{0600}'..='\u{06ff}' => self.arabic += 1,
+            '\u{0590}'..='\u{05ff}' => self.hebrew += 1,
+            c if c.is_alphabetic() && c <= '\u{024f}' => self.latin += 1,
+            _ => {}
+        }
+    }
+
+    fn language(&self) -> Option<&'static str> {
+        // 漢字だけなら中国語、かなが混じれば日本語
+        let cjk = if self.kana > 0 { "ja" } else { "zh" };
+        let candidates = [
+            (self.kana + self.han, Some(cjk)),
+            (self.hangul, Some("ko")),
+            (self.cyrillic, Some("ru")),
+            (self.greek, Some("el")),
+            (self.thai, Some("th")),
+            (self.arabic, Some("ar")),
+            (self.hebrew, Some("he")),
+            (self.latin, None),
+        ];
+
+        // 同数なら先に並べたほうを選ぶ
+        let mut best = (0, None);
+        for candidate in candidates {
+            if candidate.0 > best.0 {
+                best = candidate;
+            }
+        }
+        best.1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_languages_by_script() {
+        assert_eq!(detect_language("こんにちは、今日はいい天気ですね"), Some("ja"));
+        assert_eq!(detect_language("Rust 1.80 がリリースされました"), Some("ja"));
+        assert_eq!(detect_language("今天天气很好"), Some("zh"));
+        assert_eq!(detect_language("안녕하세요"), Some("ko"));
+        assert_eq!(detect_language("Привет, как дела?"), Some("ru"));
+        assert_eq!(detect_language("Hello, how are you?"), None);
+        assert_eq!(detect_language("👍 123"), None);
+    }
+
+    #[test]
+    fn ignores_mentions_and_urls() {
+        assert_eq!(
+            detect_language("@alice ありがとう https://example.com/some/long/path"),
+            Some("ja")
+        );
+        assert_eq!(detect_language("@alice@example.com 감사합니다"), Some("ko"));
+    }
+
+    #[test]
+    fn falls_back_to_source_language() {
+        assert_eq!(post_language("ありがとう", Some("en")).as_deref(), Some("ja"));
+        assert_eq!(post_language("Thanks!", Some("de")).as_deref(), Some("de"));
+        assert_eq!(post_language("Thanks!", Some(" ")), None);
+        assert_eq!(post_language("Thanks!", None), None);
+    }
+}
@@ -1,22 +1,37 @@
+mod approval;
 mod config;
 mod conversation_store;
+mod feed_task;
+mod feeds;
+mod free_toot_task;
+mod jp_holidays;
+mod language;
 mod mastodon;
 mod notification_stream;
 mod openai_api;
+mod opt_out;
+mod publisher;
+mod scheduler;
 #[cfg(test)]
 mod test_support;
+mod topics;
 mod util;
 
 use crate::conversation_store::ConversationStore;
 use anyhow::Result;
 use config::BotConfig;
-use mastodon::post_status;
-use openai_api::generate_free_toot;
 use std::sync::Arc;
-use tokio::time::sleep;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("check-prompts") {
+        check_prompts(args.get(2).cloned());
+    }
+    if args.get(1).map(String::as_str) == Some("deny") {
+        return deny_list(&args[2..]).await;
+    }
+
     let config = BotConfig::from_env()?;
     println!("config = {:?}", config.redacted());
 
@@ -24,6 +39,9 @@ async fn main() -> Result<()> {
     let conv_store = Arc::new(conv_store);
 
     println!("Starting Mastodon GPT bot (streaming mode)…");
+    if config.dry_run {
+        println!("DRY_RUN is enabled: replies and toots are logged, not posted.");
+    }
     println!("Streaming URL base: {}", config.streaming_base_url);
 
     let client = reqwest::Client::new();
@@ -45,32 +63,71 @@ async fn main() -> Result<()> {
         }
     });
 
-    // 2. 1時間ごとに自由トゥート
+    // 2. スケジュールに従って自由トゥート
     let client_free = client.clone();
     let config_free = config.clone();
-    let interval_free = config.free_toot_interval;
+    let conv_store_free = conv_store.clone();
 
     let free_toot_task = tokio::spawn(async move {
-        loop {
-            sleep(interval_free).await;
+        free_toot_task::run_free_toot_loop(&client_free, &config_free, conv_store_free).await;
+    });
 
-            println!("[free toot] Generating…");
-            if let Err(e) = do_free_toot(&client_free, &config_free).await {
-                eprintln!("[free toot] Error: {:?}", e);
-            }
+    // 3. フィードの新着記事を紹介
+    let client_feed = client.clone();
+    let config_feed = config.clone();
+    let conv_store_feed = conv_store.clone();
+
+    let feed_task = tokio::spawn(async move {
+        if config_feed.feed_urls.is_empty() {
+            return;
         }
+        feed_task::run_feed_loop(&client_feed, &config_feed, conv_store_feed).await;
     });
 
-    let _ = tokio::join!(stream_task, free_toot_task);
+    let _ = tokio::join!(stream_task, free_toot_task, feed_task);
     Ok(())
 }
 
-async fn do_free_toot(client: &reqwest::Client, config: &BotConfig) -> Result<()> {
-    let text = generate_free_toot(client, config).await?;
+/// `mast_gpt_bot check-prompts [PATH]`
+///
+/// PATH 省略時は PROMPTS_PATH（.env も参照）を検査する。問題があれば非ゼロで終了
+fn check_prompts(path: Option<String>) -> ! {
+    let _ = dotenvy::from_filename(".env");
+    let path = path.unwrap_or_else(openai_api::prompts_path_from_env);
+
+    let report = openai_api::check_prompts_file(&path);
+    println!("{report}");
+
+    std::process::exit(if report.has_errors() { 1 } else { 0 });
+}
 
-    println!("[free toot] {}", text);
+/// `mast_gpt_bot deny add|remove ACCT` / `mast_gpt_bot deny list`
+///
+/// 管理者の拒否リスト（`opt_outs` の `admin`）を BOT_DB_PATH の DB で編集する
+async fn deny_list(args: &[String]) -> Result<()> {
+    let config = BotConfig::from_env()?;
+    let conv_store = ConversationStore::new(&config.bot_db_path)?;
 
-    post_status(client, config, &text).await?;
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("add"), Some(acct)) => {
+            conv_store.add_opt_out(&opt_out::normalize_acct(acct), opt_out::SOURCE_ADMIN).await?;
+            println!("Added @{} to the deny list", opt_out::normalize_acct(acct));
+        }
+        (Some("remove"), Some(acct)) => {
+            let acct = opt_out::normalize_acct(acct);
+            if conv_store.remove_opt_out(&acct, opt_out::SOURCE_ADMIN).await? {
+                println!("Removed @{acct} from the deny list");
+            } else {
+                println!("@{acct} is not on the deny list");
+            }
+        }
+        (Some("list"), None) => {
+            for acct in conv_store.list_opt_outs(opt_out::SOURCE_ADMIN).await? {
+                println!("@{acct}");
+            }
+        }
+        _ => anyhow::bail!("usage: mast_gpt_bot deny add|remove ACCT | deny list"),
+    }
 
     Ok(())
 }
@@ -1,6 +1,7 @@
 //! Mastodon API まわり（型＋HTTP）
 
 use crate::config::BotConfig;
+use crate::language::{detect_language, post_language};
 use crate::util::fit_for_mastodon_plain;
 use anyhow::{Context, Result, anyhow};
 use reqwest::Client;
@@ -23,16 +24,58 @@ pub struct Status {
     pub id: String,
     pub content: String, // HTML
     pub visibility: String,
-    #[allow(dead_code)]
+    /// CW（なければ空）
+    #[serde(default)]
+    pub spoiler_text: String,
+    #[serde(default)]
+    pub sensitive: bool,
+    #[serde(default)]
+    pub language: Option<String>,
     pub in_reply_to_id: Option<String>,
+    /// ISO 8601
+    #[serde(default)]
+    pub created_at: String,
+    #[serde(default)]
+    pub media_attachments: Vec<MediaAttachment>,
     #[allow(dead_code)]
     pub account: Account,
 }
 
+/// 投稿に添付されたメディア
 #[derive(Debug, Clone, Deserialize)]
+pub struct MediaAttachment {
+    /// image / gifv / video / audio / unknown
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub url: String,
+}
+
+impl Status {
+    /// 添付された画像の URL
+    pub fn image_urls(&self) -> Vec<String> {
+        self.media_attachments.iter().filter(|m| m.kind == "image").map(|m| m.url.clone()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct Account {
+    #[serde(default)]
+    pub id: String,
     pub acct: String,
     pub bot: Option<bool>,
+    /// プロフィール（HTML）
+    #[serde(default)]
+    pub note: String,
+    /// プロフィールの補足情報
+    #[serde(default)]
+    pub fields: Vec<AccountField>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AccountField {
+    pub name: String,
+    /// HTML
+    pub value: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -40,6 +83,19 @@ struct NewStatusReply<'a> {
     status: &'a str,
     in_reply_to_id: &'a str,
     visibility: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spoiler_text: Option<&'a str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    sensitive: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+}
+
+/// 返信に付ける CW
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplyWarning {
+    pub spoiler_text: Option<String>,
+    pub sensitive: bool,
 }
 
 fn statuses_url(base_url: &str) -> String {
@@ -50,6 +106,31 @@ fn status_context_url(base_url: &str, status_id: &str) -> String {
     format!("{}/api/v1/statuses/{}/context", base_url, status_id)
 }
 
+fn account_lookup_url(base_url: &str) -> String {
+    format!("{}/api/v1/accounts/lookup", base_url)
+}
+
+fn account_statuses_url(base_url: &str, account_id: &str) -> String {
+    format!("{}/api/v1/accounts/{}/statuses", base_url, account_id)
+}
+
+fn search_url(base_url: &str) -> String {
+    format!("{}/api/v2/search", base_url)
+}
+
+fn relationships_url(base_url: &str) -> String {
+    format!("{}/api/v1/accounts/relationships", base_url)
+}
+
+fn list_accounts_url(base_url: &str, list_id: &str) -> String {
+    format!("{}/api/v1/lists/{}/accounts", base_url, list_id)
+}
+
+#[derive(Debug, Deserialize)]
+struct Relationship {
+    following: bool,
+}
+
 fn authenticated_status_post(client: &Client, url: &str, token: &str) -> RequestBuilder {
     client.post(url).header(AUTHORIZATION, format!("Bearer {}", token))
 }
@@ -71,38 +152,123 @@ async fn ensure_mastodon_post_success(
     resp: reqwest::Response,
     kind: MastodonPostKind,
 ) -> Result<()> {
+    check_mastodon_post(resp, kind).await.map(|_| ())
+}
+
+async fn check_mastodon_post(
+    resp: reqwest::Response,
+    kind: MastodonPostKind,
+) -> Result<reqwest::Response> {
     if !resp.status().is_success() {
         let status = resp.status();
         let body = resp.text().await.unwrap_or_default();
         return Err(anyhow!(mastodon_post_error_message(kind, status, &body)));
     }
 
-    Ok(())
+    Ok(resp)
 }
 
-fn reply_status_text(reply_to_acct: &str, body: &str) -> String {
+/// 返信で実際に送る本文
+pub fn reply_status_text(reply_to_acct: &str, body: &str) -> String {
     format!("@{} {}", reply_to_acct, body)
 }
 
-fn new_status_reply<'a>(status_text: &'a str, reply_to: &'a Status) -> NewStatusReply<'a> {
+fn new_status_reply<'a>(
+    status_text: &'a str,
+    reply_to: &'a Status,
+    warning: &'a ReplyWarning,
+) -> NewStatusReply<'a> {
     NewStatusReply {
         status: status_text,
         in_reply_to_id: &reply_to.id,
         visibility: &reply_to.visibility,
+        spoiler_text: warning.spoiler_text.as_deref(),
+        sensitive: warning.sensitive,
+        language: post_language(status_text, reply_to.language.as_deref()),
+    }
+}
+
+/// Mastodon は URL を長さにかかわらずこの文字数として数える
+const MASTODON_URL_CHARS: usize = 23;
+
+/// Mastodon の数え方での文字数（URL は `MASTODON_URL_CHARS` 文字）
+fn mastodon_char_count(text: &str) -> usize {
+    let urls: Vec<&str> = text
+        .split_whitespace()
+        .filter(|w| w.starts_with("http://") || w.starts_with("https://"))
+        .collect();
+    let url_chars: usize = urls.iter().map(|u| u.chars().count()).sum();
+
+    text.chars().count() - url_chars + urls.len() * MASTODON_URL_CHARS
+}
+
+/// 返信の本文の末尾に出典の URL を 1 行ずつ付ける（`limit` に収まらない URL は付けない）
+pub fn reply_body_with_links(
+    limit: usize,
+    reply_to_acct: &str,
+    body: &str,
+    links: &[String],
+) -> String {
+    let mut out = body.to_string();
+    for link in links {
+        if out.contains(link.as_str()) {
+            continue;
+        }
+        let candidate = format!("{out}\n{link}");
+        if mastodon_char_count(&reply_status_text(reply_to_acct, &candidate)) <= limit {
+            out = candidate;
+        }
     }
+    out
 }
 
 fn post_status_form(cfg: &BotConfig, text: &str) -> Result<serde_json::Value> {
-    let visibility_str = cfg.visibility.to_string();
-    let status = fit_for_mastodon_plain(text.trim(), cfg.mastodon_char_limit);
+    post_status_form_with_link(cfg, text, None)
+}
+
+/// 通常投稿で実際に送る本文
+///
+/// 本文はリンクをドメイン表記にして収め、`link` はそのまま末尾に付ける
+pub fn status_text_for_post(cfg: &BotConfig, text: &str, link: Option<&str>) -> Result<String> {
+    let link_chars = link.map_or(0, |_| MASTODON_URL_CHARS + 1);
+    let limit = cfg.mastodon_char_limit.saturating_sub(link_chars);
+    let status = fit_for_mastodon_plain(text.trim(), limit);
     if status.is_empty() {
         return Err(anyhow!("post_status: empty after fit"));
     }
 
-    Ok(json!({
+    Ok(match link {
+        Some(link) => format!("{status}\n{link}"),
+        None => status,
+    })
+}
+
+fn post_status_form_with_link(
+    cfg: &BotConfig,
+    text: &str,
+    link: Option<&str>,
+) -> Result<serde_json::Value> {
+    let visibility_str = cfg.visibility.to_string();
+    let status = status_text_for_post(cfg, text, link)?;
+
+    Ok(with_language(json!({
         "status": status,
         "visibility": visibility_str,
-    }))
+    })))
+}
+
+/// 本文から推定できれば `language` を付ける
+fn with_language(mut form: serde_json::Value) -> serde_json::Value {
+    let language = form["status"].as_str().and_then(detect_language);
+    if let Some(language) = language {
+        form["language"] = json!(language);
+    }
+    form
+}
+
+#[derive(Debug, Deserialize)]
+struct CreatedStatus {
+    id: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -126,6 +292,122 @@ pub async fn fetch_status_context(
     Ok(ctx)
 }
 
+/// acct（`user` / `user@example.com`）からアカウント情報を取得
+pub async fn lookup_account(
+    client: &Client,
+    base_url: &str,
+    access_token: &str,
+    acct: &str,
+) -> Result<Account> {
+    let url = account_lookup_url(base_url);
+    let resp = client
+        .get(&url)
+        .query(&[("acct", acct)])
+        .bearer_auth(access_token)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(resp.json().await?)
+}
+
+/// アカウントの最近の投稿（新しい順、ブーストは除く）
+pub async fn fetch_account_statuses(
+    client: &Client,
+    base_url: &str,
+    access_token: &str,
+    account_id: &str,
+    limit: usize,
+) -> Result<Vec<Status>> {
+    let url = account_statuses_url(base_url, account_id);
+    let resp = client
+        .get(&url)
+        .query(&[("limit", limit.to_string()), ("exclude_reblogs", "true".to_string())])
+        .bearer_auth(access_token)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(resp.json().await?)
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResults {
+    #[serde(default)]
+    statuses: Vec<Status>,
+}
+
+/// 投稿の URL から投稿を取得（他のインスタンスの投稿は取り込んでから返す）
+pub async fn resolve_status_url(
+    client: &Client,
+    base_url: &str,
+    access_token: &str,
+    status_url: &str,
+) -> Result<Option<Status>> {
+    let url = search_url(base_url);
+    let resp = client
+        .get(&url)
+        .query(&[("q", status_url), ("type", "statuses"), ("resolve", "true"), ("limit", "1")])
+        .bearer_auth(access_token)
+        .send()
+        .await?
+        .error_for_status()?;
+    let results: SearchResults = resp.json().await?;
+
+    Ok(results.statuses.into_iter().next())
+}
+
+/// トークンの持ち主（bot 自身）のアカウント
+pub async fn verify_credentials(
+    client: &Client,
+    base_url: &str,
+    access_token: &str,
+) -> Result<Account> {
+    let url = format!("{}/api/v1/accounts/verify_credentials", base_url);
+    let resp = client.get(&url).bearer_auth(access_token).send().await?.error_for_status()?;
+
+    Ok(resp.json().await?)
+}
+
+/// bot がそのアカウントをフォローしているか
+pub async fn is_following(
+    client: &Client,
+    base_url: &str,
+    access_token: &str,
+    account_id: &str,
+) -> Result<bool> {
+    let url = relationships_url(base_url);
+    let resp = client
+        .get(&url)
+        .query(&[("id[]", account_id)])
+        .bearer_auth(access_token)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let relationships: Vec<Relationship> = resp.json().await?;
+    Ok(relationships.first().is_some_and(|r| r.following))
+}
+
+/// リストに入っているアカウント（`limit=0` でページングせずに全件）
+pub async fn fetch_list_accounts(
+    client: &Client,
+    base_url: &str,
+    access_token: &str,
+    list_id: &str,
+) -> Result<Vec<Account>> {
+    let url = list_accounts_url(base_url, list_id);
+    let resp = client
+        .get(&url)
+        .query(&[("limit", "0")])
+        .bearer_auth(access_token)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(resp.json().await?)
+}
+
 /// 返信を投稿
 pub async fn post_reply(
     client: &Client,
@@ -134,10 +416,11 @@ pub async fn post_reply(
     reply_to: &Status,
     reply_to_acct: &str,
     body: &str,
+    warning: &ReplyWarning,
 ) -> Result<()> {
     let url = statuses_url(base_url);
     let status_text = reply_status_text(reply_to_acct, body);
-    let new_status = new_status_reply(&status_text, reply_to);
+    let new_status = new_status_reply(&status_text, reply_to, warning);
 
     let resp = authenticated_status_post(client, &url, token)
         .json(&new_status)
@@ -150,8 +433,41 @@ pub async fn post_reply(
 
 /// 自由ポスト（返信じゃない普通のトゥート）を投稿
 pub async fn post_status(client: &Client, cfg: &BotConfig, text: &str) -> Result<()> {
+    send_status_form(client, cfg, post_status_form(cfg, text)?).await
+}
+
+/// 末尾にリンクを残したまま投稿（リンクは 23 文字として上限を計算する）
+pub async fn post_status_with_link(
+    client: &Client,
+    cfg: &BotConfig,
+    text: &str,
+    link: &str,
+) -> Result<()> {
+    send_status_form(client, cfg, post_status_form_with_link(cfg, text, Some(link))?).await
+}
+
+/// 宛先のアカウントだけに見える（direct）投稿をして、その status id を返す
+///
+/// 本文は整形しない（宛先の `@acct` や候補の文面をそのまま送る）
+pub async fn post_direct_status(client: &Client, cfg: &BotConfig, text: &str) -> Result<String> {
+    let url = statuses_url(&cfg.mastodon_base);
+    let form = with_language(json!({
+        "status": text.trim(),
+        "visibility": "direct",
+    }));
+
+    let resp = authenticated_status_post(client, &url, &cfg.mastodon_access_token)
+        .form(&form)
+        .send()
+        .await?;
+    let created: CreatedStatus =
+        check_mastodon_post(resp, MastodonPostKind::Status).await?.json().await?;
+
+    Ok(created.id)
+}
+
+async fn send_status_form(client: &Client, cfg: &BotConfig, form: serde_json::Value) -> Result<()> {
     let url = statuses_url(&cfg.mastodon_base);
-    let form = post_status_form(cfg, text)?;
 
     let resp = authenticated_status_post(client, &url, &cfg.mastodon_access_token)
         .form(&form)
@@ -164,7 +480,7 @@ pub async fn post_status(client: &Client, cfg: &BotConfig, text: &str) -> Result
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_support::test_config;
+    use crate::test_support::{test_config, test_status};
 
     #[test]
     fn statuses_url_appends_statuses_endpoint_to_base_url() {
@@ -182,6 +498,55 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn post_direct_status_returns_created_status_id() {
+        let server = crate::test_support::MockHttpServer::respond("200 OK", r#"{"id":"109"}"#);
+        let mut cfg = test_config();
+        cfg.mastodon_base = server.base_url().to_string();
+
+        let id = post_direct_status(&Client::new(), &cfg, "@mod hello").await.unwrap();
+
+        assert_eq!(id, "109");
+    }
+
+    #[test]
+    fn post_status_form_keeps_link_and_reserves_url_length() {
+        let mut cfg = test_config();
+        cfg.mastodon_char_limit = 40;
+        let link = "https://blog.example/a/very/long/path/that/is/longer/than/twenty-three";
+
+        let form = post_status_form_with_link(
+            &cfg,
+            "あいうえおかきくけこさしすせそたちつてと",
+            Some(link),
+        )
+        .unwrap();
+        let status = form["status"].as_str().unwrap();
+
+        let (body, tail) = status.split_once('\n').unwrap();
+        assert_eq!(tail, link);
+        assert!(body.chars().count() <= 40 - 24, "{body}");
+        assert!(body.ends_with('…'));
+    }
+
+    #[test]
+    fn reply_links_are_budgeted_as_url_length() {
+        let links = vec![
+            format!("https://blog.rust-lang.org/{}", "a".repeat(100)),
+            "https://example.com/b".to_string(),
+        ];
+
+        // "@alice " (7) + "hi" (2) + 改行と URL 2 本 (24 * 2) = 57
+        assert_eq!(
+            reply_body_with_links(57, "alice", "hi", &links),
+            format!("hi\n{}\n{}", links[0], links[1])
+        );
+        assert_eq!(reply_body_with_links(56, "alice", "hi", &links), format!("hi\n{}", links[0]));
+        assert_eq!(reply_body_with_links(20, "alice", "hi", &links), "hi");
+        // 本文にすでにある URL は重ねない
+        assert_eq!(reply_body_with_links(500, "alice", &links[1], &links[1..]), links[1]);
+    }
+
     #[test]
     fn post_status_form_preserves_visibility_and_fits_status() {
         let cfg = test_config();
@@ -190,23 +555,67 @@ mod tests {
 
         assert_eq!(form["status"], "hello");
         assert_eq!(form["visibility"], "unlisted");
+        assert!(form.get("language").is_none());
+    }
+
+    #[test]
+    fn post_status_form_sets_detected_language() {
+        let form = post_status_form(&test_config(), "今日はいい天気").unwrap();
+
+        assert_eq!(form["language"], "ja");
     }
 
     #[test]
     fn new_status_reply_mentions_account_and_uses_reply_visibility() {
-        let reply_to = Status {
-            id: "status-1".to_string(),
-            content: "<p>hello</p>".to_string(),
-            visibility: "private".to_string(),
-            in_reply_to_id: None,
-            account: Account { acct: "alice".to_string(), bot: Some(false) },
-        };
+        let reply_to = Status { visibility: "private".into(), ..test_status("status-1", "alice") };
         let status_text = reply_status_text("alice", "thanks");
-        let new_status = new_status_reply(&status_text, &reply_to);
+        let warning = ReplyWarning::default();
+        let new_status = new_status_reply(&status_text, &reply_to, &warning);
 
         assert_eq!(new_status.status, "@alice thanks");
         assert_eq!(new_status.in_reply_to_id, "status-1");
         assert_eq!(new_status.visibility, "private");
+        // 英語は推定できないので元の投稿の言語（なし）
+        assert_eq!(new_status.language, None);
+        let json = serde_json::to_value(&new_status).unwrap();
+        assert!(json.get("spoiler_text").is_none());
+        assert!(json.get("sensitive").is_none());
+    }
+
+    #[test]
+    fn new_status_reply_carries_content_warning() {
+        let reply_to = Status {
+            spoiler_text: "ネタバレ".into(),
+            sensitive: true,
+            ..test_status("status-1", "alice")
+        };
+        let warning = ReplyWarning { spoiler_text: Some("ネタバレ".into()), sensitive: true };
+        let status_text = reply_status_text("alice", "thanks");
+
+        let json =
+            serde_json::to_value(new_status_reply(&status_text, &reply_to, &warning)).unwrap();
+
+        assert_eq!(json["spoiler_text"], "ネタバレ");
+        assert_eq!(json["sensitive"], true);
+    }
+
+    #[test]
+    fn new_status_reply_sets_language_from_text_or_source() {
+        let reply_to = Status {
+            content: "<p>hallo</p>".into(),
+            language: Some("de".into()),
+            ..test_status("status-1", "alice")
+        };
+        let warning = ReplyWarning::default();
+
+        let german = reply_status_text("alice", "danke");
+        assert_eq!(new_status_reply(&german, &reply_to, &warning).language.as_deref(), Some("de"));
+
+        let japanese = reply_status_text("alice", "ありがとう");
+        assert_eq!(
+            new_status_reply(&japanese, &reply_to, &warning).language.as_deref(),
+            Some("ja")
+        );
     }
 
     #[test]
@@ -0,0 +1,229 @@
+//! `ACCESS_MODE` による返信対象の判定
+//!
+//! OpenAI を呼ぶ前に、メンションの送信者が返信の対象かどうかを決める。
+
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+use crate::config::{AccessMode, BotConfig};
+use crate::conversation_store::ConversationStore;
+use crate::mastodon::{Account, Status, fetch_list_accounts, is_following};
+use crate::opt_out::normalize_acct;
+use crate::publisher::publish_reply;
+
+/// 読んだ時刻と acct
+type CachedAllowlist = Option<(Instant, HashSet<String>)>;
+
+// Mastodon のリストから読んだ許可リスト
+static LIST_ALLOWLIST: Lazy<Mutex<CachedAllowlist>> = Lazy::new(|| Mutex::new(None));
+
+/// 返信の対象か。確かめられなかったときは対象外にする
+pub(super) async fn is_allowed(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    account: &Account,
+) -> bool {
+    match cfg.access_mode {
+        AccessMode::Open => true,
+        AccessMode::Local => is_local_acct(&account.acct),
+        AccessMode::Following => {
+            match is_following(client, &cfg.mastodon_base, &cfg.mastodon_access_token, &account.id)
+                .await
+            {
+                Ok(following) => following,
+                Err(e) => {
+                    eprintln!(
+                        "[access] Failed to check relationship with @{}: {:?}",
+                        account.acct, e
+                    );
+                    false
+                }
+            }
+        }
+        AccessMode::Allowlist => {
+            let acct = normalize_acct(&account.acct);
+            file_allowlist(cfg).contains(&acct) || list_allowlist(client, cfg).await.contains(&acct)
+        }
+    }
+}
+
+/// 対象外のアカウントに、まだなら 1 度だけお知らせを返信する
+pub(super) async fn notify_denied(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    status: &Status,
+    acct: &str,
+    notice: &str,
+) -> anyhow::Result<()> {
+    if !cfg.access_denied_notice || notice.trim().is_empty() {
+        return Ok(());
+    }
+    if !conv_store.mark_access_notice(&normalize_acct(acct)).await? {
+        return Ok(());
+    }
+
+    publish_reply(client, cfg, conv_store, status, acct, notice).await
+}
+
+/// ローカルのアカウントの acct にはドメインが付かない
+fn is_local_acct(acct: &str) -> bool {
+    !acct.trim_start_matches('@').contains('@')
+}
+
+/// `ALLOWLIST_PATH` を読む（毎回読み直すので、書き換えればすぐ反映される）
+fn file_allowlist(cfg: &BotConfig) -> HashSet<String> {
+    let Some(path) = cfg.allowlist_path.as_deref() else {
+        return HashSet::new();
+    };
+
+    match std::fs::read_to_string(path) {
+        Ok(text) => parse_allowlist(&text, &instance_domain(&cfg.mastodon_base)),
+        Err(e) => {
+            eprintln!("[access] Failed to read allowlist {path}: {:?}", e);
+            HashSet::new()
+        }
+    }
+}
+
+/// `ALLOWLIST_LIST_ID` のリストを `ALLOWLIST_REFRESH_SECS` ごとに読み直す
+async fn list_allowlist(client: &reqwest::Client, cfg: &BotConfig) -> HashSet<String> {
+    let Some(list_id) = cfg.allowlist_list_id.as_deref() else {
+        return HashSet::new();
+    };
+
+    let mut guard = LIST_ALLOWLIST.lock().await;
+    if let Some((loaded_at, accts)) = guard.as_ref()
+        && loaded_at.elapsed() < cfg.allowlist_refresh
+    {
+        return accts.clone();
+    }
+
+    match fetch_list_accounts(client, &cfg.mastodon_base, &cfg.mastodon_access_token, list_id).await
+    {
+        Ok(accounts) => {
+            let accts: HashSet<String> = accounts.iter().map(|a| normalize_acct(&a.acct)).collect();
+            *guard = Some((Instant::now(), accts.clone()));
+            accts
+        }
+        Err(e) => {
+            // 読めなければ前回の内容を使い続ける
+            eprintln!("[access] Failed to fetch list {list_id}: {:?}", e);
+            guard.as_ref().map(|(_, accts)| accts.clone()).unwrap_or_default()
+        }
+    }
+}
+
+/// 1 行に 1 アカウント。`#` 以降はコメント。自インスタンスのドメインは外す
+fn parse_allowlist(text: &str, local_domain: &str) -> HashSet<String> {
+    let local_suffix = format!("@{}", local_domain.to_lowercase());
+    text.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .map(normalize_acct)
+        .filter(|acct| !acct.is_empty())
+        .map(|acct| acct.strip_suffix(&local_suffix).map(str::to_string).unwrap_or(acct))
+        .collect()
+}
+
+fn instance_domain(base_url: &str) -> String {
+    let without_scheme = base_url.split_once("://").map_or(base_url, |(_, rest)| rest);
+    without_scheme.split('/').next().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockHttpServer, closed_local_url, test_config, test_status};
+
+    fn account(id: &str, acct: &str) -> Account {
+        Account { id: id.into(), acct: acct.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn parses_allowlist_file() {
+        let accts = parse_allowlist(
+            "# staff\n@Alice\nbob@mastodon.example  # local\ncarol@other.example\n\n",
+            "mastodon.example",
+        );
+
+        let expected: HashSet<String> =
+            ["alice", "bob", "carol@other.example"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(accts, expected);
+    }
+
+    #[test]
+    fn instance_domain_is_taken_from_base_url() {
+        assert_eq!(instance_domain("https://mastodon.example"), "mastodon.example");
+        assert_eq!(instance_domain("https://mastodon.example:8443/"), "mastodon.example:8443");
+    }
+
+    #[tokio::test]
+    async fn local_mode_allows_only_local_accounts() {
+        let mut cfg = test_config();
+        cfg.access_mode = AccessMode::Local;
+        let client = reqwest::Client::new();
+
+        assert!(is_allowed(&client, &cfg, &account("1", "alice")).await);
+        assert!(!is_allowed(&client, &cfg, &account("2", "bob@other.example")).await);
+    }
+
+    #[tokio::test]
+    async fn following_mode_checks_relationship() {
+        let server = MockHttpServer::respond("200 OK", r#"[{"id":"1","following":true}]"#);
+        let mut cfg = test_config();
+        cfg.access_mode = AccessMode::Following;
+        cfg.mastodon_base = server.base_url().to_string();
+        let client = reqwest::Client::new();
+
+        assert!(is_allowed(&client, &cfg, &account("1", "alice")).await);
+
+        // 確かめられなければ対象外
+        cfg.mastodon_base = closed_local_url("");
+        assert!(!is_allowed(&client, &cfg, &account("1", "alice")).await);
+    }
+
+    #[tokio::test]
+    async fn allowlist_mode_reads_file() {
+        let path =
+            std::env::temp_dir().join(format!("mast_gpt_bot_allowlist_{}", std::process::id()));
+        std::fs::write(&path, "alice\n").unwrap();
+        let mut cfg = test_config();
+        cfg.access_mode = AccessMode::Allowlist;
+        cfg.allowlist_path = Some(path.display().to_string());
+        let client = reqwest::Client::new();
+
+        assert!(is_allowed(&client, &cfg, &account("1", "Alice")).await);
+        assert!(!is_allowed(&client, &cfg, &account("2", "bob")).await);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn allowlist_mode_reads_mastodon_list() {
+        let server = MockHttpServer::respond("200 OK", r#"[{"id":"1","acct":"alice"}]"#);
+        let mut cfg = test_config();
+        cfg.access_mode = AccessMode::Allowlist;
+        cfg.allowlist_list_id = Some("42".into());
+        cfg.mastodon_base = server.base_url().to_string();
+        let client = reqwest::Client::new();
+
+        assert!(is_allowed(&client, &cfg, &account("1", "alice")).await);
+        // 2 回目は読み直さずに前回の内容を使う
+        assert!(!is_allowed(&client, &cfg, &account("2", "bob")).await);
+    }
+
+    #[tokio::test]
+    async fn notice_is_sent_once() {
+        let mut cfg = test_config();
+        cfg.access_denied_notice = true;
+        cfg.dry_run = true;
+        let store = ConversationStore::new(":memory:").unwrap();
+        let client = reqwest::Client::new();
+        let status = Status { account: account("2", "bob"), ..test_status("s1", "bob") };
+
+        notify_denied(&client, &cfg, &store, &status, "bob", "sorry").await.unwrap();
+
+        assert!(!store.mark_access_notice("bob").await.unwrap());
+    }
+}
@@ -0,0 +1,175 @@
+//! メンションのコマンド（`/reset` `/help` `/forget me` `/persona 名前` `/optout` `/optin`）
+//!
+//! モデルは呼ばず、prompts の `commands` の文面で返信する。
+
+use anyhow::Result;
+
+use crate::config::BotConfig;
+use crate::conversation_store::ConversationStore;
+use crate::mastodon::Status;
+use crate::openai_api::{CommandConfig, command_config, is_known_persona, persona_names};
+use crate::opt_out::{SOURCE_ADMIN, SOURCE_COMMAND, find_nobot_tag, normalize_acct};
+use crate::publisher::publish_reply;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Command {
+    Reset,
+    Help,
+    Forget,
+    Persona(Option<String>),
+    OptOut,
+    OptIn,
+}
+
+/// メンション本文（プレーンテキスト）の先頭の語がコマンドなら取り出す
+pub(crate) fn parse_command(plain: &str, commands: &CommandConfig) -> Option<Command> {
+    let mut words = plain.split_whitespace().skip_while(|w| w.starts_with('@'));
+    let head = words.next()?;
+    let arg = words.next().map(str::to_string);
+
+    if commands.reset.matches(head) {
+        Some(Command::Reset)
+    } else if commands.help.matches(head) {
+        Some(Command::Help)
+    } else if commands.forget.matches(head) {
+        Some(Command::Forget)
+    } else if commands.persona.matches(head) {
+        Some(Command::Persona(arg))
+    } else if commands.optout.matches(head) {
+        Some(Command::OptOut)
+    } else if commands.optin.matches(head) {
+        Some(Command::OptIn)
+    } else {
+        None
+    }
+}
+
+/// コマンドを実行して、確認の返信をする
+pub(crate) async fn run_command(
+    client: &reqwest::Client,
+    config: &BotConfig,
+    conv_store: &ConversationStore,
+    status: &Status,
+    acct: &str,
+    thread_key: &str,
+    command: Command,
+) -> Result<()> {
+    let commands = command_config();
+    println!("  -> command {:?} in thread {}", command, thread_key);
+
+    let reply = match command {
+        Command::Reset => {
+            conv_store.reset_thread(thread_key, &status.id).await?;
+            commands.reset.reply.clone()
+        }
+        Command::Help => help_reply(commands),
+        Command::Forget => {
+            let forgotten = conv_store.forget_account(acct).await?;
+            commands.forget.reply.replace("{{COUNT}}", &forgotten.to_string())
+        }
+        Command::Persona(Some(name)) if is_known_persona(&name) => {
+            conv_store.set_thread_persona(thread_key, &name).await?;
+            commands.persona.reply.replace("{{PERSONA}}", &name)
+        }
+        Command::Persona(_) => {
+            commands.persona_unknown.replace("{{PERSONAS}}", &persona_names().join(", "))
+        }
+        Command::OptOut => {
+            conv_store.add_opt_out(&normalize_acct(acct), SOURCE_COMMAND).await?;
+            commands.optout.reply.clone()
+        }
+        Command::OptIn => {
+            let normalized = normalize_acct(acct);
+            conv_store.remove_opt_out(&normalized, SOURCE_COMMAND).await?;
+            let sources = conv_store.opt_out_sources(&normalized).await?;
+            // メンションに載っている今のプロフィールで確かめ、タグを外した直後でも効くようにキャッシュも直す
+            let nobot_tag = find_nobot_tag(&status.account, &config.nobot_tags);
+            if let Err(e) = conv_store.cache_nobot(&normalized, nobot_tag.is_some()).await {
+                eprintln!("[opt-out] Failed to cache profile for @{normalized}: {:?}", e);
+            }
+            optin_reply(commands, &sources, nobot_tag)
+        }
+    };
+
+    publish_reply(client, config, conv_store, status, acct, &reply).await
+}
+
+/// `/optin` の返信。ほかの理由でまだ返信できないなら、その理由を伝える
+fn optin_reply(commands: &CommandConfig, sources: &[String], nobot_tag: Option<&str>) -> String {
+    let mut reasons = Vec::new();
+    if sources.iter().any(|s| s == SOURCE_ADMIN) {
+        reasons.push("管理者の拒否リスト".to_string());
+    }
+    if let Some(tag) = nobot_tag {
+        reasons.push(format!("プロフィールの {tag}"));
+    }
+
+    if reasons.is_empty() {
+        commands.optin.reply.clone()
+    } else {
+        commands.optin_blocked.replace("{{REASONS}}", &reasons.join("・"))
+    }
+}
+
+fn help_reply(commands: &CommandConfig) -> String {
+    let lines: Vec<String> = [
+        &commands.reset,
+        &commands.help,
+        &commands.forget,
+        &commands.persona,
+        &commands.optout,
+        &commands.optin,
+    ]
+    .into_iter()
+    .filter(|def| !def.aliases.is_empty())
+    .map(|def| format!("{} … {}", def.aliases.join(" / "), def.description).trim().to_string())
+    .collect();
+
+    commands.help.reply.replace("{{COMMANDS}}", &lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands_after_mentions() {
+        let commands = CommandConfig::default();
+
+        assert_eq!(parse_command("@bot /reset", &commands), Some(Command::Reset));
+        assert_eq!(parse_command("@bot@example.com /HELP", &commands), Some(Command::Help));
+        assert_eq!(parse_command("@bot /forget me", &commands), Some(Command::Forget));
+        assert_eq!(
+            parse_command("@bot /persona cat", &commands),
+            Some(Command::Persona(Some("cat".into())))
+        );
+        assert_eq!(parse_command("@bot /persona", &commands), Some(Command::Persona(None)));
+        assert_eq!(parse_command("@bot /OptOut", &commands), Some(Command::OptOut));
+        assert_eq!(parse_command("@bot /optin", &commands), Some(Command::OptIn));
+        assert_eq!(parse_command("@bot what is /reset?", &commands), None);
+        assert_eq!(parse_command("@bot", &commands), None);
+    }
+
+    #[test]
+    fn optin_reply_names_what_still_blocks_the_account() {
+        let commands = CommandConfig::default();
+
+        assert_eq!(optin_reply(&commands, &[], None), commands.optin.reply);
+        assert_eq!(
+            optin_reply(&commands, &[SOURCE_ADMIN.to_string()], Some("#nobot")),
+            "/optout は取り消しましたが、まだ返信できません（理由: 管理者の拒否リスト・プロフィールの #nobot）。"
+        );
+    }
+
+    #[test]
+    fn help_lists_enabled_commands() {
+        let mut commands = CommandConfig::default();
+        commands.forget.aliases.clear();
+
+        let help = help_reply(&commands);
+
+        assert!(help.starts_with("使えるコマンド:\n/reset / /リセット … "), "{help}");
+        assert!(help.contains("/persona … "));
+        assert!(!help.contains("/forget"));
+    }
+}
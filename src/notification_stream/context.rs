@@ -1,6 +1,13 @@
 use crate::mastodon::{Status, StatusContext};
 use crate::util::strip_html;
 
+/// `status_id` までの ancestors を捨てる（見つからなければそのまま）
+pub fn drop_ancestors_through(ctx: &mut StatusContext, status_id: &str) {
+    if let Some(pos) = ctx.ancestors.iter().position(|s| s.id == status_id) {
+        ctx.ancestors.drain(..=pos);
+    }
+}
+
 pub fn format_conversation_context(ctx: &StatusContext, current: &Status) -> String {
     let ancestors = &ctx.ancestors;
 
@@ -10,17 +17,52 @@ pub fn format_conversation_context(ctx: &StatusContext, current: &Status) -> Str
 
     let mut lines = Vec::new();
 
-    for s in &ancestors[start..] {
-        let text = strip_html(&s.content);
+    for s in ancestors[start..].iter().chain(std::iter::once(current)) {
+        let text = status_line(s);
         if !text.is_empty() {
             lines.push(text);
         }
     }
 
-    let current_text = strip_html(&current.content);
-    if !current_text.is_empty() {
-        lines.push(current_text);
+    lines.into_iter().map(|t| format!("- {}", t)).collect::<Vec<_>>().join("\n")
+}
+
+/// CW 付きの投稿は、CW があることをモデルに分かるようにする
+fn status_line(status: &Status) -> String {
+    let text = strip_html(&status.content);
+    let spoiler = status.spoiler_text.trim();
+    if text.is_empty() || spoiler.is_empty() { text } else { format!("[CW: {spoiler}] {text}") }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_status;
+
+    fn status(id: &str) -> Status {
+        Status { content: format!("<p>{id}</p>"), ..test_status(id, "alice") }
+    }
+
+    #[test]
+    fn drops_ancestors_up_to_reset_status() {
+        let mut ctx = StatusContext {
+            ancestors: vec![status("1"), status("2"), status("3")],
+            descendants: vec![],
+        };
+
+        drop_ancestors_through(&mut ctx, "unknown");
+        assert_eq!(ctx.ancestors.len(), 3);
+
+        drop_ancestors_through(&mut ctx, "2");
+        assert_eq!(format_conversation_context(&ctx, &status("4")), "- 3\n- 4");
     }
 
-    lines.into_iter().map(|t| format!("- {}", t)).collect::<Vec<_>>().join("\n")
+    #[test]
+    fn marks_statuses_behind_content_warning() {
+        let mut cw = status("2");
+        cw.spoiler_text = "ネタバレ".into();
+        let ctx = StatusContext { ancestors: vec![status("1"), cw], descendants: vec![] };
+
+        assert_eq!(format_conversation_context(&ctx, &status("3")), "- 1\n- [CW: ネタバレ] 2\n- 3");
+    }
 }
@@ -1,11 +1,22 @@
+use crate::approval::{handle_review_favourite, handle_review_reply};
 use crate::config::BotConfig;
-use crate::conversation_store::ConversationStore;
-use crate::mastodon::{Notification, Status, fetch_status_context, post_reply};
+use crate::conversation_store::{ConversationStore, PreviousResponse};
+use crate::mastodon::{Notification, Status, fetch_status_context};
+use crate::openai_api::{
+    PersonaTarget, ReplyResult, access_denied_notice, command_config, generate_reply,
+    is_known_persona, is_previous_response_not_found, loop_closing_message, select_persona,
+};
+use crate::opt_out::{is_opted_out, remove_opted_out};
+use crate::publisher::{publish_reply_with_warning, reply_char_budget};
 use crate::util::strip_html;
 use anyhow::{Context as AnyhowContext, Result};
+use chrono::Utc;
 use std::sync::Arc;
 
+use super::access::{is_allowed, notify_denied};
+use super::commands::{Command, parse_command, run_command};
 use super::context;
+use super::loop_guard::{check_loop, close_thread, count_own_statuses, record_reply};
 use super::rate_limit::wait_for_rate_limit;
 use super::recoverable::{RecoverableFailure, log_recoverable_error};
 
@@ -15,13 +26,31 @@ pub(crate) async fn handle_ws_text(
     conv_store: &Arc<ConversationStore>,
     text: &str,
 ) -> Result<()> {
-    let Some(notif) = parse_mention_notification(text)? else {
+    let Some(notif) = parse_notification(text)? else {
         return Ok(());
     };
 
+    if notif.notif_type == "favourite" {
+        return handle_favourite_notification(client, config, conv_store, notif).await;
+    }
+
     handle_mention_notification(client, config, conv_store, notif).await
 }
 
+/// お気に入りは承認待ちの確認用投稿に付いたものだけ扱う
+async fn handle_favourite_notification(
+    client: &reqwest::Client,
+    config: &BotConfig,
+    conv_store: &Arc<ConversationStore>,
+    notif: Notification,
+) -> Result<()> {
+    let Some(status) = notif.status.as_ref() else {
+        return Ok(());
+    };
+
+    handle_review_favourite(client, config, conv_store, status, &notif.account.acct).await
+}
+
 async fn handle_mention_notification(
     client: &reqwest::Client,
     config: &BotConfig,
@@ -33,7 +62,53 @@ async fn handle_mention_notification(
         None => return Ok(()),
     };
 
-    let reply_request = prepare_reply_request(client, config, conv_store, status, &notif).await?;
+    // 承認待ちの確認用投稿への返信なら、会話の返信はしない
+    if handle_review_reply(client, config, conv_store, status, &notif.account.acct).await? {
+        return Ok(());
+    }
+
+    // ACCESS_MODE の対象外なら OpenAI を呼ばずに終わる
+    if !is_allowed(client, config, &notif.account).await {
+        println!(
+            "Skip mention from @{} (ACCESS_MODE={}, id={})",
+            notif.account.acct, config.access_mode, notif.id
+        );
+        let acct = &notif.account.acct;
+        return notify_denied(client, config, conv_store, status, acct, access_denied_notice())
+            .await;
+    }
+
+    let command = parse_command(&strip_html(&status.content), command_config());
+
+    // オプトアウトしたアカウントには返信しない（`/optin` だけは受け付ける）
+    if command != Some(Command::OptIn)
+        && is_opted_out(client, config, conv_store, &notif.account).await
+    {
+        println!("Skip mention from opted-out account @{} (id={})", notif.account.acct, notif.id);
+        return Ok(());
+    }
+
+    let thread = fetch_conversation_context(client, config, conv_store, status).await;
+
+    // bot 同士のリプ合戦になっていれば打ち切る（打ち切ったスレッドを戻す `/reset` と `/optin` は受け付ける）
+    let acct = &notif.account.acct;
+    if !matches!(command, Some(Command::OptIn | Command::Reset))
+        && let Some(reason) =
+            check_loop(config, conv_store, &thread.thread_key, acct, thread.own_replies).await
+    {
+        println!("Stop replying to @{} in thread {} ({:?})", acct, thread.thread_key, reason);
+        let message = loop_closing_message();
+        return close_thread(client, config, conv_store, status, acct, &thread.thread_key, message)
+            .await;
+    }
+
+    if let Some(command) = command {
+        run_command(client, config, conv_store, status, acct, &thread.thread_key, command).await?;
+        record_reply(conv_store, &thread.thread_key, acct).await;
+        return Ok(());
+    }
+
+    let reply_request = prepare_reply_request(config, conv_store, status, &notif, thread).await?;
 
     generate_and_post_reply(client, config, conv_store, status, &notif, reply_request).await;
 
@@ -41,36 +116,43 @@ async fn handle_mention_notification(
 }
 
 struct ReplyRequest {
-    plain_text: String,
     thread_key: String,
-    context_for_openai: Option<String>,
-    previous_response_id: Option<String>,
+    /// Mastodon 側の会話ログ（会話を引き継げないモデルのときだけ OpenAI に渡す）
+    conversation_context: Option<String>,
+    previous_response: Option<PreviousResponse>,
+    persona: String,
+}
+
+/// スレッドの文脈
+struct ThreadContext {
+    text: Option<String>,
+    thread_key: String,
+    /// ancestors にある bot 自身の投稿の数（ループ検出用）
+    own_replies: usize,
 }
 
 async fn prepare_reply_request(
-    client: &reqwest::Client,
     config: &BotConfig,
     conv_store: &Arc<ConversationStore>,
     status: &Status,
     notif: &Notification,
+    thread: ThreadContext,
 ) -> Result<ReplyRequest> {
     let plain = strip_html(&status.content);
     println!("(stream) Mention from @{}: {}", notif.account.acct, plain);
 
-    let (conversation_context, thread_key) =
-        fetch_conversation_context(client, config, status).await;
+    let ThreadContext { text: conversation_context, thread_key, .. } = thread;
 
-    let prev_response_id = load_previous_response_id(conv_store, &thread_key).await?;
-    let context_for_openai =
-        select_context_for_openai(conversation_context.as_deref(), prev_response_id.as_ref())
-            .map(str::to_string);
+    let previous_response = load_previous_response(config, conv_store, &thread_key).await?;
 
-    Ok(ReplyRequest {
-        plain_text: plain,
-        thread_key,
-        context_for_openai,
-        previous_response_id: prev_response_id,
-    })
+    let target = PersonaTarget {
+        text: &plain,
+        acct: &notif.account.acct,
+        language: status.language.as_deref(),
+    };
+    let persona = resolve_thread_persona(conv_store, &thread_key, &target).await;
+
+    Ok(ReplyRequest { thread_key, conversation_context, previous_response, persona })
 }
 
 async fn generate_and_post_reply(
@@ -83,21 +165,30 @@ async fn generate_and_post_reply(
 ) {
     wait_for_rate_limit(config.reply_min_interval.as_millis() as u64).await;
 
-    match crate::openai_api::generate_reply(
-        client,
-        config,
-        &reply_request.plain_text,
-        reply_request.context_for_openai.as_deref(),
-        reply_request.previous_response_id,
-    )
-    .await
-    {
+    match generate_reply_or_bootstrap(client, config, conv_store, status, &reply_request).await {
+        Ok(reply_result) if reply_result.no_reply => {
+            // モデルが返信不要と判断したら投稿しない（会話の状態だけ進める）
+            println!(" -> No reply needed for @{}", notif.account.acct);
+            save_response_id(conv_store, &reply_request.thread_key, &reply_result).await;
+        }
         Ok(reply_result) => {
             println!(" -> Reply: {}", reply_result.text);
-            post_generated_reply(client, config, status, &notif.account.acct, &reply_result.text)
-                .await;
-            save_response_id(conv_store, &reply_request.thread_key, &reply_result.response_id)
+            let limit = reply_char_budget(config, status, reply_result.content_warning.as_deref());
+            let body = reply_result.body_with_sources(limit, &notif.account.acct);
+            post_generated_reply(
+                client,
+                config,
+                conv_store,
+                status,
+                &notif.account.acct,
+                &body,
+                reply_result.content_warning.as_deref(),
+            )
+            .await;
+            save_response_id(conv_store, &reply_request.thread_key, &reply_result).await;
+            remember_thread_account(conv_store, &reply_request.thread_key, &notif.account.acct)
                 .await;
+            record_reply(conv_store, &reply_request.thread_key, &notif.account.acct).await;
         }
         Err(e) => {
             log_recoverable_error(RecoverableFailure::GenerateReply, &e);
@@ -105,7 +196,59 @@ async fn generate_and_post_reply(
     }
 }
 
-fn parse_mention_notification(text: &str) -> Result<Option<Notification>> {
+/// 返信を生成する。`previous_response_id` の応答が OpenAI 側で消えていたら、
+/// その id を忘れて Mastodon の会話ログから始め直す
+async fn generate_reply_or_bootstrap(
+    client: &reqwest::Client,
+    config: &BotConfig,
+    conv_store: &Arc<ConversationStore>,
+    status: &Status,
+    reply_request: &ReplyRequest,
+) -> Result<ReplyResult> {
+    let result = generate_reply(
+        client,
+        config,
+        conv_store,
+        reply_request.conversation_context.as_deref(),
+        reply_request.previous_response.as_ref(),
+        &reply_request.persona,
+        status,
+    )
+    .await;
+
+    let stale = match (&reply_request.previous_response, result) {
+        (Some(previous), Err(e)) if is_previous_response_not_found(&e) => previous,
+        (_, result) => return result,
+    };
+
+    let thread_key = &reply_request.thread_key;
+    println!(
+        "  -> previous response {} for thread {} is gone; starting over from the thread context",
+        stale.id, thread_key
+    );
+    if let Err(e) = conv_store.forget_response_id(thread_key).await {
+        log_recoverable_error(RecoverableFailure::ForgetResponseId { thread_key }, &e);
+    }
+
+    // 会話ログを取れていなければ取り直す
+    let conversation_context = match &reply_request.conversation_context {
+        Some(context) => Some(context.clone()),
+        None => fetch_conversation_context(client, config, conv_store, status).await.text,
+    };
+
+    generate_reply(
+        client,
+        config,
+        conv_store,
+        conversation_context.as_deref(),
+        None,
+        &reply_request.persona,
+        status,
+    )
+    .await
+}
+
+fn parse_notification(text: &str) -> Result<Option<Notification>> {
     let ev: StreamEvent =
         serde_json::from_str(text).context("Failed to parse stream event JSON")?;
 
@@ -121,13 +264,16 @@ fn parse_mention_notification(text: &str) -> Result<Option<Notification>> {
     let notif: Notification =
         serde_json::from_str(payload).context("Failed to parse notification payload")?;
 
-    if notif.notif_type != "mention" {
+    if notif.notif_type != "mention" && notif.notif_type != "favourite" {
         return Ok(None);
     }
 
     // bot 同士のリプ合戦防止
     if notif.account.bot.unwrap_or(false) {
-        println!("Skip mention from bot account @{} (id={})", notif.account.acct, notif.id);
+        println!(
+            "Skip {} from bot account @{} (id={})",
+            notif.notif_type, notif.account.acct, notif.id
+        );
         return Ok(None);
     }
 
@@ -137,8 +283,9 @@ fn parse_mention_notification(text: &str) -> Result<Option<Notification>> {
 async fn fetch_conversation_context(
     client: &reqwest::Client,
     config: &BotConfig,
+    conv_store: &ConversationStore,
     status: &Status,
-) -> (Option<String>, String) {
+) -> ThreadContext {
     match fetch_status_context(
         client,
         &config.mastodon_base,
@@ -147,7 +294,7 @@ async fn fetch_conversation_context(
     )
     .await
     {
-        Ok(ctx) => {
+        Ok(mut ctx) => {
             // ancestors からスレッドルートIDを決める
             let root_id = if let Some(first) = ctx.ancestors.first() {
                 first.id.clone()
@@ -155,58 +302,93 @@ async fn fetch_conversation_context(
                 status.id.clone()
             };
 
+            // `/reset` より前の発言は文脈に含めない
+            match conv_store.get_thread_reset(&root_id).await {
+                Ok(Some(reset_status_id)) => {
+                    context::drop_ancestors_through(&mut ctx, &reset_status_id)
+                }
+                Ok(None) => {}
+                Err(e) => log_recoverable_error(
+                    RecoverableFailure::LoadThreadReset { thread_key: &root_id },
+                    &e,
+                ),
+            }
+            let own_replies = count_own_statuses(client, config, &ctx.ancestors).await;
+
+            // オプトアウトしたアカウントの発言も含めない
+            remove_opted_out(client, config, conv_store, &mut ctx.ancestors).await;
+
             let ctx_text = context::format_conversation_context(&ctx, status);
-            let ctx_opt = if ctx_text.is_empty() { None } else { Some(ctx_text) };
-            (ctx_opt, root_id)
+            let text = if ctx_text.is_empty() { None } else { Some(ctx_text) };
+            ThreadContext { text, thread_key: root_id, own_replies }
         }
         Err(e) => {
             log_recoverable_error(RecoverableFailure::FetchStatusContext, &e);
             // コンテキスト取れなくても、とりあえずこのステータスIDを thread_key にする
-            (None, status.id.clone())
+            ThreadContext { text: None, thread_key: status.id.clone(), own_replies: 0 }
         }
     }
 }
 
-async fn load_previous_response_id(
+async fn load_previous_response(
+    config: &BotConfig,
     conv_store: &Arc<ConversationStore>,
     thread_key: &str,
-) -> Result<Option<String>> {
-    let prev_response_id = conv_store.get_previous_response_id(thread_key).await?;
-    if let Some(ref id) = prev_response_id {
-        println!("  -> previous_response_id for thread {}: {}", thread_key, id);
+) -> Result<Option<PreviousResponse>> {
+    // CONVERSATION_MAX_AGE_SECS より前の応答は OpenAI 側で消えているものとして使わない
+    let updated_after = Utc::now().timestamp() - config.conversation_max_age.as_secs() as i64;
+    let previous = conv_store.get_previous_response_id(thread_key, updated_after).await?;
+    if let Some(ref previous) = previous {
+        println!(
+            "  -> previous_response_id for thread {}: {} ({})",
+            thread_key,
+            previous.id,
+            previous.model.as_deref().unwrap_or("model unknown")
+        );
     }
 
-    Ok(prev_response_id)
+    Ok(previous)
 }
 
-fn select_context_for_openai<'a>(
-    conversation_context: Option<&'a str>,
-    previous_response_id: Option<&String>,
-) -> Option<&'a str> {
-    if previous_response_id.is_some() {
-        // 2回目以降：OpenAI 側の会話状態に任せる
-        None
-    } else {
-        // 初回だけ Mastodon 側の会話ログをブートストラップとして渡す
-        conversation_context
+/// スレッドに記録済みのペルソナを使い、なければルールで選んで記録する
+async fn resolve_thread_persona(
+    conv_store: &Arc<ConversationStore>,
+    thread_key: &str,
+    target: &PersonaTarget<'_>,
+) -> String {
+    match conv_store.get_thread_persona(thread_key).await {
+        Ok(Some(persona)) if is_known_persona(&persona) => return persona,
+        Ok(_) => {}
+        Err(e) => log_recoverable_error(RecoverableFailure::LoadPersona { thread_key }, &e),
+    }
+
+    let persona = select_persona(target);
+    println!("  -> persona for thread {}: {}", thread_key, persona);
+    if let Err(e) = conv_store.set_thread_persona(thread_key, &persona).await {
+        log_recoverable_error(RecoverableFailure::SavePersona { thread_key }, &e);
     }
+
+    persona
 }
 
 async fn post_generated_reply(
     client: &reqwest::Client,
     config: &BotConfig,
+    conv_store: &ConversationStore,
     status: &Status,
     account_acct: &str,
     reply_text: &str,
+    content_warning: Option<&str>,
 ) {
-    // 4-1. Mastodon へ投稿
-    if let Err(e) = post_reply(
+    // 4-1. Mastodon へ投稿（ドライランなら記録だけ）
+    if let Err(e) = publish_reply_with_warning(
         client,
-        &config.mastodon_base,
-        &config.mastodon_access_token,
+        config,
+        conv_store,
         status,
         account_acct,
         reply_text,
+        content_warning,
     )
     .await
     {
@@ -217,14 +399,24 @@ async fn post_generated_reply(
 async fn save_response_id(
     conv_store: &Arc<ConversationStore>,
     thread_key: &str,
-    response_id: &str,
+    reply_result: &ReplyResult,
 ) {
-    // 4-2. このスレッドの last_response_id として保存
-    if let Err(e) = conv_store.upsert_last_response_id(thread_key, response_id).await {
+    // 4-2. このスレッドの last_response_id として、返信を書いたモデルと一緒に保存
+    if let Err(e) = conv_store
+        .upsert_last_response_id(thread_key, &reply_result.response_id, &reply_result.model)
+        .await
+    {
         log_recoverable_error(RecoverableFailure::SaveResponseId { thread_key }, &e);
     }
 }
 
+async fn remember_thread_account(conv_store: &ConversationStore, thread_key: &str, acct: &str) {
+    // `/forget me` で消せるように、話した相手を記録
+    if let Err(e) = conv_store.remember_thread_account(thread_key, acct).await {
+        log_recoverable_error(RecoverableFailure::SaveThreadAccount { thread_key }, &e);
+    }
+}
+
 #[derive(Debug, serde::Deserialize)]
 struct StreamEvent {
     event: String,
@@ -250,6 +442,20 @@ mod tests {
         handle_ws_text(&client, &config, &store, text).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn ignores_reblog_notifications() {
+        let client = reqwest::Client::new();
+        let config = test_config();
+        let store = test_store();
+        let text = r#"{
+            "event":"notification",
+            "payload":"{\"id\":\"n1\",\"type\":\"reblog\",\"status\":null,\"account\":{\"acct\":\"alice\",\"bot\":false}}"
+        }"#;
+
+        assert!(parse_notification(text).unwrap().is_none());
+        handle_ws_text(&client, &config, &store, text).await.unwrap();
+    }
+
     #[tokio::test]
     async fn ignores_non_mention_notifications() {
         let client = reqwest::Client::new();
@@ -283,7 +489,7 @@ mod tests {
             "payload":"{\"id\":\"n1\",\"type\":\"mention\",\"status\":{\"id\":\"s1\",\"content\":\"<p>hello</p>\",\"visibility\":\"unlisted\",\"in_reply_to_id\":null,\"account\":{\"acct\":\"alice\",\"bot\":false}},\"account\":{\"acct\":\"alice\",\"bot\":false}}"
         }"#;
 
-        let notif = parse_mention_notification(text).unwrap().unwrap();
+        let notif = parse_notification(text).unwrap().unwrap();
         let status = notif.status.unwrap();
 
         assert_eq!(notif.id, "n1");
@@ -292,13 +498,4 @@ mod tests {
         assert_eq!(status.content, "<p>hello</p>");
         assert_eq!(status.visibility, "unlisted");
     }
-
-    #[test]
-    fn selects_context_only_without_previous_response() {
-        let previous_response_id = "resp_123".to_string();
-
-        assert_eq!(select_context_for_openai(Some("ctx"), None), Some("ctx"));
-        assert_eq!(select_context_for_openai(Some("ctx"), Some(&previous_response_id)), None);
-        assert_eq!(select_context_for_openai(None, None), None);
-    }
 }
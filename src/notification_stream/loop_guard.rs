@@ -0,0 +1,195 @@
+//! 返信のループ対策
+//!
+//! `bot` フラグを付けていない bot とのリプ合戦を止めるため、次のどれかで返信を打ち切る。
+//!
+//! - 1 つのスレッドで bot が返信した回数が `LOOP_MAX_THREAD_REPLIES` に達した
+//!   （`bot_replies` の記録と、ancestors にある bot 自身の投稿の多い方で数える）
+//! - 同じアカウントへの返信が `LOOP_ACCOUNT_WINDOW_SECS` の間に `LOOP_MAX_ACCOUNT_REPLIES` に達した
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use tokio::sync::Mutex;
+
+use crate::config::BotConfig;
+use crate::conversation_store::ConversationStore;
+use crate::mastodon::{Status, verify_credentials};
+use crate::opt_out::normalize_acct;
+use crate::publisher::publish_reply;
+
+// bot 自身のアカウント ID（最初に必要になったときに verify_credentials で取る）
+static OWN_ACCOUNT_ID: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum LoopReason {
+    ThreadDepth,
+    AccountFrequency,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct LoopStats {
+    /// `bot_replies` に記録したこのスレッドでの返信数
+    pub thread_replies: usize,
+    /// ancestors にある bot 自身の投稿の数
+    pub own_replies_in_ancestors: usize,
+    /// 窓の間に同じアカウントへ返信した数
+    pub account_replies: usize,
+}
+
+/// 打ち切るべきなら理由を返す
+pub(super) fn loop_reason(cfg: &BotConfig, stats: &LoopStats) -> Option<LoopReason> {
+    let thread_replies = stats.thread_replies.max(stats.own_replies_in_ancestors);
+    if cfg.loop_max_thread_replies > 0 && thread_replies >= cfg.loop_max_thread_replies {
+        return Some(LoopReason::ThreadDepth);
+    }
+    if cfg.loop_max_account_replies > 0 && stats.account_replies >= cfg.loop_max_account_replies {
+        return Some(LoopReason::AccountFrequency);
+    }
+    None
+}
+
+/// 記録と ancestors を数えて、打ち切るべきか判定する。記録を読めなければ ancestors だけで判断する
+pub(super) async fn check_loop(
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    thread_key: &str,
+    acct: &str,
+    own_replies_in_ancestors: usize,
+) -> Option<LoopReason> {
+    let since = Utc::now().timestamp() - cfg.loop_account_window.as_secs() as i64;
+    let thread_replies = conv_store.count_thread_replies(thread_key).await.unwrap_or_else(|e| {
+        eprintln!("[loop] Failed to count replies in thread {thread_key}: {:?}", e);
+        0
+    });
+    let account_replies =
+        conv_store.count_account_replies(&normalize_acct(acct), since).await.unwrap_or_else(|e| {
+            eprintln!("[loop] Failed to count replies to @{acct}: {:?}", e);
+            0
+        });
+
+    loop_reason(cfg, &LoopStats { thread_replies, own_replies_in_ancestors, account_replies })
+}
+
+/// 返信したことを記録する
+pub(super) async fn record_reply(conv_store: &ConversationStore, thread_key: &str, acct: &str) {
+    if let Err(e) = conv_store.record_bot_reply(thread_key, &normalize_acct(acct)).await {
+        eprintln!("[loop] Failed to record reply in thread {thread_key}: {:?}", e);
+    }
+}
+
+/// スレッドを打ち切る。`LOOP_CLOSING_NOTICE=true` なら、そのスレッドで 1 度だけお知らせを返信する
+pub(super) async fn close_thread(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    status: &Status,
+    acct: &str,
+    thread_key: &str,
+    message: &str,
+) -> anyhow::Result<()> {
+    if !conv_store.close_thread(thread_key).await? {
+        return Ok(());
+    }
+    if !cfg.loop_closing_notice || message.trim().is_empty() {
+        return Ok(());
+    }
+
+    publish_reply(client, cfg, conv_store, status, acct, message).await
+}
+
+/// ancestors のうち bot 自身の投稿の数。自分の ID が分からなければ 0
+pub(super) async fn count_own_statuses(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    statuses: &[Status],
+) -> usize {
+    match own_account_id(client, cfg).await {
+        Some(own_id) => statuses.iter().filter(|s| s.account.id == own_id).count(),
+        None => 0,
+    }
+}
+
+async fn own_account_id(client: &reqwest::Client, cfg: &BotConfig) -> Option<String> {
+    let mut guard = OWN_ACCOUNT_ID.lock().await;
+    if let Some(id) = guard.as_ref() {
+        return Some(id.clone());
+    }
+
+    match verify_credentials(client, &cfg.mastodon_base, &cfg.mastodon_access_token).await {
+        Ok(account) if !account.id.is_empty() => {
+            *guard = Some(account.id.clone());
+            Some(account.id)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            // 次のメンションでまた試す
+            eprintln!("[loop] Failed to verify credentials: {:?}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{closed_local_url, test_config, test_status};
+
+    fn stats(thread: usize, ancestors: usize, account: usize) -> LoopStats {
+        LoopStats {
+            thread_replies: thread,
+            own_replies_in_ancestors: ancestors,
+            account_replies: account,
+        }
+    }
+
+    #[test]
+    fn stops_at_thread_depth_or_account_frequency() {
+        let mut cfg = test_config();
+        cfg.loop_max_thread_replies = 3;
+        cfg.loop_max_account_replies = 5;
+
+        assert_eq!(loop_reason(&cfg, &stats(2, 0, 0)), None);
+        assert_eq!(loop_reason(&cfg, &stats(3, 0, 0)), Some(LoopReason::ThreadDepth));
+        // DB に記録がなくても ancestors の自分の投稿で数える
+        assert_eq!(loop_reason(&cfg, &stats(0, 3, 0)), Some(LoopReason::ThreadDepth));
+        assert_eq!(loop_reason(&cfg, &stats(0, 0, 5)), Some(LoopReason::AccountFrequency));
+    }
+
+    #[test]
+    fn zero_disables_limits() {
+        let mut cfg = test_config();
+        cfg.loop_max_thread_replies = 0;
+        cfg.loop_max_account_replies = 0;
+
+        assert_eq!(loop_reason(&cfg, &stats(100, 100, 100)), None);
+    }
+
+    #[tokio::test]
+    async fn checks_recorded_replies() {
+        let mut cfg = test_config();
+        cfg.loop_max_thread_replies = 2;
+        cfg.loop_max_account_replies = 0;
+        let store = ConversationStore::new(":memory:").unwrap();
+
+        record_reply(&store, "t1", "@Alice").await;
+        assert_eq!(check_loop(&cfg, &store, "t1", "alice", 0).await, None);
+
+        record_reply(&store, "t1", "alice").await;
+        assert_eq!(check_loop(&cfg, &store, "t1", "alice", 0).await, Some(LoopReason::ThreadDepth));
+        assert_eq!(check_loop(&cfg, &store, "t2", "alice", 0).await, None);
+    }
+
+    #[tokio::test]
+    async fn closing_notice_is_sent_once_per_thread() {
+        let mut cfg = test_config();
+        cfg.loop_closing_notice = true;
+        // 送信しようとすれば接続エラーになる
+        cfg.mastodon_base = closed_local_url("");
+        let store = ConversationStore::new(":memory:").unwrap();
+        let status = test_status("s1", "bob");
+        let client = reqwest::Client::new();
+
+        assert!(close_thread(&client, &cfg, &store, &status, "bob", "t1", "bye").await.is_err());
+        // 2 回目は送らない
+        close_thread(&client, &cfg, &store, &status, "bob", "t1", "bye").await.unwrap();
+    }
+}
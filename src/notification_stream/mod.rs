@@ -3,9 +3,12 @@ use crate::conversation_store::ConversationStore;
 use anyhow::Result;
 use std::sync::Arc;
 
+mod access;
+mod commands;
 mod connection;
 mod context;
 mod handler;
+mod loop_guard;
 mod rate_limit;
 mod recoverable;
 
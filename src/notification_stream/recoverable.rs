@@ -4,6 +4,11 @@ pub(super) enum RecoverableFailure<'a> {
     GenerateReply,
     PostReply,
     SaveResponseId { thread_key: &'a str },
+    ForgetResponseId { thread_key: &'a str },
+    LoadPersona { thread_key: &'a str },
+    SavePersona { thread_key: &'a str },
+    LoadThreadReset { thread_key: &'a str },
+    SaveThreadAccount { thread_key: &'a str },
     HandleStreamMessage,
     WebSocket,
     ConnectStreamingApi,
@@ -18,6 +23,21 @@ impl RecoverableFailure<'_> {
             Self::SaveResponseId { thread_key } => {
                 format!("Failed to update last_response_id for thread {}", thread_key)
             }
+            Self::ForgetResponseId { thread_key } => {
+                format!("Failed to forget last_response_id for thread {}", thread_key)
+            }
+            Self::LoadPersona { thread_key } => {
+                format!("Failed to load persona for thread {}", thread_key)
+            }
+            Self::SavePersona { thread_key } => {
+                format!("Failed to save persona for thread {}", thread_key)
+            }
+            Self::LoadThreadReset { thread_key } => {
+                format!("Failed to load reset point for thread {}", thread_key)
+            }
+            Self::SaveThreadAccount { thread_key } => {
+                format!("Failed to save account for thread {}", thread_key)
+            }
             Self::HandleStreamMessage => "Error handling stream message".to_string(),
             Self::WebSocket => "WebSocket error".to_string(),
             Self::ConnectStreamingApi => "Failed to connect streaming API".to_string(),
@@ -1,54 +1,193 @@
-use crate::config::BotConfig;
-use crate::openai_api::stream::CallResponsesArgs;
-use crate::openai_api::types::{ChatMessage, Tool};
+use anyhow::{Result, bail};
+use reqwest::Client;
+
+use crate::config::{BotConfig, FunctionTool};
+use crate::conversation_store::ConversationStore;
+use crate::openai_api::fallback::{FailedModel, is_fallback_error};
+use crate::openai_api::reply::reply_text_format;
+use crate::openai_api::stream::{CallResponsesArgs, call_responses};
+use crate::openai_api::tools::{call_responses_with_tools, function_tool_definitions};
+use crate::openai_api::types::{ChatMessage, ResponsesResult, TextFormat, Tool};
+
+/// 構造化出力の JSON の枠（キー名や括弧）の分だけ出力トークンの上限を増やす
+const STRUCTURED_OUTPUT_EXTRA_TOKENS: u32 = 40;
 
 pub(super) struct OpenAiCallConfig<'a> {
+    cfg: &'a BotConfig,
     model: &'a str,
     model_reply: &'a str,
     api_key: &'a str,
     temperature: f32,
+    text_format: Option<TextFormat>,
+    function_tools: &'a [FunctionTool],
+    /// 関数ツールがオプトアウトを確かめるのに使う（返信のときだけ）
+    conv_store: Option<&'a ConversationStore>,
+    /// 返信の呼び出しか（`model_reply` を使う）
+    is_reply: bool,
+    /// 設定のモデルが失敗したときに順に試すモデル
+    fallback_models: &'a [String],
 }
 
 impl<'a> OpenAiCallConfig<'a> {
-    pub(super) fn for_reply(cfg: &'a BotConfig) -> Self {
+    pub(super) fn for_reply(cfg: &'a BotConfig, conv_store: &'a ConversationStore) -> Self {
         Self {
+            cfg,
             model: &cfg.openai_model,
             model_reply: &cfg.openai_reply_model,
             api_key: &cfg.openai_api_key,
             temperature: cfg.reply_temperature,
+            text_format: cfg.reply_structured_output.then(reply_text_format),
+            function_tools: &cfg.function_tools,
+            conv_store: Some(conv_store),
+            is_reply: true,
+            fallback_models: &cfg.reply_fallback_models,
         }
     }
 
     pub(super) fn for_free_toot(cfg: &'a BotConfig) -> Self {
         Self {
+            cfg,
             model: &cfg.openai_model,
             model_reply: &cfg.openai_reply_model,
             api_key: &cfg.openai_api_key,
             temperature: cfg.free_toot_temperature,
+            text_format: None,
+            function_tools: &[],
+            conv_store: None,
+            is_reply: false,
+            fallback_models: &cfg.free_toot_fallback_models,
         }
     }
 
+    /// 構造化出力を使わない（プレーンテキストで受け取る）
+    pub(super) fn plain_text(mut self) -> Self {
+        self.text_format = None;
+        self
+    }
+
+    pub(super) fn is_structured(&self) -> bool {
+        self.text_format.is_some()
+    }
+
+    pub(super) fn reply_model(&self) -> &str {
+        self.model_reply
+    }
+
+    /// 設定のモデルと、失敗したときに順に試すモデル
+    pub(super) fn models(&self) -> Vec<&'a str> {
+        let primary = if self.is_reply { self.model_reply } else { self.model };
+        chain(primary, self.fallback_models)
+    }
+
+    /// リトライで試すモデル（最初の返信を書いたモデル、続けて `retry_fallback_models`）
+    pub(super) fn retry_models<'m>(&self, answered_by: &'m str) -> Vec<&'m str>
+    where
+        'a: 'm,
+    {
+        chain(answered_by, &self.cfg.retry_fallback_models)
+    }
+
     pub(super) fn build(
         &self,
         messages: Vec<ChatMessage>,
         max_output_tokens: u32,
         previous_response_id: Option<String>,
-        tools: Vec<Tool>,
+        mut tools: Vec<Tool>,
     ) -> CallResponsesArgs<'a> {
+        let max_output_tokens = if self.is_structured() {
+            max_output_tokens + STRUCTURED_OUTPUT_EXTRA_TOKENS
+        } else {
+            max_output_tokens
+        };
         let mut builder =
             CallResponsesArgs::new(self.model, self.model_reply, self.api_key, messages)
                 .temperature(self.temperature)
-                .max_output_tokens(max_output_tokens);
+                .max_output_tokens(max_output_tokens)
+                .model_table(&self.cfg.model_capabilities);
 
         if let Some(prev) = previous_response_id {
             builder = builder.previous_response_id(prev);
         }
+        tools.extend(function_tool_definitions(self.function_tools));
         if !tools.is_empty() {
             builder = builder.tools(tools);
         }
+        if let Some(format) = &self.text_format {
+            builder = builder.text_format(format.clone());
+        }
 
         builder
     }
+
+    /// 設定のモデルで呼び、失敗したらフォールバックのモデルで同じリクエストを送り直す
+    pub(super) async fn call(
+        &self,
+        client: &Client,
+        args: CallResponsesArgs<'_>,
+    ) -> Result<ResponsesResult> {
+        self.call_with_fallback(client, &self.models(), |_| args.clone()).await
+    }
+
+    /// `models` を先頭から呼ぶ。別のモデルなら通りそうな失敗（`is_fallback_error`）のときだけ次へ進む。
+    /// 返すエラーには失敗したモデル（`FailedModel`）を付ける
+    ///
+    /// リクエストはモデルごとに `build` で作る（会話を引き継げるかがモデルで変わるため）
+    pub(super) async fn call_with_fallback<'m>(
+        &self,
+        client: &Client,
+        models: &[&'m str],
+        build: impl Fn(&'m str) -> CallResponsesArgs<'m>,
+    ) -> Result<ResponsesResult> {
+        for (i, &model) in models.iter().enumerate() {
+            let args = build(model).model(model);
+            match self.call_once(client, args).await {
+                Err(e) if i + 1 < models.len() && is_fallback_error(&e) => {
+                    eprintln!(
+                        "[openai] {} failed, falling back to {}: {:?}",
+                        model,
+                        models[i + 1],
+                        e
+                    );
+                }
+                result => return result.map_err(|e| e.context(FailedModel(model.to_string()))),
+            }
+        }
+
+        bail!("no OpenAI model to call")
+    }
+
+    /// 1 つのモデルでの呼び出し。返信で関数ツールがあれば、呼ばれた関数を実行しながら答えが出るまで続ける
+    async fn call_once(
+        &self,
+        client: &Client,
+        args: CallResponsesArgs<'_>,
+    ) -> Result<ResponsesResult> {
+        match self.conv_store {
+            Some(conv_store) if self.is_reply && !self.function_tools.is_empty() => {
+                call_responses_with_tools(
+                    client,
+                    self.cfg,
+                    conv_store,
+                    self.function_tools,
+                    args,
+                    true,
+                )
+                .await
+            }
+            _ => call_responses(client, args, self.is_reply).await,
+        }
+    }
+}
+
+/// 先頭のモデルに、重ならないフォールバックのモデルを続ける
+fn chain<'m>(primary: &'m str, fallback_models: &'m [String]) -> Vec<&'m str> {
+    let mut models = vec![primary];
+    for model in fallback_models {
+        if !models.contains(&model.as_str()) {
+            models.push(model);
+        }
+    }
+    models
 }
 
 pub(super) fn build_web_search_tools(
@@ -63,3 +202,65 @@ pub(super) fn build_web_search_tools(
         Vec::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openai_api::fallback::failed_model;
+    use crate::test_support::{MockHttpServer, test_config};
+
+    const OK_BODY: &str =
+        r#"{"id":"resp_ok","status":"completed","output":[{"type":"output_text","text":"hi"}]}"#;
+
+    #[test]
+    fn chains_primary_and_fallback_models_per_role() {
+        let mut cfg = test_config();
+        cfg.reply_fallback_models = vec!["gpt-b".into(), "gpt-test-reply".into()];
+        cfg.free_toot_fallback_models = vec!["gpt-c".into()];
+        cfg.retry_fallback_models = vec!["gpt-d".into()];
+
+        let store = ConversationStore::new(":memory:").unwrap();
+        let reply = OpenAiCallConfig::for_reply(&cfg, &store);
+        assert_eq!(reply.models(), vec!["gpt-test-reply", "gpt-b"]);
+        assert_eq!(reply.retry_models("gpt-b"), vec!["gpt-b", "gpt-d"]);
+        assert_eq!(OpenAiCallConfig::for_free_toot(&cfg).models(), vec!["gpt-test", "gpt-c"]);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_next_model_on_retryable_error() {
+        let overloaded = MockHttpServer::respond("503 Service Unavailable", "overloaded");
+        let fallback = MockHttpServer::respond("200 OK", OK_BODY);
+        let urls = [overloaded.url("/v1/responses"), fallback.url("/v1/responses")];
+        let cfg = test_config();
+        let call_config = OpenAiCallConfig::for_free_toot(&cfg);
+
+        let res = call_config
+            .call_with_fallback(&Client::new(), &["gpt-a", "gpt-b"], |model| {
+                let url = if model == "gpt-a" { &urls[0] } else { &urls[1] };
+                call_config.build(Vec::new(), 100, None, Vec::new()).api_url(url)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(res.text, "hi");
+        assert_eq!(res.model, "gpt-b");
+    }
+
+    #[tokio::test]
+    async fn does_not_fall_back_on_request_errors() {
+        let server = MockHttpServer::respond("401 Unauthorized", "bad key");
+        let url = server.url("/v1/responses");
+        let cfg = test_config();
+        let call_config = OpenAiCallConfig::for_free_toot(&cfg);
+
+        let err = call_config
+            .call_with_fallback(&Client::new(), &["gpt-a", "gpt-b"], |_| {
+                call_config.build(Vec::new(), 100, None, Vec::new()).api_url(&url)
+            })
+            .await
+            .unwrap_err();
+
+        assert!(err.root_cause().to_string().starts_with("OpenAI error 401"), "{err:#}");
+        assert_eq!(failed_model(&err), Some("gpt-a"));
+    }
+}
@@ -0,0 +1,146 @@
+//! 呼び出しが失敗したときに別のモデルへ切り替えるかの判定
+//!
+//! 混雑・クォータ切れ・サーバーエラー・つながらないときと、モデル自体が使えない
+//! （廃止された・存在しない・このキーでは使えない）ときだけ次のモデルを試す。
+//! 認証エラーやリクエストの形の誤りは、どのモデルでも同じなので切り替えない。
+//! `previous_response_id` の応答が見つからないときも切り替えず、呼び出し側で会話を始め直す。
+
+use serde_json::Value;
+use std::fmt;
+
+/// モデルが原因だと分かる `error.code`
+const MODEL_ERROR_CODES: [&str; 4] =
+    ["model_not_found", "unsupported_model", "model_deprecated", "insufficient_quota"];
+
+/// 失敗したモデル。`call_with_fallback` がエラーの文脈として付ける
+#[derive(Debug)]
+pub(super) struct FailedModel(pub String);
+
+impl fmt::Display for FailedModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OpenAI model {}", self.0)
+    }
+}
+
+/// エラーを返したモデル（`call_with_fallback` を通ったエラーだけ分かる）
+pub(super) fn failed_model(err: &anyhow::Error) -> Option<&str> {
+    err.downcast_ref::<FailedModel>().map(|m| m.0.as_str())
+}
+
+/// OpenAI のエラー文（`OpenAI error 400 ...`）。モデル名などの文脈を付けていても元のエラーを見る
+pub(super) fn openai_error_message(err: &anyhow::Error) -> String {
+    err.root_cause().to_string()
+}
+
+pub(super) fn is_fallback_error(err: &anyhow::Error) -> bool {
+    if let Some(e) = err.chain().find_map(|e| e.downcast_ref::<reqwest::Error>()) {
+        return e.is_timeout() || e.is_connect();
+    }
+    if is_previous_response_not_found(err) {
+        return false;
+    }
+
+    let message = openai_error_message(err);
+    let Some((status, body)) = parse_openai_error(&message) else {
+        return false;
+    };
+
+    match status {
+        404 | 429 => true,
+        500..=599 => true,
+        400 | 403 => is_model_error(body),
+        _ => false,
+    }
+}
+
+/// `previous_response_id` の応答が OpenAI 側で期限切れ・削除などで見つからない
+pub fn is_previous_response_not_found(err: &anyhow::Error) -> bool {
+    let message = openai_error_message(err);
+    let Some((_, body)) = parse_openai_error(&message) else {
+        return false;
+    };
+    let Ok(json) = serde_json::from_str::<Value>(body) else {
+        return false;
+    };
+    let error = &json["error"];
+
+    error["code"] == "previous_response_not_found"
+        || (error["param"] == "previous_response_id"
+            && error["message"].as_str().is_some_and(|m| m.contains("not found")))
+}
+
+/// `OpenAI error 429 Too Many Requests: {...}` を状態コードと本文に分ける
+fn parse_openai_error(message: &str) -> Option<(u16, &str)> {
+    let rest = message.strip_prefix("OpenAI error ")?;
+    let status = rest.get(..3)?.parse().ok()?;
+    let body = rest.split_once(": ").map_or("", |(_, body)| body);
+    Some((status, body))
+}
+
+fn is_model_error(body: &str) -> bool {
+    let Ok(json) = serde_json::from_str::<Value>(body) else {
+        return false;
+    };
+    let error = &json["error"];
+
+    error["param"] == "model"
+        || error["code"].as_str().is_some_and(|code| MODEL_ERROR_CODES.contains(&code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn openai_error(status: &str, body: &str) -> anyhow::Error {
+        anyhow!("OpenAI error {}: {}", status, body)
+    }
+
+    #[test]
+    fn falls_back_on_overload_quota_and_server_errors() {
+        assert!(is_fallback_error(&openai_error("429 Too Many Requests", "{}")));
+        assert!(is_fallback_error(&openai_error("500 Internal Server Error", "boom")));
+        assert!(is_fallback_error(&openai_error("503 Service Unavailable", "")));
+        assert!(is_fallback_error(&openai_error("404 Not Found", "{}")));
+    }
+
+    #[test]
+    fn falls_back_on_model_specific_errors_only() {
+        let deprecated = r#"{"error":{"message":"The model `gpt-old` has been deprecated","code":"model_not_found","param":null}}"#;
+        assert!(is_fallback_error(&openai_error("400 Bad Request", deprecated)));
+        let no_access = r#"{"error":{"message":"no access","param":"model","code":null}}"#;
+        assert!(is_fallback_error(&openai_error("403 Forbidden", no_access)));
+
+        let text_format =
+            r#"{"error":{"message":"Invalid schema","param":"text.format","code":null}}"#;
+        assert!(!is_fallback_error(&openai_error("400 Bad Request", text_format)));
+        assert!(!is_fallback_error(&openai_error("401 Unauthorized", "{}")));
+        assert!(!is_fallback_error(&anyhow!("Failed to parse OpenAI response JSON")));
+    }
+
+    #[test]
+    fn detects_missing_previous_response() {
+        let not_found = r#"{"error":{"message":"Previous response with id 'resp_1' not found.","type":"invalid_request_error","param":"previous_response_id","code":"previous_response_not_found"}}"#;
+        let err = openai_error("400 Bad Request", not_found);
+        assert!(is_previous_response_not_found(&err));
+        // 別のモデルに切り替えても直らない
+        assert!(!is_fallback_error(&openai_error("404 Not Found", not_found)));
+
+        let other = r#"{"error":{"message":"Invalid 'previous_response_id'","param":"previous_response_id","code":null}}"#;
+        assert!(!is_previous_response_not_found(&openai_error("400 Bad Request", other)));
+        assert!(!is_previous_response_not_found(&anyhow!("connection reset")));
+    }
+
+    #[test]
+    fn sees_through_the_failed_model_context() {
+        let not_found = r#"{"error":{"code":"previous_response_not_found"}}"#;
+        let err = openai_error("400 Bad Request", not_found).context(FailedModel("gpt-b".into()));
+
+        assert_eq!(failed_model(&err), Some("gpt-b"));
+        assert!(is_previous_response_not_found(&err));
+        assert!(is_fallback_error(
+            &openai_error("503 Service Unavailable", "").context(FailedModel("gpt-a".into()))
+        ));
+        assert_eq!(failed_model(&anyhow!("boom")), None);
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use reqwest::Client;
+
+use crate::config::BotConfig;
+use crate::feeds::FeedEntry;
+use crate::openai_api::call_config::OpenAiCallConfig;
+use crate::openai_api::prompts::{PROMPTS, PromptConfig};
+use crate::openai_api::types::ChatMessage;
+
+fn build_feed_digest_messages(
+    prompts: &PromptConfig,
+    feed_title: &str,
+    entry: &FeedEntry,
+) -> Vec<ChatMessage> {
+    let vars = [
+        ("{{FEED_TITLE}}", feed_title),
+        ("{{TITLE}}", entry.title.as_str()),
+        ("{{SUMMARY}}", entry.summary.as_str()),
+        ("{{LINK}}", entry.link.as_deref().unwrap_or("")),
+    ];
+
+    prompts
+        .feed_digest
+        .iter()
+        .map(|m| ChatMessage {
+            role: m.role.clone(),
+            content: vars.iter().fold(m.content.clone(), |acc, (k, v)| acc.replace(k, v)),
+        })
+        .collect()
+}
+
+/// フィードの新着記事を紹介する投稿文を生成
+pub async fn generate_feed_digest(
+    client: &Client,
+    cfg: &BotConfig,
+    feed_title: &str,
+    entry: &FeedEntry,
+) -> Result<String> {
+    let messages = build_feed_digest_messages(&PROMPTS, feed_title, entry);
+    let call_config = OpenAiCallConfig::for_free_toot(cfg);
+    let args = call_config.build(messages, 1024, None, Vec::new());
+
+    let res = call_config.call(client, args).await?;
+
+    Ok(res.text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_entry_placeholders() {
+        let prompts: PromptConfig = serde_json::from_str(
+            r#"{
+                "reply_with_context": [],
+                "reply_without_context": [],
+                "feed_digest": [
+                    {"role": "system", "content": "persona"},
+                    {"role": "user", "content": "{{FEED_TITLE}} / {{TITLE}} / {{SUMMARY}} / {{LINK}}"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let entry = FeedEntry {
+            id: "1".into(),
+            title: "v1.2.0".into(),
+            link: None,
+            summary: "Bug fixes".into(),
+        };
+
+        let messages = build_feed_digest_messages(&prompts, "Releases", &entry);
+
+        assert_eq!(messages[0].content, "persona");
+        assert_eq!(messages[1].content, "Releases / v1.2.0 / Bug fixes / ");
+    }
+
+    #[test]
+    fn default_template_passes_title_and_summary() {
+        let prompts: PromptConfig =
+            serde_json::from_str(r#"{"reply_with_context": [], "reply_without_context": []}"#)
+                .unwrap();
+        let entry = FeedEntry {
+            id: "1".into(),
+            title: "Maintenance".into(),
+            link: Some("https://blog.example/m".into()),
+            summary: "Upgrade on Sunday".into(),
+        };
+
+        let messages = build_feed_digest_messages(&prompts, "Blog", &entry);
+
+        assert_eq!(
+            messages.last().unwrap().content,
+            "フィード: Blog\nタイトル: Maintenance\n概要: Upgrade on Sunday"
+        );
+    }
+}
@@ -1,106 +1,159 @@
-use anyhow::Result;
-use chrono::{DateTime, Datelike, Timelike, Utc};
-use chrono_tz::Asia::Tokyo;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Datelike, Timelike, Weekday};
+use chrono_tz::Tz;
 use reqwest::Client;
 
 use crate::config::BotConfig;
+use crate::jp_holidays::holiday_name;
 use crate::openai_api::call_config::{
     OpenAiCallConfig, build_web_search_tools as build_openai_web_search_tools,
 };
-use crate::openai_api::prompts::PROMPTS;
-use crate::openai_api::stream::{CallResponsesArgs, call_responses};
+use crate::openai_api::prompts::{
+    CalendarHints, PROMPTS, PromptConfig, season_for_month, slot_for_hour,
+};
+use crate::openai_api::stream::CallResponsesArgs;
+use crate::openai_api::time::{current_time_instruction, now_in};
 use crate::openai_api::types::{ChatMessage, Tool};
+use crate::util::text_similarity;
+
+const WEEKDAY_LABELS: [&str; 7] = ["月", "火", "水", "木", "金", "土", "日"];
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum FreeTootSlot {
-    Morning,
-    Day,
-    Evening,
-    Night,
+/// 自由トゥートのプレースホルダーに入れる値
+struct FreeTootVars {
+    values: Vec<(&'static str, String)>,
 }
 
-impl FreeTootSlot {
-    fn as_log_label(self) -> &'static str {
-        match self {
-            FreeTootSlot::Morning => "morning",
-            FreeTootSlot::Day => "day",
-            FreeTootSlot::Evening => "evening",
-            FreeTootSlot::Night => "night",
+impl FreeTootVars {
+    fn new(prompts: &PromptConfig, now: DateTime<Tz>, time_label: &str) -> Self {
+        let date = now.date_naive();
+        let season =
+            season_for_month(&prompts.seasons, now.month()).map_or("", |s| s.label.as_str());
+        let holiday = holiday_name(date).unwrap_or("");
+        let special: Vec<&str> = prompts
+            .special_dates
+            .iter()
+            .filter(|s| s.matches(date))
+            .map(|s| s.label.as_str())
+            .collect();
+        let weekday = now.weekday();
+        let day_type = if !holiday.is_empty() {
+            "祝日"
+        } else if matches!(weekday, Weekday::Sat | Weekday::Sun) {
+            "週末"
+        } else {
+            "平日"
+        };
+
+        Self {
+            values: vec![
+                ("SEASON", season.to_string()),
+                ("TIME_LABEL", time_label.to_string()),
+                ("DATE", format!("{}月{}日", now.month(), now.day())),
+                ("WEEKDAY", WEEKDAY_LABELS[weekday.num_days_from_monday() as usize].to_string()),
+                ("DAY_TYPE", day_type.to_string()),
+                ("HOLIDAY", holiday.to_string()),
+                ("SPECIAL_DAY", special.join("、")),
+            ],
         }
     }
-}
 
-fn pick_free_toot_prompt_for_slot(slot: FreeTootSlot) -> (Vec<ChatMessage>, &'static str) {
-    match slot {
-        FreeTootSlot::Morning => (PROMPTS.free_toot_morning.clone(), slot.as_log_label()),
-        FreeTootSlot::Day => (PROMPTS.free_toot_day.clone(), slot.as_log_label()),
-        FreeTootSlot::Evening => {
-            // 夕方スロット（テンプレ自体は daytime を流用）
-            (PROMPTS.free_toot_day.clone(), slot.as_log_label())
-        }
-        FreeTootSlot::Night => (PROMPTS.free_toot_night.clone(), slot.as_log_label()),
+    fn get(&self, name: &str) -> &str {
+        self.values.iter().find(|(k, _)| *k == name).map_or("", |(_, v)| v.as_str())
     }
-}
 
-fn free_toot_slot_from_hour(hour: u32) -> FreeTootSlot {
-    match hour {
-        5..=8 => FreeTootSlot::Morning,
-        9..=15 => FreeTootSlot::Day,
-        16..=18 => FreeTootSlot::Evening,
-        _ => FreeTootSlot::Night,
+    fn fill(&self, text: &str) -> String {
+        self.values.iter().fold(text.to_string(), |acc, (name, value)| {
+            acc.replace(&format!("{{{{{name}}}}}"), value)
+        })
     }
 }
 
-fn season_label_from_month(month: u32) -> &'static str {
-    match month {
-        3..=5 => "春",
-        6..=8 => "夏",
-        9..=11 => "秋",
-        _ => "冬", // 12,1,2
+fn apply_free_toot_user_prompt(
+    messages: &mut [ChatMessage],
+    user_prompt: &str,
+    vars: &FreeTootVars,
+) {
+    // 最後の user メッセージを書き換える
+    if let Some(user_msg) = messages.iter_mut().rev().find(|m| m.role == "user") {
+        // fine-tune に合わせて、季節＋時間帯の指示を埋め込む
+        user_msg.content = user_prompt.to_string();
+    }
+    for message in messages.iter_mut() {
+        message.content = vars.fill(&message.content);
     }
 }
 
-fn time_label_from_hour(hour: u32) -> &'static str {
-    match hour {
-        5..=8 => "朝",
-        9..=15 => "昼",
-        16..=18 => "夕方",
-        _ => "夜",
-    }
+/// 曜日・祝日・特別な日の system 指示（空の指示と、該当しない日の指示は追加しない）
+fn calendar_hint_messages(hints: &CalendarHints, vars: &FreeTootVars) -> Vec<ChatMessage> {
+    [
+        (hints.today.as_str(), true),
+        (hints.holiday.as_str(), !vars.get("HOLIDAY").is_empty()),
+        (hints.special_day.as_str(), !vars.get("SPECIAL_DAY").is_empty()),
+    ]
+    .into_iter()
+    .filter(|(text, applies)| *applies && !text.trim().is_empty())
+    .map(|(text, _)| ChatMessage { role: "system".into(), content: vars.fill(text) })
+    .collect()
 }
 
-fn current_jst() -> DateTime<chrono_tz::Tz> {
-    Utc::now().with_timezone(&Tokyo)
+/// 最近の投稿を繰り返さないよう伝える system メッセージ
+fn avoid_recent_message(template: &str, recent: &[String]) -> Option<ChatMessage> {
+    list_message(template, "{{RECENT_POSTS}}", recent)
 }
 
-fn apply_free_toot_user_prompt(messages: &mut [ChatMessage], season: &str, time_label: &str) {
-    // 最後の user メッセージを書き換える
-    if let Some(user_msg) = messages.iter_mut().rev().find(|m| m.role == "user") {
-        // fine-tune に合わせて、季節＋時間帯の指示を埋め込む
-        user_msg.content = format!("{season}の{time_label}のような投稿を生成してください。");
+/// 似すぎて使えなかった下書きを伝える system メッセージ
+fn rejected_drafts_message(template: &str, drafts: &[String]) -> Option<ChatMessage> {
+    list_message(template, "{{DRAFTS}}", drafts)
+}
+
+/// `placeholder` に投稿を箇条書きで入れた system メッセージ（投稿もテンプレートも空なら None）
+fn list_message(template: &str, placeholder: &str, posts: &[String]) -> Option<ChatMessage> {
+    if posts.is_empty() || template.trim().is_empty() {
+        return None;
     }
+
+    let posts: Vec<String> = posts.iter().map(|p| format!("- {}", p.replace('\n', " "))).collect();
+    Some(ChatMessage {
+        role: "system".into(),
+        content: template.replace(placeholder, &posts.join("\n")),
+    })
 }
 
-/// 実行用の message 配列を組み立て（JST時刻だけ追記）
-fn build_messages_for_free_toot() -> (Vec<ChatMessage>, &'static str) {
-    build_messages_for_free_toot_at(current_jst())
+/// 直近の投稿との類似度の最大値
+fn max_similarity(text: &str, history: &[String]) -> f32 {
+    history.iter().map(|past| text_similarity(text, past)).fold(0.0, f32::max)
 }
 
+/// 実行用の message 配列を組み立て（日付の指示、話題、最近の投稿、現在時刻を追記）。スロット名も返す
 fn build_messages_for_free_toot_at(
-    jst: DateTime<chrono_tz::Tz>,
-) -> (Vec<ChatMessage>, &'static str) {
-    let (mut messages, slot) = pick_free_toot_prompt_for_slot(free_toot_slot_from_hour(jst.hour()));
-    let season = season_label_from_month(jst.month());
-    let time_label = time_label_from_hour(jst.hour());
-    apply_free_toot_user_prompt(&mut messages, season, time_label);
-
-    // 現在時刻（JST）を追加（systemメッセージとして）
-    messages.push(ChatMessage {
-        role: "system".into(),
-        content: format!("CurrentTime(JST): {}", jst.to_rfc3339()),
-    });
+    prompts: &PromptConfig,
+    now: DateTime<Tz>,
+    topic: Option<&str>,
+    recent: &[String],
+) -> Result<(Vec<ChatMessage>, String)> {
+    let slot = slot_for_hour(&prompts.free_toot_slots, now.hour())
+        .ok_or_else(|| anyhow!("no free toot slots are configured"))?;
+    let mut messages = prompts
+        .free_toot_template(&slot.prompt)
+        .ok_or_else(|| anyhow!("unknown free toot prompt {:?} in slot {}", slot.prompt, slot.name))?
+        .to_vec();
+
+    let vars = FreeTootVars::new(prompts, now, &slot.label);
+    apply_free_toot_user_prompt(&mut messages, &prompts.free_toot_user_prompt, &vars);
+
+    messages.extend(calendar_hint_messages(&prompts.calendar_hints, &vars));
+    if let Some(topic) = topic {
+        messages.push(ChatMessage {
+            role: "system".into(),
+            content: prompts.free_toot_topic.replace("{{TOPIC}}", topic),
+        });
+    }
+    messages.extend(avoid_recent_message(&prompts.free_toot_avoid_recent, recent));
+
+    // 現在時刻を追加（systemメッセージとして）
+    messages.push(ChatMessage { role: "system".into(), content: current_time_instruction(now) });
 
-    (messages, slot)
+    Ok((messages, slot.name.clone()))
 }
 
 fn build_free_toot_tools(enable_web_search: bool) -> Vec<Tool> {
@@ -115,56 +168,290 @@ fn build_free_toot_call<'a>(
     OpenAiCallConfig::for_free_toot(cfg).build(messages, 1024, None, tools)
 }
 
-pub async fn generate_free_toot(client: &Client, cfg: &BotConfig) -> Result<String> {
-    let (messages, slot) = build_messages_for_free_toot();
+/// `history` は直近の自由トゥート（新しい順）。似すぎていたら、その下書きを伝えて作り直す
+///
+/// `topic` があればその話題で書くよう指示する（なければ自由に生成）。
+/// `FREE_TOOT_MAX_ATTEMPTS` 回とも似すぎていたら、その回は投稿しない（None）
+pub async fn generate_free_toot(
+    client: &Client,
+    cfg: &BotConfig,
+    topic: Option<&str>,
+    history: &[String],
+) -> Result<Option<String>> {
+    let recent = &history[..history.len().min(cfg.free_toot_avoid_recent_in_prompt)];
+    let compared = &history[..history.len().min(cfg.free_toot_history_size)];
+    let (messages, slot) =
+        build_messages_for_free_toot_at(&PROMPTS, now_in(cfg.timezone), topic, recent)?;
     println!("[free toot] using {} prompt", slot);
 
     // time ツールは存在しないので使わない。web_search は preview 名称。
     let tools = build_free_toot_tools(cfg.enable_web_search);
-    let args = build_free_toot_call(cfg, messages, tools);
+    let rejected_template = &PROMPTS.free_toot_rejected_drafts;
+
+    generate_distinct(cfg, compared, |rejected| {
+        let mut messages = messages.clone();
+        messages.extend(rejected_drafts_message(rejected_template, &rejected));
+        let args = build_free_toot_call(cfg, messages, tools.clone());
+        async move { Ok(OpenAiCallConfig::for_free_toot(cfg).call(client, args).await?.text) }
+    })
+    .await
+}
 
-    let res = call_responses(client, args, false).await?;
+/// 直近の投稿と似すぎない下書きができるまで `generate` を呼ぶ（これまでに使えなかった下書きを渡す）
+async fn generate_distinct<F, Fut>(
+    cfg: &BotConfig,
+    compared: &[String],
+    mut generate: F,
+) -> Result<Option<String>>
+where
+    F: FnMut(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let max_attempts = cfg.free_toot_max_attempts.max(1);
+    let mut rejected = Vec::new();
+
+    for attempt in 1..=max_attempts {
+        let draft = generate(rejected.clone()).await?;
+
+        let similarity = max_similarity(&draft, compared);
+        if similarity <= cfg.free_toot_similarity_threshold {
+            return Ok(Some(draft));
+        }
+        println!(
+            "[free toot] attempt {attempt}/{max_attempts} too similar to a recent post ({similarity:.2}): {draft}"
+        );
+        rejected.push(draft);
+    }
 
-    Ok(res.text)
+    println!(
+        "[free toot] Skipping this run: all {max_attempts} drafts were too similar to recent posts"
+    );
+    Ok(None)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::test_support::test_config;
+    use chrono::TimeZone;
 
     fn message(role: &str, content: &str) -> ChatMessage {
         ChatMessage { role: role.to_string(), content: content.to_string() }
     }
 
+    fn prompts(json: &str) -> PromptConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn builds_messages_from_default_slots_and_seasons() {
+        let prompts = prompts(
+            r#"{
+                "free_toot_morning": [{"role": "user", "content": "morning"}],
+                "free_toot_day": [{"role": "system", "content": "day"}, {"role": "user", "content": "day"}],
+                "free_toot_night": [{"role": "user", "content": "night"}],
+                "reply_with_context": [],
+                "reply_without_context": []
+            }"#,
+        );
+        let evening = chrono_tz::Asia::Tokyo.with_ymd_and_hms(2025, 4, 1, 17, 0, 0).unwrap();
+
+        let (messages, slot) =
+            build_messages_for_free_toot_at(&prompts, evening, None, &[]).unwrap();
+
+        assert_eq!(slot, "evening");
+        assert_eq!(messages[0].content, "day");
+        assert_eq!(messages[1].content, "春の夕方のような投稿を生成してください。");
+        assert_eq!(messages[2].content, "今日は4月1日（火曜日）、平日です。");
+        assert_eq!(messages[3].content, "CurrentTime(JST): 2025-04-01T17:00:00+09:00");
+    }
+
     #[test]
-    fn free_toot_slot_boundaries_match_existing_hour_ranges() {
-        assert_eq!(free_toot_slot_from_hour(4), FreeTootSlot::Night);
-        assert_eq!(free_toot_slot_from_hour(5), FreeTootSlot::Morning);
-        assert_eq!(free_toot_slot_from_hour(8), FreeTootSlot::Morning);
-        assert_eq!(free_toot_slot_from_hour(9), FreeTootSlot::Day);
-        assert_eq!(free_toot_slot_from_hour(15), FreeTootSlot::Day);
-        assert_eq!(free_toot_slot_from_hour(16), FreeTootSlot::Evening);
-        assert_eq!(free_toot_slot_from_hour(18), FreeTootSlot::Evening);
-        assert_eq!(free_toot_slot_from_hour(19), FreeTootSlot::Night);
+    fn builds_messages_from_custom_slots_seasons_and_templates() {
+        let prompts = prompts(
+            r#"{
+                "reply_with_context": [],
+                "reply_without_context": [],
+                "free_toot_templates": {
+                    "late": [{"role": "user", "content": "x"}]
+                },
+                "free_toot_slots": [
+                    {"name": "late", "start_hour": 22, "end_hour": 2, "label": "late night", "prompt": "late"}
+                ],
+                "seasons": [{"label": "summer", "months": [12, 1, 2]}],
+                "free_toot_user_prompt": "Write a {{SEASON}} {{TIME_LABEL}} post."
+            }"#,
+        );
+        let now = chrono_tz::Australia::Sydney.with_ymd_and_hms(2025, 1, 10, 23, 0, 0).unwrap();
+
+        let (messages, slot) = build_messages_for_free_toot_at(&prompts, now, None, &[]).unwrap();
+
+        assert_eq!(slot, "late");
+        assert_eq!(messages[0].content, "Write a summer late night post.");
+        assert!(messages[2].content.starts_with("CurrentTime(AEDT): 2025-01-10T23:00:00"));
     }
 
     #[test]
-    fn season_labels_match_existing_month_ranges() {
-        assert_eq!(season_label_from_month(1), "冬");
-        assert_eq!(season_label_from_month(3), "春");
-        assert_eq!(season_label_from_month(6), "夏");
-        assert_eq!(season_label_from_month(9), "秋");
-        assert_eq!(season_label_from_month(12), "冬");
+    fn reports_unknown_slot_prompt_key() {
+        let prompts = prompts(
+            r#"{
+                "reply_with_context": [],
+                "reply_without_context": [],
+                "free_toot_slots": [
+                    {"name": "all", "start_hour": 0, "end_hour": 23, "label": "x", "prompt": "missing"}
+                ]
+            }"#,
+        );
+        let now = chrono_tz::Asia::Tokyo.with_ymd_and_hms(2025, 1, 10, 10, 0, 0).unwrap();
+
+        let err = build_messages_for_free_toot_at(&prompts, now, None, &[]).unwrap_err();
+
+        assert!(err.to_string().contains("\"missing\""));
     }
 
     #[test]
-    fn time_labels_match_existing_hour_ranges() {
-        assert_eq!(time_label_from_hour(4), "夜");
-        assert_eq!(time_label_from_hour(5), "朝");
-        assert_eq!(time_label_from_hour(9), "昼");
-        assert_eq!(time_label_from_hour(16), "夕方");
-        assert_eq!(time_label_from_hour(19), "夜");
+    fn adds_recent_posts_before_current_time() {
+        let prompts = prompts(
+            r#"{
+                "free_toot_morning": [{"role": "user", "content": "morning"}],
+                "free_toot_day": [{"role": "user", "content": "day"}],
+                "free_toot_night": [{"role": "user", "content": "night"}],
+                "reply_with_context": [],
+                "reply_without_context": [],
+                "free_toot_avoid_recent": "Avoid:\n{{RECENT_POSTS}}",
+                "calendar_hints": {"today": ""}
+            }"#,
+        );
+        let morning = chrono_tz::Asia::Tokyo.with_ymd_and_hms(2025, 4, 1, 7, 0, 0).unwrap();
+        let recent = vec!["おはよう\n眠い".to_string(), "朝ごはん".to_string()];
+
+        let (messages, _) =
+            build_messages_for_free_toot_at(&prompts, morning, None, &recent).unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].role, "system");
+        assert_eq!(messages[1].content, "Avoid:\n- おはよう 眠い\n- 朝ごはん");
+        assert!(messages[2].content.starts_with("CurrentTime"));
+    }
+
+    #[test]
+    fn exposes_holidays_and_special_dates_as_hints_and_variables() {
+        let prompts = prompts(
+            r#"{
+                "free_toot_morning": [{"role": "system", "content": "{{DAY_TYPE}}"}, {"role": "user", "content": "u"}],
+                "free_toot_day": [{"role": "user", "content": "day"}],
+                "free_toot_night": [{"role": "user", "content": "night"}],
+                "reply_with_context": [],
+                "reply_without_context": [],
+                "free_toot_user_prompt": "{{DATE}} {{WEEKDAY}} {{HOLIDAY}} {{SPECIAL_DAY}}",
+                "special_dates": [
+                    {"date": "05-05", "label": "鯉のぼりの日"},
+                    {"date": "2025-05-05", "label": "開設記念日"},
+                    {"date": "2024-05-05", "label": "去年だけ"}
+                ]
+            }"#,
+        );
+        let morning = chrono_tz::Asia::Tokyo.with_ymd_and_hms(2025, 5, 5, 8, 0, 0).unwrap();
+
+        let (messages, _) = build_messages_for_free_toot_at(&prompts, morning, None, &[]).unwrap();
+
+        assert_eq!(messages[0].content, "祝日");
+        assert_eq!(messages[1].content, "5月5日 月 こどもの日 鯉のぼりの日、開設記念日");
+        assert_eq!(messages[2].content, "今日は5月5日（月曜日）、祝日です。");
+        assert_eq!(messages[3].content, "今日は祝日「こどもの日」です。");
+        assert_eq!(
+            messages[4].content,
+            "今日は「鯉のぼりの日、開設記念日」です。話題にしてもかまいません。"
+        );
+        assert!(messages[5].content.starts_with("CurrentTime"));
+    }
+
+    #[test]
+    fn weekend_day_type_without_holiday() {
+        let prompts = prompts(r#"{"reply_with_context": [], "reply_without_context": []}"#);
+        let saturday = chrono_tz::Asia::Tokyo.with_ymd_and_hms(2025, 4, 5, 12, 0, 0).unwrap();
+
+        let vars = FreeTootVars::new(&prompts, saturday, "昼");
+
+        assert_eq!(vars.get("DAY_TYPE"), "週末");
+        assert_eq!(vars.get("WEEKDAY"), "土");
+        assert_eq!(vars.get("HOLIDAY"), "");
+        assert_eq!(calendar_hint_messages(&prompts.calendar_hints, &vars).len(), 1);
+    }
+
+    #[test]
+    fn adds_topic_instruction_when_queued() {
+        let prompts = prompts(
+            r#"{
+                "free_toot_morning": [{"role": "user", "content": "morning"}],
+                "reply_with_context": [],
+                "reply_without_context": [],
+                "free_toot_topic": "Topic: {{TOPIC}}",
+                "calendar_hints": {"today": ""}
+            }"#,
+        );
+        let morning = chrono_tz::Asia::Tokyo.with_ymd_and_hms(2025, 4, 1, 7, 0, 0).unwrap();
+
+        let (messages, _) =
+            build_messages_for_free_toot_at(&prompts, morning, Some("maintenance day"), &[])
+                .unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].role, "system");
+        assert_eq!(messages[1].content, "Topic: maintenance day");
+    }
+
+    #[test]
+    fn max_similarity_takes_closest_recent_post() {
+        let history =
+            vec!["夜のラーメンは罪の味".to_string(), "おはよう、今日もいい天気".to_string()];
+
+        assert_eq!(max_similarity("おはよう！今日もいい天気", &history), 1.0);
+        assert!(max_similarity("週末は映画を観たい", &history) < 0.2);
+        assert_eq!(max_similarity("anything", &[]), 0.0);
+    }
+
+    #[tokio::test]
+    async fn retries_with_rejected_drafts_and_skips_when_exhausted() {
+        let cfg = test_config();
+        let history = vec!["おはよう、今日もいい天気".to_string()];
+        let mut seen = Vec::new();
+
+        let result = generate_distinct(&cfg, &history, |rejected| {
+            seen.push(rejected);
+            async { Ok("おはよう！今日もいい天気".to_string()) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(result, None);
+        assert_eq!(seen.len(), 3);
+        assert!(seen[0].is_empty());
+        assert_eq!(seen[2], vec!["おはよう！今日もいい天気"; 2]);
+    }
+
+    #[tokio::test]
+    async fn returns_the_first_distinct_draft() {
+        let cfg = test_config();
+        let history = vec!["おはよう、今日もいい天気".to_string()];
+        let mut drafts = vec!["週末は映画を観たい", "おはよう！今日もいい天気"];
+
+        let result = generate_distinct(&cfg, &history, |_| {
+            let draft = drafts.pop().unwrap().to_string();
+            async move { Ok(draft) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(result.as_deref(), Some("週末は映画を観たい"));
+    }
+
+    #[test]
+    fn rejected_drafts_are_listed_in_one_line_each() {
+        let message = rejected_drafts_message("Not these:\n{{DRAFTS}}", &["a\nb".into()]).unwrap();
+
+        assert_eq!(message.content, "Not these:\n- a b");
+        assert!(rejected_drafts_message("{{DRAFTS}}", &[]).is_none());
     }
 
     #[test]
@@ -172,7 +459,15 @@ mod tests {
         let mut messages =
             vec![message("user", "first"), message("system", "system"), message("user", "last")];
 
-        apply_free_toot_user_prompt(&mut messages, "春", "朝");
+        let prompts = prompts(r#"{"reply_with_context": [], "reply_without_context": []}"#);
+        let morning = chrono_tz::Asia::Tokyo.with_ymd_and_hms(2025, 4, 1, 7, 0, 0).unwrap();
+        let vars = FreeTootVars::new(&prompts, morning, "朝");
+
+        apply_free_toot_user_prompt(
+            &mut messages,
+            "{{SEASON}}の{{TIME_LABEL}}のような投稿を生成してください。",
+            &vars,
+        );
 
         assert_eq!(messages[0].content, "first");
         assert_eq!(messages[1].content, "system");
@@ -206,6 +501,7 @@ mod tests {
             Tool::WebSearchPreview { search_context_size } => {
                 assert_eq!(search_context_size.as_deref(), None);
             }
+            other => panic!("unexpected tool: {other:?}"),
         }
     }
 }
@@ -1,9 +1,21 @@
 mod call_config;
+mod fallback;
+mod feed_digest;
 mod free_toot;
+mod persona;
 mod prompts;
 mod reply;
 mod stream;
+mod time;
+mod tools;
 mod types;
 
+pub use fallback::is_previous_response_not_found;
+pub use feed_digest::generate_feed_digest;
 pub use free_toot::generate_free_toot;
-pub use reply::generate_reply;
+pub use persona::{PersonaTarget, is_known_persona, persona_names, select_persona};
+pub use prompts::{
+    CommandConfig, access_denied_notice, check_prompts_file, command_config, loop_closing_message,
+    prompts_path_from_env,
+};
+pub use reply::{ReplyResult, generate_reply};
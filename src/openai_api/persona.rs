@@ -0,0 +1,122 @@
+//! メンションごとのペルソナ選択
+
+use crate::openai_api::prompts::{DEFAULT_PERSONA, PROMPTS, PersonaRule};
+
+/// ペルソナ選択に使うメンションの属性
+pub struct PersonaTarget<'a> {
+    pub text: &'a str,
+    pub acct: &'a str,
+    pub language: Option<&'a str>,
+}
+
+/// prompts の `persona_rules` でペルソナを選ぶ。どれにも一致しなければ `default`
+pub fn select_persona(target: &PersonaTarget<'_>) -> String {
+    select_persona_from_rules(&PROMPTS.persona_rules, target).to_string()
+}
+
+/// 保存済みのペルソナ名が今の prompts にも存在するか
+pub fn is_known_persona(persona: &str) -> bool {
+    PROMPTS.has_persona(persona)
+}
+
+/// `/persona` で選べるペルソナ名
+pub fn persona_names() -> Vec<&'static str> {
+    PROMPTS.persona_names()
+}
+
+fn select_persona_from_rules<'r>(rules: &'r [PersonaRule], target: &PersonaTarget<'_>) -> &'r str {
+    rules
+        .iter()
+        .find(|rule| rule_matches(rule, target))
+        .map(|rule| rule.persona.as_str())
+        .unwrap_or(DEFAULT_PERSONA)
+}
+
+fn rule_matches(rule: &PersonaRule, target: &PersonaTarget<'_>) -> bool {
+    let text = target.text.to_lowercase();
+    let keyword_ok = rule.keywords.is_empty()
+        || rule.keywords.iter().any(|k| !k.is_empty() && text.contains(&k.to_lowercase()));
+
+    let acct_ok = rule.accounts.is_empty()
+        || rule
+            .accounts
+            .iter()
+            .any(|a| a.trim_start_matches('@').eq_ignore_ascii_case(target.acct));
+
+    let language_ok = rule.languages.is_empty()
+        || target
+            .language
+            .is_some_and(|lang| rule.languages.iter().any(|l| language_matches(l, lang)));
+
+    keyword_ok && acct_ok && language_ok
+}
+
+fn language_matches(rule_lang: &str, status_lang: &str) -> bool {
+    let primary = status_lang.split(['-', '_']).next().unwrap_or(status_lang);
+    rule_lang.eq_ignore_ascii_case(status_lang) || rule_lang.eq_ignore_ascii_case(primary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(
+        persona: &str,
+        keywords: &[&str],
+        accounts: &[&str],
+        languages: &[&str],
+    ) -> PersonaRule {
+        PersonaRule {
+            persona: persona.to_string(),
+            keywords: keywords.iter().map(|s| s.to_string()).collect(),
+            accounts: accounts.iter().map(|s| s.to_string()).collect(),
+            languages: languages.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn target<'a>(text: &'a str, acct: &'a str, language: Option<&'a str>) -> PersonaTarget<'a> {
+        PersonaTarget { text, acct, language }
+    }
+
+    #[test]
+    fn falls_back_to_default_without_matching_rule() {
+        let rules = vec![rule("staff", &[], &["admin"], &[])];
+
+        assert_eq!(select_persona_from_rules(&rules, &target("hi", "alice", None)), "default");
+        assert_eq!(select_persona_from_rules(&[], &target("hi", "alice", None)), "default");
+    }
+
+    #[test]
+    fn matches_keyword_account_and_language() {
+        let rules = vec![
+            rule("staff", &[], &["@Admin"], &[]),
+            rule("poet", &["#Haiku"], &[], &[]),
+            rule("english", &[], &[], &["en"]),
+        ];
+
+        assert_eq!(select_persona_from_rules(&rules, &target("hi", "admin", None)), "staff");
+        assert_eq!(select_persona_from_rules(&rules, &target("一句 #haiku", "bob", None)), "poet");
+        assert_eq!(
+            select_persona_from_rules(&rules, &target("hello", "bob", Some("en-US"))),
+            "english"
+        );
+        assert_eq!(
+            select_persona_from_rules(&rules, &target("hello", "bob", Some("ja"))),
+            "default"
+        );
+    }
+
+    #[test]
+    fn requires_all_given_conditions_of_a_rule() {
+        let rules = vec![rule("staff_en", &[], &["admin"], &["en"])];
+
+        assert_eq!(
+            select_persona_from_rules(&rules, &target("hi", "admin", Some("ja"))),
+            "default"
+        );
+        assert_eq!(
+            select_persona_from_rules(&rules, &target("hi", "admin", Some("en"))),
+            "staff_en"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+
+/// 自由トゥートで話題にできる特別な日（インスタンスの記念日、イベントなど）
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpecialDateDef {
+    /// 毎年なら `MM-DD`、その年だけなら `YYYY-MM-DD`
+    pub date: String,
+    /// `{{SPECIAL_DAY}}` に入る呼び名
+    pub label: String,
+}
+
+impl SpecialDateDef {
+    pub fn matches(&self, date: NaiveDate) -> bool {
+        match parse_special_date(&self.date) {
+            Some((Some(year), month, day)) => {
+                (year, month, day) == (date.year(), date.month(), date.day())
+            }
+            Some((None, month, day)) => (month, day) == (date.month(), date.day()),
+            None => false,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        parse_special_date(&self.date).is_some()
+    }
+}
+
+/// `MM-DD` / `YYYY-MM-DD` → (年, 月, 日)
+fn parse_special_date(s: &str) -> Option<(Option<i32>, u32, u32)> {
+    let parts: Vec<&str> = s.trim().split('-').collect();
+    let (year, month, day) = match parts.as_slice() {
+        [m, d] => (None, m.parse().ok()?, d.parse().ok()?),
+        [y, m, d] => (Some(y.parse().ok()?), m.parse().ok()?, d.parse().ok()?),
+        _ => return None,
+    };
+    // 2/29 も毎年の指定として受け付ける
+    NaiveDate::from_ymd_opt(year.unwrap_or(2000), month, day)?;
+    Some((year, month, day))
+}
+
+/// 自由トゥートに追加する日付の system 指示
+///
+/// 自由トゥート用のプレースホルダーがすべて使える。空文字にした指示は追加しない。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CalendarHints {
+    /// 毎回追加する
+    pub today: String,
+    /// 祝日（振替休日・国民の休日を含む）のとき追加する
+    pub holiday: String,
+    /// `special_dates` に一致したとき追加する
+    pub special_day: String,
+}
+
+impl Default for CalendarHints {
+    fn default() -> Self {
+        Self {
+            today: "今日は{{DATE}}（{{WEEKDAY}}曜日）、{{DAY_TYPE}}です。".into(),
+            holiday: "今日は祝日「{{HOLIDAY}}」です。".into(),
+            special_day: "今日は「{{SPECIAL_DAY}}」です。話題にしてもかまいません。".into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn special(date: &str) -> SpecialDateDef {
+        SpecialDateDef { date: date.to_string(), label: "x".to_string() }
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn special_dates_match_yearly_or_once() {
+        assert!(special("04-01").matches(date(2025, 4, 1)));
+        assert!(special("04-01").matches(date(2030, 4, 1)));
+        assert!(special("2025-11-03").matches(date(2025, 11, 3)));
+        assert!(!special("2025-11-03").matches(date(2026, 11, 3)));
+        assert!(special("02-29").matches(date(2028, 2, 29)));
+    }
+
+    #[test]
+    fn rejects_malformed_special_dates() {
+        assert!(!special("4/1").is_valid());
+        assert!(!special("13-01").is_valid());
+        assert!(!special("2025-02-30").is_valid());
+        assert!(!special("2025-02-30").matches(date(2025, 3, 2)));
+        assert!(special("2025-12-31").is_valid());
+    }
+}
@@ -0,0 +1,663 @@
+//! `check-prompts` モード用のプロンプトファイル検査
+
+use std::fmt;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::{
+    DEFAULT_PERSONA, PromptConfig, SearchRule, load_prompts_from_path, resolve_prompts_path,
+};
+use crate::openai_api::types::ChatMessage;
+
+static PLACEHOLDER_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{([^{}]*)\}\}").unwrap());
+
+const KNOWN_ROLES: [&str; 3] = ["system", "user", "assistant"];
+const REPLY_PLACEHOLDERS: [&str; 3] = ["USER_TEXT", "CONTEXT", "LANGUAGE"];
+const REPLY_LANGUAGE_PLACEHOLDERS: [&str; 1] = ["LANGUAGE"];
+/// 自由トゥートのテンプレート・`free_toot_user_prompt`・`calendar_hints` で使える
+const FREE_TOOT_PLACEHOLDERS: [&str; 7] =
+    ["SEASON", "TIME_LABEL", "DATE", "WEEKDAY", "DAY_TYPE", "HOLIDAY", "SPECIAL_DAY"];
+const FREE_TOOT_TOPIC_PLACEHOLDERS: [&str; 1] = ["TOPIC"];
+const FREE_TOOT_AVOID_RECENT_PLACEHOLDERS: [&str; 1] = ["RECENT_POSTS"];
+const FREE_TOOT_REJECTED_DRAFTS_PLACEHOLDERS: [&str; 1] = ["DRAFTS"];
+const FEED_DIGEST_PLACEHOLDERS: [&str; 4] = ["FEED_TITLE", "TITLE", "SUMMARY", "LINK"];
+const COMMAND_COUNT_PLACEHOLDERS: [&str; 1] = ["COUNT"];
+const COMMAND_PERSONA_PLACEHOLDERS: [&str; 1] = ["PERSONA"];
+const COMMAND_PERSONAS_PLACEHOLDERS: [&str; 1] = ["PERSONAS"];
+const COMMAND_HELP_PLACEHOLDERS: [&str; 1] = ["COMMANDS"];
+const COMMAND_REASONS_PLACEHOLDERS: [&str; 1] = ["REASONS"];
+const SEARCH_RULE_PLACEHOLDERS: [&str; 1] = ["MATCH"];
+const SEARCH_CONTEXT_SIZES: [&str; 3] = ["low", "medium", "high"];
+const BUILTIN_FREE_TOOT_KEYS: [&str; 3] = ["free_toot_morning", "free_toot_day", "free_toot_night"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct PromptIssue {
+    pub severity: Severity,
+    /// 例: `reply_with_context[0].content`
+    pub location: String,
+    pub message: String,
+}
+
+/// プロンプトファイルの検査結果
+#[derive(Debug)]
+pub struct PromptCheckReport {
+    pub path: String,
+    pub issues: Vec<PromptIssue>,
+}
+
+impl PromptCheckReport {
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.issues.iter().filter(|i| i.severity == severity).count()
+    }
+}
+
+impl fmt::Display for PromptCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Checking prompts: {}", self.path)?;
+        for issue in &self.issues {
+            let label = match issue.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            writeln!(f, "  {label}: {}: {}", issue.location, issue.message)?;
+        }
+
+        let errors = self.count(Severity::Error);
+        let warnings = self.count(Severity::Warning);
+        if errors == 0 && warnings == 0 {
+            write!(f, "OK")
+        } else {
+            write!(f, "{errors} error(s), {warnings} warning(s)")
+        }
+    }
+}
+
+/// `load_prompts_from_path` で読み込み、内容を検査する
+pub fn check_prompts_file(raw_path: &str) -> PromptCheckReport {
+    let path = resolve_prompts_path(raw_path).display().to_string();
+
+    let issues = match load_prompts_from_path(raw_path) {
+        Ok(prompts) => check_prompt_config(&prompts),
+        Err(e) => vec![PromptIssue {
+            severity: Severity::Error,
+            location: raw_path.to_string(),
+            message: e.to_string(),
+        }],
+    };
+
+    PromptCheckReport { path, issues }
+}
+
+fn check_prompt_config(prompts: &PromptConfig) -> Vec<PromptIssue> {
+    let mut issues = Vec::new();
+
+    check_free_toot_slots(&mut issues, prompts);
+    check_seasons(&mut issues, prompts);
+    if prompts.free_toot_user_prompt.trim().is_empty() {
+        issues.push(error("free_toot_user_prompt".to_string(), "text is empty"));
+    }
+    check_placeholders(
+        &mut issues,
+        "free_toot_user_prompt",
+        &prompts.free_toot_user_prompt,
+        &FREE_TOOT_PLACEHOLDERS,
+    );
+    if prompts.free_toot_topic.trim().is_empty() {
+        issues.push(error("free_toot_topic".to_string(), "text is empty"));
+    }
+    check_placeholders(
+        &mut issues,
+        "free_toot_topic",
+        &prompts.free_toot_topic,
+        &FREE_TOOT_TOPIC_PLACEHOLDERS,
+    );
+    check_placeholders(
+        &mut issues,
+        "free_toot_avoid_recent",
+        &prompts.free_toot_avoid_recent,
+        &FREE_TOOT_AVOID_RECENT_PLACEHOLDERS,
+    );
+    check_placeholders(
+        &mut issues,
+        "free_toot_rejected_drafts",
+        &prompts.free_toot_rejected_drafts,
+        &FREE_TOOT_REJECTED_DRAFTS_PLACEHOLDERS,
+    );
+
+    check_calendar(&mut issues, prompts);
+    check_template(&mut issues, "feed_digest", &prompts.feed_digest, &FEED_DIGEST_PLACEHOLDERS);
+
+    for (key, template) in [
+        ("reply_with_context", &prompts.reply_with_context),
+        ("reply_without_context", &prompts.reply_without_context),
+    ] {
+        check_template(&mut issues, key, template, &REPLY_PLACEHOLDERS);
+    }
+
+    let mut persona_names: Vec<&String> = prompts.personas.keys().collect();
+    persona_names.sort();
+    for name in persona_names {
+        let persona = &prompts.personas[name];
+        if name == DEFAULT_PERSONA {
+            issues.push(warning(
+                format!("personas.{name}"),
+                "shadowed by the top-level reply prompts and never used",
+            ));
+        }
+        for (key, template) in [
+            ("reply_with_context", &persona.reply_with_context),
+            ("reply_without_context", &persona.reply_without_context),
+        ] {
+            check_template(
+                &mut issues,
+                &format!("personas.{name}.{key}"),
+                template,
+                &REPLY_PLACEHOLDERS,
+            );
+        }
+    }
+
+    if prompts.reply_instructions.json_fallback_reply.trim().is_empty() {
+        issues.push(error(
+            "reply_instructions.json_fallback_reply".to_string(),
+            "fallback reply must not be empty (it is posted as-is)",
+        ));
+    }
+
+    check_placeholders(
+        &mut issues,
+        "reply_instructions.reply_language",
+        &prompts.reply_instructions.reply_language,
+        &REPLY_LANGUAGE_PLACEHOLDERS,
+    );
+
+    check_commands(&mut issues, prompts);
+    check_search_rules(&mut issues, prompts);
+
+    for (i, rule) in prompts.persona_rules.iter().enumerate() {
+        if !prompts.has_persona(&rule.persona) {
+            issues.push(error(
+                format!("persona_rules[{i}].persona"),
+                format!("unknown persona {:?}", rule.persona),
+            ));
+        }
+    }
+
+    issues
+}
+
+fn check_template(
+    issues: &mut Vec<PromptIssue>,
+    key: &str,
+    template: &[ChatMessage],
+    known_placeholders: &[&str],
+) {
+    if template.is_empty() {
+        issues.push(error(key.to_string(), "template has no messages"));
+        return;
+    }
+
+    for (i, msg) in template.iter().enumerate() {
+        if !KNOWN_ROLES.contains(&msg.role.as_str()) {
+            issues.push(error(
+                format!("{key}[{i}].role"),
+                format!("unknown role {:?} (expected one of {})", msg.role, KNOWN_ROLES.join("/")),
+            ));
+        }
+
+        if msg.content.trim().is_empty() {
+            issues.push(error(format!("{key}[{i}].content"), "message content is empty"));
+        }
+
+        check_placeholders(
+            issues,
+            &format!("{key}[{i}].content"),
+            &msg.content,
+            known_placeholders,
+        );
+    }
+}
+
+fn check_placeholders(
+    issues: &mut Vec<PromptIssue>,
+    location: &str,
+    text: &str,
+    known_placeholders: &[&str],
+) {
+    for caps in PLACEHOLDER_RE.captures_iter(text) {
+        let name = &caps[1];
+        if !known_placeholders.contains(&name) {
+            let known = if known_placeholders.is_empty() {
+                "none are supported here".to_string()
+            } else {
+                format!("known: {}", known_placeholders.join(", "))
+            };
+            issues.push(error(
+                location.to_string(),
+                format!("unknown placeholder {{{{{name}}}}} ({known})"),
+            ));
+        }
+    }
+}
+
+fn check_free_toot_slots(issues: &mut Vec<PromptIssue>, prompts: &PromptConfig) {
+    if prompts.free_toot_slots.is_empty() {
+        issues.push(error("free_toot_slots".to_string(), "at least one slot is required"));
+        return;
+    }
+
+    let mut referenced: Vec<&str> = Vec::new();
+    for (i, slot) in prompts.free_toot_slots.iter().enumerate() {
+        for (field, hour) in [("start_hour", slot.start_hour), ("end_hour", slot.end_hour)] {
+            if hour > 23 {
+                issues.push(error(
+                    format!("free_toot_slots[{i}].{field}"),
+                    format!("hour {hour} is out of range 0-23"),
+                ));
+            }
+        }
+
+        if prompts.free_toot_template(&slot.prompt).is_none() {
+            issues.push(error(
+                format!("free_toot_slots[{i}].prompt"),
+                format!("unknown free toot template {:?}", slot.prompt),
+            ));
+        } else if !referenced.contains(&slot.prompt.as_str()) {
+            referenced.push(&slot.prompt);
+        }
+    }
+
+    for key in &referenced {
+        let template = prompts.free_toot_template(key).unwrap_or_default();
+        let location = if BUILTIN_FREE_TOOT_KEYS.contains(key) {
+            key.to_string()
+        } else {
+            format!("free_toot_templates.{key}")
+        };
+        check_template(issues, &location, template, &FREE_TOOT_PLACEHOLDERS);
+        check_free_toot_user_message(issues, &location, template);
+    }
+
+    let mut unused: Vec<&String> =
+        prompts.free_toot_templates.keys().filter(|k| !referenced.contains(&k.as_str())).collect();
+    unused.sort();
+    for key in unused {
+        issues.push(warning(format!("free_toot_templates.{key}"), "not used by any slot"));
+    }
+
+    let uncovered: Vec<String> = (0..24)
+        .filter(|h| !prompts.free_toot_slots.iter().any(|s| s.contains_hour(*h)))
+        .map(|h| h.to_string())
+        .collect();
+    if !uncovered.is_empty() {
+        issues.push(warning(
+            "free_toot_slots".to_string(),
+            format!("hours {} are not covered; the last slot is used", uncovered.join(",")),
+        ));
+    }
+}
+
+fn check_seasons(issues: &mut Vec<PromptIssue>, prompts: &PromptConfig) {
+    for (i, season) in prompts.seasons.iter().enumerate() {
+        if let Some(month) = season.months.iter().find(|m| !(1..=12).contains(*m)) {
+            issues.push(error(
+                format!("seasons[{i}].months"),
+                format!("month {month} is out of range 1-12"),
+            ));
+        }
+    }
+
+    let uncovered: Vec<String> = (1..=12)
+        .filter(|m| !prompts.seasons.iter().any(|s| s.months.contains(m)))
+        .map(|m| m.to_string())
+        .collect();
+    if !uncovered.is_empty() {
+        issues.push(warning(
+            "seasons".to_string(),
+            format!("months {} have no season; {{{{SEASON}}}} becomes empty", uncovered.join(",")),
+        ));
+    }
+}
+
+fn check_calendar(issues: &mut Vec<PromptIssue>, prompts: &PromptConfig) {
+    for (i, special) in prompts.special_dates.iter().enumerate() {
+        if !special.is_valid() {
+            issues.push(error(
+                format!("special_dates[{i}].date"),
+                format!("{:?} is not MM-DD or YYYY-MM-DD", special.date),
+            ));
+        }
+        if special.label.trim().is_empty() {
+            issues.push(error(format!("special_dates[{i}].label"), "label is empty"));
+        }
+    }
+
+    let hints = &prompts.calendar_hints;
+    for (key, text) in
+        [("today", &hints.today), ("holiday", &hints.holiday), ("special_day", &hints.special_day)]
+    {
+        check_placeholders(issues, &format!("calendar_hints.{key}"), text, &FREE_TOOT_PLACEHOLDERS);
+    }
+}
+
+fn check_commands(issues: &mut Vec<PromptIssue>, prompts: &PromptConfig) {
+    let commands = &prompts.commands;
+    let mut seen: Vec<(String, &str)> = Vec::new();
+
+    for (name, def, placeholders) in [
+        ("reset", &commands.reset, &[][..]),
+        ("help", &commands.help, &COMMAND_HELP_PLACEHOLDERS[..]),
+        ("forget", &commands.forget, &COMMAND_COUNT_PLACEHOLDERS[..]),
+        ("persona", &commands.persona, &COMMAND_PERSONA_PLACEHOLDERS[..]),
+        ("optout", &commands.optout, &[][..]),
+        ("optin", &commands.optin, &[][..]),
+    ] {
+        for alias in &def.aliases {
+            let location = format!("commands.{name}.aliases");
+            if alias.trim().is_empty() || alias.chars().any(char::is_whitespace) {
+                issues.push(error(location, format!("{alias:?} must be a single word")));
+                continue;
+            }
+            let key = alias.to_lowercase();
+            if let Some((_, other)) = seen.iter().find(|(k, _)| *k == key) {
+                issues.push(error(location, format!("{alias:?} is already used by {other}")));
+                continue;
+            }
+            seen.push((key, name));
+        }
+
+        let location = format!("commands.{name}.reply");
+        if !def.aliases.is_empty() && def.reply.trim().is_empty() {
+            issues.push(error(location.clone(), "reply is empty"));
+        }
+        check_placeholders(issues, &location, &def.reply, placeholders);
+    }
+
+    check_placeholders(
+        issues,
+        "commands.persona_unknown",
+        &commands.persona_unknown,
+        &COMMAND_PERSONAS_PLACEHOLDERS,
+    );
+    check_placeholders(
+        issues,
+        "commands.optin_blocked",
+        &commands.optin_blocked,
+        &COMMAND_REASONS_PLACEHOLDERS,
+    );
+}
+
+fn check_search_rules(issues: &mut Vec<PromptIssue>, prompts: &PromptConfig) {
+    for (i, def) in prompts.search_rules.iter().enumerate() {
+        let location = format!("search_rules[{i}]");
+        if def.name.trim().is_empty() {
+            issues.push(error(format!("{location}.name"), "name is empty"));
+        }
+        if def.keywords.iter().all(|k| k.trim().is_empty()) && def.patterns.is_empty() {
+            issues.push(warning(
+                location.clone(),
+                "rule has no keywords or patterns and never matches",
+            ));
+        }
+        if let Err(e) = SearchRule::compile(def) {
+            issues.push(error(format!("{location}.patterns"), format!("invalid regex: {e}")));
+        }
+        for (j, instruction) in def.instructions.iter().enumerate() {
+            check_placeholders(
+                issues,
+                &format!("{location}.instructions[{j}]"),
+                instruction,
+                &SEARCH_RULE_PLACEHOLDERS,
+            );
+        }
+        if let Some(size) = &def.search_context_size
+            && !SEARCH_CONTEXT_SIZES.contains(&size.as_str())
+        {
+            issues.push(error(
+                format!("{location}.search_context_size"),
+                format!("{size:?} is not one of {}", SEARCH_CONTEXT_SIZES.join("/")),
+            ));
+        }
+    }
+}
+
+/// 自由トゥートは最後の user メッセージを季節・時間帯の指示で上書きするので、必ず 1 つ必要
+fn check_free_toot_user_message(
+    issues: &mut Vec<PromptIssue>,
+    key: &str,
+    template: &[ChatMessage],
+) {
+    if !template.is_empty() && !template.iter().any(|m| m.role == "user") {
+        issues.push(error(
+            key.to_string(),
+            "free toot template needs a user message to be overwritten with the season/time instruction",
+        ));
+    }
+}
+
+fn error(location: String, message: impl Into<String>) -> PromptIssue {
+    PromptIssue { severity: Severity::Error, location, message: message.into() }
+}
+
+fn warning(location: String, message: impl Into<String>) -> PromptIssue {
+    PromptIssue { severity: Severity::Warning, location, message: message.into() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> PromptConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn locations(issues: &[PromptIssue]) -> Vec<&str> {
+        issues.iter().map(|i| i.location.as_str()).collect()
+    }
+
+    #[test]
+    fn valid_config_has_no_issues() {
+        let prompts = parse(
+            r#"{
+                "free_toot_morning": [{"role": "system", "content": "m"}, {"role": "user", "content": "u"}],
+                "free_toot_day": [{"role": "user", "content": "u"}],
+                "free_toot_night": [{"role": "user", "content": "u"}],
+                "reply_with_context": [{"role": "system", "content": "ctx={{CONTEXT}}"}],
+                "reply_without_context": [{"role": "user", "content": "{{USER_TEXT}}"}]
+            }"#,
+        );
+
+        assert!(check_prompt_config(&prompts).is_empty());
+    }
+
+    #[test]
+    fn reports_roles_empty_messages_placeholders_and_missing_user_message() {
+        let prompts = parse(
+            r#"{
+                "free_toot_morning": [{"role": "system", "content": "no user"}],
+                "free_toot_day": [{"role": "user", "content": "{{USER_TEXT}}"}],
+                "free_toot_night": [],
+                "reply_with_context": [{"role": "sytem", "content": "x"}],
+                "reply_without_context": [{"role": "user", "content": "  {{ USER_TEXT }}"}, {"role": "system", "content": " "}],
+                "reply_instructions": {"json_fallback_reply": ""},
+                "persona_rules": [{"persona": "ghost"}]
+            }"#,
+        );
+
+        let issues = check_prompt_config(&prompts);
+
+        assert!(issues.iter().all(|i| i.severity == Severity::Error));
+        assert_eq!(
+            locations(&issues),
+            vec![
+                "free_toot_morning",
+                "free_toot_day[0].content",
+                "free_toot_night",
+                "reply_with_context[0].role",
+                "reply_without_context[0].content",
+                "reply_without_context[1].content",
+                "reply_instructions.json_fallback_reply",
+                "persona_rules[0].persona",
+            ]
+        );
+        assert!(issues[4].message.contains("{{ USER_TEXT }}"));
+    }
+
+    #[test]
+    fn checks_custom_slots_templates_and_seasons() {
+        let prompts = parse(
+            r#"{
+                "reply_with_context": [{"role": "system", "content": "x"}],
+                "reply_without_context": [{"role": "system", "content": "x"}],
+                "free_toot_templates": {
+                    "day": [{"role": "user", "content": "x"}],
+                    "spare": [{"role": "user", "content": "x"}]
+                },
+                "free_toot_slots": [
+                    {"name": "day", "start_hour": 6, "end_hour": 24, "label": "day", "prompt": "day"},
+                    {"name": "night", "start_hour": 0, "end_hour": 4, "label": "night", "prompt": "nope"}
+                ],
+                "seasons": [{"label": "all", "months": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 13]}],
+                "free_toot_user_prompt": "{{SEASON}} {{WEATHER}}"
+            }"#,
+        );
+
+        let issues = check_prompt_config(&prompts);
+
+        assert_eq!(
+            locations(&issues),
+            vec![
+                "free_toot_slots[0].end_hour",
+                "free_toot_slots[1].prompt",
+                "free_toot_templates.spare",
+                "free_toot_slots",
+                "seasons[0].months",
+                "seasons",
+                "free_toot_user_prompt",
+            ]
+        );
+        assert!(issues[3].message.contains("hours 5 are not covered"));
+        assert!(issues[5].message.contains("months 12 have no season"));
+    }
+
+    #[test]
+    fn checks_special_dates_and_calendar_hints() {
+        let prompts = parse(
+            r#"{
+                "free_toot_morning": [{"role": "user", "content": "{{WEEKDAY}}"}],
+                "free_toot_day": [{"role": "user", "content": "u"}],
+                "free_toot_night": [{"role": "user", "content": "u"}],
+                "reply_with_context": [{"role": "system", "content": "x"}],
+                "reply_without_context": [{"role": "system", "content": "x"}],
+                "special_dates": [
+                    {"date": "04-01", "label": "anniversary"},
+                    {"date": "2025/12/31", "label": ""}
+                ],
+                "calendar_hints": {"holiday": "{{HOLIDAY}} {{USER_TEXT}}"}
+            }"#,
+        );
+
+        let issues = check_prompt_config(&prompts);
+
+        assert_eq!(
+            locations(&issues),
+            vec!["special_dates[1].date", "special_dates[1].label", "calendar_hints.holiday"]
+        );
+    }
+
+    #[test]
+    fn checks_command_aliases_and_replies() {
+        let prompts = parse(
+            r#"{
+                "free_toot_morning": [{"role": "user", "content": "u"}],
+                "free_toot_day": [{"role": "user", "content": "u"}],
+                "free_toot_night": [{"role": "user", "content": "u"}],
+                "reply_with_context": [{"role": "system", "content": "x"}],
+                "reply_without_context": [{"role": "system", "content": "x"}],
+                "commands": {
+                    "reset": {"aliases": ["/reset", "/re set"], "reply": ""},
+                    "help": {"aliases": ["/RESET"], "reply": "{{COMMANDS}}"},
+                    "forget": {"aliases": [], "reply": ""},
+                    "persona": {"aliases": ["/persona"], "reply": "{{COUNT}}"}
+                }
+            }"#,
+        );
+
+        let issues = check_prompt_config(&prompts);
+
+        assert_eq!(
+            locations(&issues),
+            vec![
+                "commands.reset.aliases",
+                "commands.reset.reply",
+                "commands.help.aliases",
+                "commands.persona.reply",
+            ]
+        );
+        assert!(issues[2].message.contains("already used by reset"));
+    }
+
+    #[test]
+    fn checks_search_rules() {
+        let prompts = parse(
+            r#"{
+                "free_toot_morning": [{"role": "user", "content": "u"}],
+                "free_toot_day": [{"role": "user", "content": "u"}],
+                "free_toot_night": [{"role": "user", "content": "u"}],
+                "reply_with_context": [{"role": "system", "content": "x"}],
+                "reply_without_context": [{"role": "system", "content": "x"}],
+                "search_rules": [
+                    {"name": "ok", "keywords": ["天気"], "instructions": ["{{MATCH}}"]},
+                    {"name": "", "patterns": ["("], "search_context_size": "huge"},
+                    {"name": "empty", "instructions": ["{{USER_TEXT}}"]}
+                ]
+            }"#,
+        );
+
+        let issues = check_prompt_config(&prompts);
+
+        assert_eq!(
+            locations(&issues),
+            vec![
+                "search_rules[1].name",
+                "search_rules[1].patterns",
+                "search_rules[1].search_context_size",
+                "search_rules[2]",
+                "search_rules[2].instructions[0]",
+            ]
+        );
+    }
+
+    #[test]
+    fn report_display_summarizes_counts() {
+        let report = PromptCheckReport {
+            path: "prompts.json".to_string(),
+            issues: vec![error("a".to_string(), "broken"), warning("b".to_string(), "suspicious")],
+        };
+
+        assert!(report.has_errors());
+        assert_eq!(
+            report.to_string(),
+            "Checking prompts: prompts.json\n  error: a: broken\n  warning: b: suspicious\n1 error(s), 1 warning(s)"
+        );
+    }
+
+    #[test]
+    fn unreadable_file_is_reported_as_error() {
+        let report = check_prompts_file("/nonexistent/mast_gpt_bot/prompts.json");
+
+        assert!(report.has_errors());
+        assert!(report.issues[0].message.contains("Failed to read prompts file"));
+    }
+}
@@ -0,0 +1,117 @@
+use serde::Deserialize;
+
+use super::PROMPTS;
+
+/// メンションで使えるコマンドと、その返信の文面
+///
+/// prompts の `commands` でコマンドごとに上書きできる。省略したコマンドは組み込みの定義を使い、
+/// `aliases` を空にしたコマンドは無効になる。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CommandConfig {
+    /// スレッドの会話をリセット
+    pub reset: CommandDef,
+    /// コマンドの一覧（`{{COMMANDS}}`）
+    pub help: CommandDef,
+    /// 送信者と話したスレッドの記録を消す（`{{COUNT}}`）
+    pub forget: CommandDef,
+    /// スレッドのペルソナを変える（`{{PERSONA}}`）
+    pub persona: CommandDef,
+    /// 以後このアカウントには返信せず、文脈にも含めない
+    pub optout: CommandDef,
+    /// `optout` を取り消す
+    pub optin: CommandDef,
+    /// `/persona` の名前がないか知らないときの返信（`{{PERSONAS}}`）
+    pub persona_unknown: String,
+    /// `/optin` しても拒否リストやプロフィールのタグで返信できないときの返信（`{{REASONS}}`）
+    pub optin_blocked: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommandDef {
+    /// メンション先頭の語がこれに一致したらコマンドとして扱う（大文字小文字は無視）
+    pub aliases: Vec<String>,
+    /// ヘルプに載せる説明
+    #[serde(default)]
+    pub description: String,
+    /// 実行後の返信
+    pub reply: String,
+}
+
+impl CommandDef {
+    fn new(aliases: &[&str], description: &str, reply: &str) -> Self {
+        Self {
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            description: description.into(),
+            reply: reply.into(),
+        }
+    }
+
+    pub fn matches(&self, word: &str) -> bool {
+        self.aliases.iter().any(|alias| alias.to_lowercase() == word.to_lowercase())
+    }
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        Self {
+            reset: CommandDef::new(
+                &["/reset", "/リセット"],
+                "このスレッドの会話をリセット",
+                "会話をリセットしました。次のメンションから新しく話しましょう！",
+            ),
+            help: CommandDef::new(
+                &["/help", "/ヘルプ"],
+                "この一覧",
+                "使えるコマンド:\n{{COMMANDS}}",
+            ),
+            forget: CommandDef::new(
+                &["/forget"],
+                "あなたとの会話の記録を消す（/forget me）",
+                "{{COUNT}} 件のスレッドの記録を消しました。",
+            ),
+            persona: CommandDef::new(
+                &["/persona"],
+                "このスレッドのキャラを変える（/persona 名前）",
+                "このスレッドは {{PERSONA}} で話します。",
+            ),
+            optout: CommandDef::new(
+                &["/optout"],
+                "今後あなたには返信せず、会話の文脈にも使わない",
+                "わかりました。今後はあなたに返信しません（/optin で元に戻せます）。",
+            ),
+            optin: CommandDef::new(
+                &["/optin"],
+                "/optout を取り消す",
+                "おかえりなさい！また話しかけてね。",
+            ),
+            persona_unknown: "そのペルソナは知りません。使えるのは: {{PERSONAS}}".into(),
+            optin_blocked: "/optout は取り消しましたが、まだ返信できません（理由: {{REASONS}}）。"
+                .into(),
+        }
+    }
+}
+
+pub fn command_config() -> &'static CommandConfig {
+    &PROMPTS.commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overriding_one_command_keeps_the_others() {
+        let parsed: CommandConfig = serde_json::from_str(
+            r#"{"reset": {"aliases": ["/RESET", "!reset"], "reply": "ok"}, "forget": {"aliases": [], "reply": ""}}"#,
+        )
+        .unwrap();
+
+        assert!(parsed.reset.matches("!reset"));
+        assert!(parsed.reset.matches("/reset"));
+        assert!(!parsed.reset.matches("/リセット"));
+        assert!(!parsed.forget.matches("/forget"));
+        assert!(parsed.help.matches("/HELP"));
+        assert_eq!(parsed.persona_unknown, CommandConfig::default().persona_unknown);
+    }
+}
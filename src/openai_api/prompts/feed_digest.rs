@@ -0,0 +1,17 @@
+use crate::openai_api::types::ChatMessage;
+
+pub(super) fn default_feed_digest() -> Vec<ChatMessage> {
+    let message = |role: &str, content: &str| ChatMessage {
+        role: role.to_string(),
+        content: content.to_string(),
+    };
+
+    vec![
+        message(
+            "system",
+            "あなたは Mastodon ボットです。フィードの新着記事を、いつもの口調で 1〜2 文に要約して紹介してください。\
+URL やリンクは書かず、記事にない内容を付け足さないでください。",
+        ),
+        message("user", "フィード: {{FEED_TITLE}}\nタイトル: {{TITLE}}\n概要: {{SUMMARY}}"),
+    ]
+}
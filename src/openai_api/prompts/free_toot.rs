@@ -0,0 +1,136 @@
+use serde::Deserialize;
+
+/// 自由トゥートの時間帯（スロット）定義
+#[derive(Debug, Clone, Deserialize)]
+pub struct FreeTootSlotDef {
+    /// ログ用の名前
+    pub name: String,
+    /// 開始時（0〜23、この時を含む）
+    pub start_hour: u32,
+    /// 終了時（0〜23、この時を含む）。`start_hour` より小さければ日付をまたぐ
+    pub end_hour: u32,
+    /// `{{TIME_LABEL}}` に入る時間帯の呼び名
+    pub label: String,
+    /// 使うテンプレートのキー（`free_toot_morning` など、または `free_toot_templates` のキー）
+    pub prompt: String,
+}
+
+impl FreeTootSlotDef {
+    pub fn contains_hour(&self, hour: u32) -> bool {
+        if self.start_hour <= self.end_hour {
+            (self.start_hour..=self.end_hour).contains(&hour)
+        } else {
+            hour >= self.start_hour || hour <= self.end_hour
+        }
+    }
+}
+
+/// 季節の定義
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeasonDef {
+    /// `{{SEASON}}` に入る呼び名
+    pub label: String,
+    /// 含まれる月（1〜12）
+    pub months: Vec<u32>,
+}
+
+/// 最初に一致したスロット。どれにも一致しなければ最後のスロット
+pub fn slot_for_hour(slots: &[FreeTootSlotDef], hour: u32) -> Option<&FreeTootSlotDef> {
+    slots.iter().find(|s| s.contains_hour(hour)).or_else(|| slots.last())
+}
+
+pub fn season_for_month(seasons: &[SeasonDef], month: u32) -> Option<&SeasonDef> {
+    seasons.iter().find(|s| s.months.contains(&month))
+}
+
+pub(super) fn default_free_toot_slots() -> Vec<FreeTootSlotDef> {
+    let slot = |name: &str, start_hour, end_hour, label: &str, prompt: &str| FreeTootSlotDef {
+        name: name.to_string(),
+        start_hour,
+        end_hour,
+        label: label.to_string(),
+        prompt: prompt.to_string(),
+    };
+
+    vec![
+        slot("morning", 5, 8, "朝", "free_toot_morning"),
+        slot("day", 9, 15, "昼", "free_toot_day"),
+        // 夕方は専用テンプレートを用意していないので daytime を流用
+        slot("evening", 16, 18, "夕方", "free_toot_day"),
+        slot("night", 19, 4, "夜", "free_toot_night"),
+    ]
+}
+
+pub(super) fn default_seasons() -> Vec<SeasonDef> {
+    let season = |label: &str, months: &[u32]| SeasonDef {
+        label: label.to_string(),
+        months: months.to_vec(),
+    };
+
+    vec![
+        season("春", &[3, 4, 5]),
+        season("夏", &[6, 7, 8]),
+        season("秋", &[9, 10, 11]),
+        season("冬", &[12, 1, 2]),
+    ]
+}
+
+pub(super) fn default_free_toot_user_prompt() -> String {
+    "{{SEASON}}の{{TIME_LABEL}}のような投稿を生成してください。".to_string()
+}
+
+pub(super) fn default_free_toot_avoid_recent() -> String {
+    "次は最近の投稿です。同じ話題や言い回しを繰り返さないでください。\n{{RECENT_POSTS}}".to_string()
+}
+
+pub(super) fn default_free_toot_rejected_drafts() -> String {
+    "次の下書きは最近の投稿に似すぎていました。これらとは違う話題・書き出しで書いてください。\n{{DRAFTS}}"
+        .to_string()
+}
+
+pub(super) fn default_free_toot_topic() -> String {
+    "今回は次の話題について投稿してください。\n{{TOPIC}}".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot_name(hour: u32) -> String {
+        slot_for_hour(&default_free_toot_slots(), hour).unwrap().name.clone()
+    }
+
+    #[test]
+    fn default_slot_boundaries_match_existing_hour_ranges() {
+        assert_eq!(slot_name(4), "night");
+        assert_eq!(slot_name(5), "morning");
+        assert_eq!(slot_name(8), "morning");
+        assert_eq!(slot_name(9), "day");
+        assert_eq!(slot_name(15), "day");
+        assert_eq!(slot_name(16), "evening");
+        assert_eq!(slot_name(18), "evening");
+        assert_eq!(slot_name(19), "night");
+        assert_eq!(slot_name(0), "night");
+    }
+
+    #[test]
+    fn unmatched_hour_falls_back_to_last_slot() {
+        let mut slots = default_free_toot_slots();
+        slots.truncate(2);
+
+        assert_eq!(slot_for_hour(&slots, 22).unwrap().name, "day");
+        assert!(slot_for_hour(&[], 22).is_none());
+    }
+
+    #[test]
+    fn default_seasons_match_existing_month_ranges() {
+        let seasons = default_seasons();
+        let label = |month| season_for_month(&seasons, month).unwrap().label.clone();
+
+        assert_eq!(label(1), "冬");
+        assert_eq!(label(3), "春");
+        assert_eq!(label(6), "夏");
+        assert_eq!(label(9), "秋");
+        assert_eq!(label(12), "冬");
+    }
+}
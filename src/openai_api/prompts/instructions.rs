@@ -0,0 +1,107 @@
+use serde::Deserialize;
+
+use super::PROMPTS;
+
+/// 返信生成時にコード側で差し込む指示文
+///
+/// prompts の `reply_instructions` で個別に上書きできる。省略したキーは組み込みの文面を使い、
+/// 空文字にした指示はメッセージに追加しない。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReplyInstructions {
+    /// オウム返し禁止（毎回の返信に追加）
+    pub anti_parrot: String,
+    /// 検索を強制したときの出力ルール
+    pub search_mandate: String,
+    /// 検索を強制したときのパッチリリース向け補足
+    pub patch_release: String,
+    /// 検索が必要かをモデルに判定させるときの指示（`SEARCH_CLASSIFIER`）
+    pub search_classifier: String,
+    /// 空・途中終了だったときのリトライ指示
+    pub retry_format: String,
+    /// オウム返しだったときのリトライ指示
+    pub parrot_retry: String,
+    /// 投稿の言語が分かるときの指示（`{{LANGUAGE}}`）
+    pub reply_language: String,
+    /// CW の付け方（`REPLY_MODEL_CW=true` のとき毎回の返信に追加）
+    pub content_warning: String,
+    /// JSON っぽい出力しか得られなかったときに代わりに返す文
+    pub json_fallback_reply: String,
+    /// 返信の対象外のアカウントに 1 度だけ返すお知らせ（`ACCESS_DENIED_NOTICE=true`）
+    pub access_denied_notice: String,
+    /// 返信のループを打ち切るときに 1 度だけ返す文（`LOOP_CLOSING_NOTICE=true`）
+    pub loop_closing_message: String,
+}
+
+impl Default for ReplyInstructions {
+    fn default() -> Self {
+        Self {
+            anti_parrot: "ユーザーの発言をそのまま繰り返すだけの返答は禁止です。必ず質問や発言の内容に答え、そのうえで必要なら短くボケや\
+相槌を添えてください。質問文を引用するときは、その後に必ずあなたの考えを書くこと。".into(),
+            search_mandate: [
+                "When asked about versions/release notes/highlights:",
+                "• You MUST use web_search to fetch official sources.",
+                "• Output: 2 bullets max, plain text only.",
+                "• Each bullet ≤ 70 Japanese chars.",
+                "• Include the exact version and a YYYY-MM-DD (JST) date.",
+                "• Add one source domain in parentheses, e.g., (blog.rust-lang.org).",
+                "• Do NOT speculate about future releases.",
+                "• If a future date isn't confirmed by official sources, say “未確定”.",
+                "• NO URLs and NO markdown. Do not output '[' ']' '(' within URLs.",
+                "• Keep total length ≤ 180 Japanese chars.",
+                "• Perform at most one search call.",
+            ]
+            .join(" "),
+            patch_release: "For patch releases (e.g., 1.91.1), summarize only 1–2 key fixes.".into(),
+            search_classifier: "You decide whether replying to the following Mastodon post needs a web search \
+for up-to-date facts (software releases, news, prices, schedules, recent events). Small talk, opinions, feelings \
+and general knowledge do not. Answer with exactly one word: yes or no."
+                .into(),
+            retry_format:
+                "2 bullets max. ≤ 60 Japanese chars each. Plain text. No URLs. Unconfirmed future dates → “未確定”."
+                    .into(),
+            parrot_retry: "さっきの返答はユーザーの発言をそのまま繰り返してしまっていました。今度は必ず質問に答えてください。質問文を\
+そのまま返すのではなく、あなたの答えやリアクションを1〜3文で書いてください。".into(),
+            reply_language: "ユーザーの投稿の言語は {{LANGUAGE}} です。特に頼まれない限り、同じ言語で返信してください。"
+                .into(),
+            content_warning: "返信は reply に書いてください。死・病気・暴力・性的な話題・事件事故・作品のネタバレなど、\
+読む人によってはつらかったり見たくなかったりする話題に触れるときだけ、content_warning に「ネタバレ」「病気の話」の\
+ような短い注意書きを入れてください。それ以外は null にすること。".into(),
+            json_fallback_reply: "短く要点＋出典ドメインでまとめられなかったみたい。もう一度聞いて！".into(),
+            access_denied_notice:
+                "ごめんなさい、このbotは限られたメンバー向けに運用しているため、お返事できません。".into(),
+            loop_closing_message:
+                "このスレッドではたくさんお話ししたので、ここでいったん失礼します。また新しく話しかけてね！"
+                    .into(),
+        }
+    }
+}
+
+/// 返信の対象外のアカウントへのお知らせ
+pub fn access_denied_notice() -> &'static str {
+    &PROMPTS.reply_instructions.access_denied_notice
+}
+
+/// 返信のループを打ち切るときのお知らせ
+pub fn loop_closing_message() -> &'static str {
+    &PROMPTS.reply_instructions.loop_closing_message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_keys_fall_back_to_builtin_text() {
+        let parsed: ReplyInstructions =
+            serde_json::from_str(r#"{"anti_parrot": "", "json_fallback_reply": "Try again!"}"#)
+                .unwrap();
+        let defaults = ReplyInstructions::default();
+
+        assert_eq!(parsed.anti_parrot, "");
+        assert_eq!(parsed.json_fallback_reply, "Try again!");
+        assert_eq!(parsed.search_mandate, defaults.search_mandate);
+        assert_eq!(parsed.parrot_retry, defaults.parrot_retry);
+        assert!(defaults.search_mandate.starts_with("When asked about versions"));
+    }
+}
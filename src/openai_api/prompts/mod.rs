@@ -0,0 +1,366 @@
+use crate::openai_api::types::ChatMessage;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+mod calendar;
+mod check;
+mod commands;
+mod feed_digest;
+mod free_toot;
+mod instructions;
+mod search_rules;
+mod source;
+
+pub use calendar::{CalendarHints, SpecialDateDef};
+pub use check::check_prompts_file;
+pub use commands::{CommandConfig, command_config};
+pub use free_toot::{FreeTootSlotDef, SeasonDef, season_for_month, slot_for_hour};
+pub use instructions::{ReplyInstructions, access_denied_notice, loop_closing_message};
+pub use search_rules::{SearchRule, SearchRuleDef, search_rules};
+
+#[derive(Debug, Deserialize)]
+pub struct PromptConfig {
+    #[serde(default)]
+    pub free_toot_morning: Vec<ChatMessage>,
+    #[serde(default)]
+    pub free_toot_day: Vec<ChatMessage>,
+    #[serde(default)]
+    pub free_toot_night: Vec<ChatMessage>,
+    /// スロットから参照できる追加の自由トゥート用テンプレート
+    #[serde(default)]
+    pub free_toot_templates: HashMap<String, Vec<ChatMessage>>,
+    /// 自由トゥートの時間帯。省略時は従来の朝・昼・夕方・夜
+    #[serde(default = "free_toot::default_free_toot_slots")]
+    pub free_toot_slots: Vec<FreeTootSlotDef>,
+    /// 季節の定義。省略時は日本の春夏秋冬
+    #[serde(default = "free_toot::default_seasons")]
+    pub seasons: Vec<SeasonDef>,
+    /// 自由トゥートのテンプレートの最後の user メッセージをこの文で上書きする
+    #[serde(default = "free_toot::default_free_toot_user_prompt")]
+    pub free_toot_user_prompt: String,
+    /// 最近の投稿を「繰り返さないで」と伝える system 指示（`FREE_TOOT_AVOID_RECENT_IN_PROMPT` 件）
+    #[serde(default = "free_toot::default_free_toot_avoid_recent")]
+    pub free_toot_avoid_recent: String,
+    /// 似すぎて作り直すときに、使えなかった下書きを伝える system 指示（`{{DRAFTS}}` に箇条書きで入る）
+    #[serde(default = "free_toot::default_free_toot_rejected_drafts")]
+    pub free_toot_rejected_drafts: String,
+    /// 話題キューから話題を取り出したときに追加する system 指示（`{{TOPIC}}` に話題が入る）
+    #[serde(default = "free_toot::default_free_toot_topic")]
+    pub free_toot_topic: String,
+    /// フィードの新着記事を紹介する投稿のテンプレート
+    #[serde(default = "feed_digest::default_feed_digest")]
+    pub feed_digest: Vec<ChatMessage>,
+    /// 特別な日（毎年 `MM-DD` またはその年だけ `YYYY-MM-DD`）
+    #[serde(default)]
+    pub special_dates: Vec<SpecialDateDef>,
+    /// 曜日・祝日・特別な日を伝える system 指示（省略時は組み込みの文面）
+    #[serde(default)]
+    pub calendar_hints: CalendarHints,
+
+    pub reply_with_context: Vec<ChatMessage>,
+    pub reply_without_context: Vec<ChatMessage>,
+
+    /// 名前付きペルソナ。トップレベルの返信プロンプトは `default` として扱う
+    #[serde(default)]
+    pub personas: HashMap<String, PersonaPrompts>,
+    /// メンションごとのペルソナ選択ルール（先頭から評価し、最初に一致したもの）
+    #[serde(default)]
+    pub persona_rules: Vec<PersonaRule>,
+
+    /// 返信で Web 検索を強制するルール（省略時はリリースノート向けの組み込みルール）
+    #[serde(default = "search_rules::default_search_rules")]
+    pub search_rules: Vec<SearchRuleDef>,
+
+    /// コード側で追加する返信用の指示文（省略時は組み込みの文面）
+    #[serde(default)]
+    pub reply_instructions: ReplyInstructions,
+
+    /// メンションのコマンド（`/reset` など）。省略時は組み込みの定義
+    #[serde(default)]
+    pub commands: CommandConfig,
+}
+
+/// ペルソナごとの返信プロンプト
+#[derive(Debug, Deserialize)]
+pub struct PersonaPrompts {
+    pub reply_with_context: Vec<ChatMessage>,
+    pub reply_without_context: Vec<ChatMessage>,
+}
+
+/// ペルソナ選択ルール
+///
+/// 指定された条件（キーワード・アカウント・言語）をすべて満たすと一致する。
+/// 各リストの中はどれか 1 つに一致すればよい。条件が空のルールは常に一致する。
+#[derive(Debug, Deserialize)]
+pub struct PersonaRule {
+    pub persona: String,
+    /// メンション本文に含まれるキーワードやハッシュタグ（大文字小文字は無視）
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// メンション送信者の acct（`user` / `user@example.com`）
+    #[serde(default)]
+    pub accounts: Vec<String>,
+    /// メンションの言語コード（`en` は `en-US` にも一致）
+    #[serde(default)]
+    pub languages: Vec<String>,
+}
+
+/// ペルソナ未指定・ルール不一致のときに使うペルソナ名
+pub const DEFAULT_PERSONA: &str = "default";
+
+impl PromptConfig {
+    /// ペルソナ名から返信テンプレートを引く。未知の名前はデフォルトにフォールバック
+    pub fn reply_template(&self, persona: &str, with_context: bool) -> &[ChatMessage] {
+        let (with_ctx, without_ctx) = match self.personas.get(persona) {
+            Some(p) if persona != DEFAULT_PERSONA => {
+                (&p.reply_with_context, &p.reply_without_context)
+            }
+            _ => (&self.reply_with_context, &self.reply_without_context),
+        };
+
+        if with_context { with_ctx } else { without_ctx }
+    }
+
+    /// スロットの `prompt` キーから自由トゥート用テンプレートを引く
+    pub fn free_toot_template(&self, key: &str) -> Option<&[ChatMessage]> {
+        let template = match key {
+            "free_toot_morning" => &self.free_toot_morning,
+            "free_toot_day" => &self.free_toot_day,
+            "free_toot_night" => &self.free_toot_night,
+            other => self.free_toot_templates.get(other)?,
+        };
+
+        Some(template)
+    }
+
+    pub fn has_persona(&self, persona: &str) -> bool {
+        persona == DEFAULT_PERSONA || self.personas.contains_key(persona)
+    }
+
+    /// `default` を先頭に、名前付きペルソナを名前順に
+    pub fn persona_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> =
+            self.personas.keys().map(String::as_str).filter(|n| *n != DEFAULT_PERSONA).collect();
+        names.sort();
+        names.insert(0, DEFAULT_PERSONA);
+        names
+    }
+}
+
+#[derive(Debug)]
+enum PromptLoadError {
+    Read { raw_path: String, resolved: PathBuf, source: io::Error },
+    Parse { resolved: PathBuf, format: &'static str, source: Box<dyn Error + Send + Sync> },
+    Include { from: PathBuf, source: Box<PromptLoadError> },
+    IncludeCycle { resolved: PathBuf },
+    Fragment { resolved: PathBuf, message: String },
+}
+
+impl fmt::Display for PromptLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromptLoadError::Read { raw_path, resolved, source } => write!(
+                f,
+                "Failed to read prompts file.\n  tried: {}\n  (from PROMPTS_PATH = {:?})\n  error: {}",
+                resolved.display(),
+                raw_path,
+                source
+            ),
+            PromptLoadError::Parse { resolved, format, source } => {
+                write!(
+                    f,
+                    "Failed to parse prompts {} {}\n  error: {}",
+                    format,
+                    resolved.display(),
+                    source
+                )
+            }
+            PromptLoadError::Include { from, source } => {
+                write!(f, "{}\n  (included from {})", source, from.display())
+            }
+            PromptLoadError::IncludeCycle { resolved } => {
+                write!(f, "Prompts include cycle detected at {}", resolved.display())
+            }
+            PromptLoadError::Fragment { resolved, message } => {
+                write!(
+                    f,
+                    "Failed to expand prompt fragments in {}\n  error: {}",
+                    resolved.display(),
+                    message
+                )
+            }
+        }
+    }
+}
+
+impl Error for PromptLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PromptLoadError::Read { source, .. } => Some(source),
+            PromptLoadError::Parse { source, .. } => Some(source.as_ref()),
+            PromptLoadError::Include { source, .. } => Some(source.as_ref()),
+            PromptLoadError::IncludeCycle { .. } | PromptLoadError::Fragment { .. } => None,
+        }
+    }
+}
+
+/// 相対パスが指定された場合に、
+///
+/// 1. カレントディレクトリ基準
+/// 2. 実行ファイルのあるディレクトリ基準
+///
+/// の順に試す
+fn resolve_prompts_path(raw: &str) -> PathBuf {
+    let p = Path::new(raw);
+
+    // 絶対パスならそのまま
+    if p.is_absolute() {
+        return p.to_path_buf();
+    }
+
+    // 1. 現在のカレントディレクトリからの相対パス
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let from_cwd = cwd.join(p);
+    if from_cwd.exists() {
+        return from_cwd;
+    }
+
+    // 2. 実行ファイルと同じディレクトリからの相対パス
+    if let Ok(exe) = std::env::current_exe()
+        && let Some(exe_dir) = exe.parent()
+    {
+        let from_exe = exe_dir.join(p);
+
+        if from_exe.exists() {
+            return from_exe;
+        }
+    }
+
+    // どちらにも存在しない場合は、とりあえず cwd 基準を返す
+    // （後で read_to_string がエラーを出す）
+    cwd.join(p)
+}
+
+fn load_prompts_from_path(raw_path: &str) -> std::result::Result<PromptConfig, PromptLoadError> {
+    let resolved = resolve_prompts_path(raw_path);
+    let value = source::load_prompt_value(raw_path, &resolved)?;
+
+    serde_json::from_value::<PromptConfig>(value).map_err(|source| PromptLoadError::Parse {
+        format: source::PromptFormat::from_path(&resolved).label(),
+        resolved,
+        source: Box::new(source),
+    })
+}
+
+/// プロンプトファイルのパス。PROMPTS_PATH が未設定なら config/prompts.json（`BotConfig` と同じ）
+pub fn prompts_path_from_env() -> String {
+    std::env::var("PROMPTS_PATH")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+        .unwrap_or_else(|| "config/prompts.json".to_string())
+}
+
+/// 起動後に最初にアクセスされたタイミングで prompts.json を読み込む。
+/// パスは環境変数 PROMPTS_PATH で上書き可能。デフォルトは ./config/prompts.json
+/// 拡張子が `.yaml` / `.yml` / `.toml` ならその形式として読む
+pub static PROMPTS: Lazy<PromptConfig> = Lazy::new(|| {
+    let raw_path = prompts_path_from_env();
+    load_prompts_from_path(&raw_path).unwrap_or_else(|e| panic!("{e}"))
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn unique_temp_path(name: &str) -> PathBuf {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        std::env::temp_dir().join(format!("mast_gpt_bot_{name}_{nanos}.json"))
+    }
+
+    fn minimal_prompts_json() -> &'static str {
+        r#"{
+            "free_toot_morning": [{"role": "system", "content": "morning"}],
+            "free_toot_day": [{"role": "system", "content": "day"}],
+            "free_toot_night": [{"role": "system", "content": "night"}],
+            "reply_with_context": [{"role": "system", "content": "with context"}],
+            "reply_without_context": [{"role": "system", "content": "without context"}]
+        }"#
+    }
+
+    #[test]
+    fn load_prompts_from_path_reads_and_parses_prompt_config() {
+        let path = unique_temp_path("valid_prompts");
+        fs::write(&path, minimal_prompts_json()).unwrap();
+
+        let prompts = load_prompts_from_path(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(prompts.free_toot_morning[0].content, "morning");
+        assert_eq!(prompts.free_toot_day[0].content, "day");
+        assert_eq!(prompts.free_toot_night[0].content, "night");
+        assert_eq!(prompts.reply_with_context[0].content, "with context");
+        assert_eq!(prompts.reply_without_context[0].content, "without context");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reply_template_resolves_named_personas_and_falls_back_to_default() {
+        let json = r#"{
+            "free_toot_morning": [],
+            "free_toot_day": [],
+            "free_toot_night": [],
+            "reply_with_context": [{"role": "system", "content": "default ctx"}],
+            "reply_without_context": [{"role": "system", "content": "default"}],
+            "personas": {
+                "staff": {
+                    "reply_with_context": [{"role": "system", "content": "staff ctx"}],
+                    "reply_without_context": [{"role": "system", "content": "staff"}]
+                }
+            },
+            "persona_rules": [{"persona": "staff", "accounts": ["admin"]}]
+        }"#;
+        let prompts: PromptConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(prompts.reply_template("staff", true)[0].content, "staff ctx");
+        assert_eq!(prompts.reply_template("staff", false)[0].content, "staff");
+        assert_eq!(prompts.reply_template(DEFAULT_PERSONA, false)[0].content, "default");
+        assert_eq!(prompts.reply_template("unknown", true)[0].content, "default ctx");
+        assert!(prompts.has_persona("staff"));
+        assert!(!prompts.has_persona("unknown"));
+        assert_eq!(prompts.persona_rules[0].accounts, vec!["admin".to_string()]);
+    }
+
+    #[test]
+    fn load_prompts_from_path_reports_parse_errors_with_path_context() {
+        let path = unique_temp_path("invalid_prompts");
+        fs::write(&path, "{").unwrap();
+
+        let err = load_prompts_from_path(path.to_str().unwrap()).unwrap_err();
+        let message = format!("{err:#}");
+
+        assert!(message.contains("Failed to parse prompts JSON"));
+        assert!(message.contains(path.to_str().unwrap()));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn load_prompts_from_path_labels_parse_errors_with_file_format() {
+        let path = unique_temp_path("invalid_prompts").with_extension("yaml");
+        fs::write(&path, "free_toot_morning: [").unwrap();
+
+        let err = load_prompts_from_path(path.to_str().unwrap()).unwrap_err();
+
+        assert!(err.to_string().contains("Failed to parse prompts YAML"));
+
+        fs::remove_file(path).unwrap();
+    }
+}
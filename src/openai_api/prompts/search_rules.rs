@@ -0,0 +1,176 @@
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+use super::PROMPTS;
+
+/// 返信で Web 検索を強制するルール
+///
+/// prompts の `search_rules` で置き換えられる（先頭から評価し、最初に一致したもの）。
+/// `exclude` に一致した部分を本文から取り除いてから `keywords` と `patterns` を調べる。
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchRuleDef {
+    /// ログに出す名前
+    pub name: String,
+    /// 本文に含まれていれば一致（大文字小文字は無視）
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// 本文に一致すれば一致する正規表現（大文字小文字は無視）
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// 判定の前に本文から取り除く正規表現（「3.5時間」のような数字など）
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// 一致したときに追加する指示（`{{MATCH}}` に一致した語句が入る）。
+    /// 空なら `reply_instructions.search_mandate` と `patch_release`
+    #[serde(default)]
+    pub instructions: Vec<String>,
+    /// `web_search_preview` の `search_context_size`（省略時は `low`）
+    #[serde(default)]
+    pub search_context_size: Option<String>,
+}
+
+/// 正規表現をコンパイルしたルール
+#[derive(Debug)]
+pub struct SearchRule {
+    pub name: String,
+    keywords: Vec<String>,
+    patterns: Vec<Regex>,
+    exclude: Vec<Regex>,
+    pub instructions: Vec<String>,
+    pub search_context_size: Option<String>,
+}
+
+impl SearchRule {
+    pub fn compile(def: &SearchRuleDef) -> Result<Self, regex::Error> {
+        let compile_all = |patterns: &[String]| -> Result<Vec<Regex>, regex::Error> {
+            patterns.iter().map(|p| RegexBuilder::new(p).case_insensitive(true).build()).collect()
+        };
+
+        Ok(Self {
+            name: def.name.clone(),
+            keywords: def
+                .keywords
+                .iter()
+                .map(|k| k.trim().to_lowercase())
+                .filter(|k| !k.is_empty())
+                .collect(),
+            patterns: compile_all(&def.patterns)?,
+            exclude: compile_all(&def.exclude)?,
+            instructions: def.instructions.clone(),
+            search_context_size: def.search_context_size.clone(),
+        })
+    }
+
+    /// 一致した語句を返す
+    pub fn find(&self, text: &str) -> Option<String> {
+        let mut text = text.to_string();
+        for exclude in &self.exclude {
+            text = exclude.replace_all(&text, " ").into_owned();
+        }
+
+        let lower = text.to_lowercase();
+        if let Some(keyword) = self.keywords.iter().find(|k| lower.contains(k.as_str())) {
+            return Some(keyword.clone());
+        }
+
+        self.patterns.iter().find_map(|p| p.find(&text)).map(|m| m.as_str().to_string())
+    }
+}
+
+static SEARCH_RULES: Lazy<Vec<SearchRule>> = Lazy::new(|| {
+    PROMPTS
+        .search_rules
+        .iter()
+        .filter_map(|def| match SearchRule::compile(def) {
+            Ok(rule) => Some(rule),
+            Err(e) => {
+                eprintln!("[search] skipping search rule {:?}: {}", def.name, e);
+                None
+            }
+        })
+        .collect()
+});
+
+/// prompts の検索ルール（正規表現が壊れているルールは除く）
+pub fn search_rules() -> &'static [SearchRule] {
+    &SEARCH_RULES
+}
+
+pub(super) fn default_search_rules() -> Vec<SearchRuleDef> {
+    let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+
+    vec![SearchRuleDef {
+        name: "release_notes".into(),
+        keywords: strings(&[
+            "リリースノート",
+            "変更点",
+            "変更履歴",
+            "ハイライト",
+            "新機能",
+            "release note",
+            "changelog",
+            "patch note",
+        ]),
+        patterns: strings(&[
+            r"何が(新しい|変わった)",
+            r"what'?s\s*new",
+            r"\bhighlights?\b",
+            r"\bv?\d+\.\d+(\.\d+)?",
+        ]),
+        // 「3.5時間」「1.5倍」のような量は版数ではない
+        exclude: strings(&[
+            r"\d+(\.\d+)?\s*(時間|分|秒|日|週間|か月|ヶ月|年|歳|才|度|℃|%|％|倍|円|人|回|個|杯)",
+            r"\d+(\.\d+)?\s*(kg|km|cm|mm|g|m|h|min|hours?|minutes?|days?|years?|times)\b",
+        ]),
+        instructions: Vec::new(),
+        search_context_size: Some("low".into()),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_rule() -> SearchRule {
+        SearchRule::compile(&default_search_rules()[0]).unwrap()
+    }
+
+    #[test]
+    fn default_rule_matches_release_queries() {
+        let rule = default_rule();
+
+        assert_eq!(
+            rule.find("Rust 1.91.1 のリリースノート教えて").as_deref(),
+            Some("リリースノート")
+        );
+        assert!(rule.find("新機能は何が変わった？").is_some());
+        assert_eq!(rule.find("Show me the CHANGELOG").as_deref(), Some("changelog"));
+        assert!(rule.find("what's new in Rust").is_some());
+        assert_eq!(rule.find("1.91.1").as_deref(), Some("1.91.1"));
+        assert_eq!(rule.find("1.91").as_deref(), Some("1.91"));
+        assert_eq!(rule.find("Rust 1.80について").as_deref(), Some("1.80"));
+    }
+
+    #[test]
+    fn default_rule_ignores_quantities_and_small_talk() {
+        let rule = default_rule();
+
+        assert_eq!(rule.find("3.5時間寝た"), None);
+        assert_eq!(rule.find("3.5 時間寝た"), None);
+        assert_eq!(rule.find("体重が 1.5 kg 減った"), None);
+        assert_eq!(rule.find("おすすめのお菓子教えて"), None);
+        assert_eq!(rule.find("今日のお昼なに食べよう"), None);
+        // 取り除くのは量の部分だけ
+        assert_eq!(rule.find("1.5倍速くなった Rust 1.80").as_deref(), Some("1.80"));
+        assert_eq!(rule.find("Rust 1.80 mod の話").as_deref(), Some("1.80"));
+    }
+
+    #[test]
+    fn invalid_patterns_fail_to_compile() {
+        let def: SearchRuleDef =
+            serde_json::from_str(r#"{"name": "broken", "patterns": ["("]}"#).unwrap();
+
+        assert!(SearchRule::compile(&def).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+//! プロンプトファイルの読み込み（JSON / YAML / TOML、include、フラグメント展開）
+//!
+//! どの形式も一度 `serde_json::Value` に変換してから合成し、最後に `PromptConfig` にする。
+//!
+//! - `include`: 先に読み込むファイル（文字列または配列）。パスは include 元のファイル基準
+//! - `fragments`: 名前付きの文字列。`{{> name}}` で任意の content から参照できる
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde_json::{Map, Value};
+
+use super::PromptLoadError;
+
+static FRAGMENT_REF_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{>\s*([A-Za-z0-9_.-]+)\s*\}\}").unwrap());
+
+const INCLUDE_KEY: &str = "include";
+const FRAGMENTS_KEY: &str = "fragments";
+/// フラグメントからフラグメントを参照できる深さの上限（循環参照よけ）
+const MAX_FRAGMENT_DEPTH: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum PromptFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl PromptFormat {
+    /// 拡張子で判定。不明なものは従来どおり JSON として扱う
+    pub(super) fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("yaml" | "yml") => Self::Yaml,
+            Some("toml") => Self::Toml,
+            _ => Self::Json,
+        }
+    }
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+        }
+    }
+
+    fn parse(self, data: &str) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(match self {
+            Self::Json => serde_json::from_str(data)?,
+            Self::Yaml => serde_yaml::from_str(data)?,
+            Self::Toml => toml::from_str(data)?,
+        })
+    }
+}
+
+/// include を解決して 1 つにまとめ、フラグメントを展開した値を返す
+pub(super) fn load_prompt_value(raw_path: &str, resolved: &Path) -> Result<Value, PromptLoadError> {
+    let mut stack = Vec::new();
+    let mut merged = load_with_includes(raw_path, resolved, &mut stack)?;
+
+    let fragments = match merged.as_object_mut().and_then(|m| m.remove(FRAGMENTS_KEY)) {
+        Some(Value::Object(map)) => map,
+        Some(_) => {
+            return Err(PromptLoadError::Fragment {
+                resolved: resolved.to_path_buf(),
+                message: "`fragments` must be a map of name to text".to_string(),
+            });
+        }
+        None => Map::new(),
+    };
+
+    expand_fragments(&mut merged, &fragments, resolved)?;
+    Ok(merged)
+}
+
+fn load_with_includes(
+    raw_path: &str,
+    resolved: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Value, PromptLoadError> {
+    let canonical = resolved.canonicalize().unwrap_or_else(|_| resolved.to_path_buf());
+    if stack.contains(&canonical) {
+        return Err(PromptLoadError::IncludeCycle { resolved: resolved.to_path_buf() });
+    }
+
+    let data = fs::read_to_string(resolved).map_err(|source| PromptLoadError::Read {
+        raw_path: raw_path.to_string(),
+        resolved: resolved.to_path_buf(),
+        source,
+    })?;
+    let format = PromptFormat::from_path(resolved);
+    let mut value = format.parse(&data).map_err(|source| PromptLoadError::Parse {
+        resolved: resolved.to_path_buf(),
+        format: format.label(),
+        source,
+    })?;
+
+    let includes = match value.as_object_mut().and_then(|m| m.remove(INCLUDE_KEY)) {
+        None => Vec::new(),
+        Some(Value::String(s)) => vec![s],
+        Some(Value::Array(items)) => items
+            .into_iter()
+            .map(|v| match v {
+                Value::String(s) => Ok(s),
+                other => Err(include_type_error(resolved, &other)),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(other) => return Err(include_type_error(resolved, &other)),
+    };
+
+    stack.push(canonical);
+    let base_dir = resolved.parent().unwrap_or_else(|| Path::new("."));
+    let mut merged = Value::Object(Map::new());
+    for include in includes {
+        let include_path = base_dir.join(&include);
+        let included = load_with_includes(&include, &include_path, stack).map_err(|source| {
+            PromptLoadError::Include { from: resolved.to_path_buf(), source: Box::new(source) }
+        })?;
+        merge_values(&mut merged, included);
+    }
+    stack.pop();
+
+    merge_values(&mut merged, value);
+    Ok(merged)
+}
+
+fn include_type_error(resolved: &Path, value: &Value) -> PromptLoadError {
+    PromptLoadError::Parse {
+        resolved: resolved.to_path_buf(),
+        format: PromptFormat::from_path(resolved).label(),
+        source: format!("`include` must be a path or a list of paths, got {value}").into(),
+    }
+}
+
+/// オブジェクト同士はキーごとに再帰的にマージし、それ以外（配列含む）は後勝ちで置き換える
+fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base_map), Value::Object(overlay_map)) => {
+            for (key, value) in overlay_map {
+                match base_map.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base_map.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn expand_fragments(
+    value: &mut Value,
+    fragments: &Map<String, Value>,
+    resolved: &Path,
+) -> Result<(), PromptLoadError> {
+    match value {
+        Value::String(s) if s.contains("{{>") => {
+            *s = expand_fragment_refs(s, fragments, resolved, 0)?;
+        }
+        Value::Array(items) => {
+            for item in items {
+                expand_fragments(item, fragments, resolved)?;
+            }
+        }
+        Value::Object(map) => {
+            for item in map.values_mut() {
+                expand_fragments(item, fragments, resolved)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn expand_fragment_refs(
+    text: &str,
+    fragments: &Map<String, Value>,
+    resolved: &Path,
+    depth: usize,
+) -> Result<String, PromptLoadError> {
+    if depth > MAX_FRAGMENT_DEPTH {
+        return Err(PromptLoadError::Fragment {
+            resolved: resolved.to_path_buf(),
+            message: format!("fragments nested deeper than {MAX_FRAGMENT_DEPTH} levels (cycle?)"),
+        });
+    }
+
+    let mut error = None;
+    let expanded = FRAGMENT_REF_RE.replace_all(text, |caps: &Captures| {
+        let name = &caps[1];
+        let result = match fragments.get(name) {
+            Some(Value::String(body)) => expand_fragment_refs(body, fragments, resolved, depth + 1),
+            Some(_) => Err(PromptLoadError::Fragment {
+                resolved: resolved.to_path_buf(),
+                message: format!("fragment {name:?} must be a string"),
+            }),
+            None => Err(PromptLoadError::Fragment {
+                resolved: resolved.to_path_buf(),
+                message: format!("unknown fragment {{{{> {name}}}}}"),
+            }),
+        };
+        result.unwrap_or_else(|e| {
+            error.get_or_insert(e);
+            String::new()
+        })
+    });
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(expanded.into_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let dir = std::env::temp_dir().join(format!("mast_gpt_bot_{name}_{nanos}"));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn detects_format_by_extension() {
+        assert_eq!(PromptFormat::from_path(Path::new("a/prompts.json")), PromptFormat::Json);
+        assert_eq!(PromptFormat::from_path(Path::new("prompts.YAML")), PromptFormat::Yaml);
+        assert_eq!(PromptFormat::from_path(Path::new("prompts.yml")), PromptFormat::Yaml);
+        assert_eq!(PromptFormat::from_path(Path::new("prompts.toml")), PromptFormat::Toml);
+        assert_eq!(PromptFormat::from_path(Path::new("prompts")), PromptFormat::Json);
+    }
+
+    #[test]
+    fn merges_includes_before_own_keys_and_expands_fragments() {
+        let dir = temp_dir("prompt_includes");
+        fs::create_dir_all(dir.join("parts")).unwrap();
+        fs::write(
+            dir.join("parts/common.toml"),
+            r#"
+                [fragments]
+                persona = "I am {{> name}}."
+                name = "mekamopo"
+
+                [[reply_without_context]]
+                role = "system"
+                content = "common"
+            "#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("prompts.yaml"),
+            "include: parts/common.toml\n\
+             reply_with_context:\n  - role: system\n    content: |\n      {{> persona}}\n      Be brief.\n",
+        )
+        .unwrap();
+        let root = dir.join("prompts.yaml");
+
+        let value = load_prompt_value(root.to_str().unwrap(), &root).unwrap();
+
+        assert_eq!(value["reply_with_context"][0]["content"], "I am mekamopo.\nBe brief.\n");
+        assert_eq!(
+            value["reply_without_context"],
+            json!([{"role": "system", "content": "common"}])
+        );
+        assert!(value.get("fragments").is_none());
+        assert!(value.get("include").is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reports_unknown_fragments_and_include_cycles() {
+        let dir = temp_dir("prompt_errors");
+        fs::write(dir.join("a.json"), r#"{"include": "b.json", "x": "{{> missing}}"}"#).unwrap();
+        fs::write(dir.join("b.json"), r#"{"include": ["a.json"]}"#).unwrap();
+        fs::write(dir.join("c.json"), r#"{"x": "{{> missing}}"}"#).unwrap();
+
+        let a = dir.join("a.json");
+        let err = load_prompt_value("a.json", &a).unwrap_err().to_string();
+        assert!(err.contains("include cycle"), "{err}");
+
+        let c = dir.join("c.json");
+        let err = load_prompt_value("c.json", &c).unwrap_err().to_string();
+        assert!(err.contains("unknown fragment {{> missing}}"), "{err}");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn later_values_override_objects_key_by_key_and_replace_arrays() {
+        let mut base = json!({"a": {"x": 1, "y": [1, 2]}, "b": 1});
+
+        merge_values(&mut base, json!({"a": {"y": [3]}, "c": 2}));
+
+        assert_eq!(base, json!({"a": {"x": 1, "y": [3]}, "b": 1, "c": 2}));
+    }
+}
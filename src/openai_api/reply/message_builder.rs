@@ -1,87 +1,87 @@
+use chrono_tz::Tz;
+
 use crate::openai_api::prompts::PROMPTS;
+use crate::openai_api::time::{current_time_instruction, now_in};
 use crate::openai_api::types::ChatMessage;
 
-use super::time::now_tokyo_rfc3339;
+/// 返信プロンプトを組み立てるための入力
+#[derive(Clone, Copy)]
+pub(super) struct ReplyPrompt<'a> {
+    pub user_text: &'a str,
+    pub conversation_context: Option<&'a str>,
+    pub persona: &'a str,
+    pub timezone: Tz,
+    /// 返信先の投稿の言語（Mastodon の `language`）
+    pub language: Option<&'a str>,
+    /// 構造化出力で CW を付けさせる（`REPLY_MODEL_CW=true`）
+    pub content_warning: bool,
+    /// メンションに添付された画像の URL（画像に対応したモデルにだけ送る）
+    pub images: &'a [String],
+}
 
 pub(super) fn build_initial_messages(
-    user_text: &str,
-    conversation_context: Option<&str>,
-    force_search: bool,
+    prompt: ReplyPrompt<'_>,
+    search_instructions: &[String],
 ) -> Vec<ChatMessage> {
-    let (mut msgs, placeholders) = messages_from_reply_template(user_text, conversation_context);
-
-    msgs.push(ChatMessage {
-        role: "system".into(),
-        content: format!("CurrentTime(JST): {}", now_tokyo_rfc3339()),
-    });
+    let (mut msgs, placeholders) = messages_from_reply_template(prompt);
 
     msgs.push(ChatMessage {
         role: "system".into(),
-        content: "ユーザーの発言をそのまま繰り返すだけの返答は禁止です。必ず質問や発言の内容に答え、そのうえで必要なら短くボケや\
-相槌を添えてください。質問文を引用するときは、その後に必ずあなたの考えを書くこと。".into(),
+        content: current_time_instruction(now_in(prompt.timezone)),
     });
 
-    if force_search {
-        msgs.push(ChatMessage { role: "system".into(), content: search_mandate_instruction() });
+    let instructions = &PROMPTS.reply_instructions;
+    push_instruction(&mut msgs, &instructions.anti_parrot);
+    if let Some(language) = prompt.language {
+        push_instruction(&mut msgs, &instructions.reply_language.replace("{{LANGUAGE}}", language));
+    }
+    if prompt.content_warning {
+        push_instruction(&mut msgs, &instructions.content_warning);
+    }
 
-        msgs.push(ChatMessage {
-            role: "system".into(),
-            content: "For patch releases (e.g., 1.91.1), summarize only 1–2 key fixes.".into(),
-        });
+    for instruction in search_instructions {
+        push_instruction(&mut msgs, instruction);
     }
 
-    append_missing_context_and_user(&mut msgs, user_text, conversation_context, placeholders);
+    append_missing_context_and_user(
+        &mut msgs,
+        prompt.user_text,
+        prompt.conversation_context,
+        placeholders,
+    );
 
     msgs
 }
 
-pub(super) fn build_retry_messages(
-    user_text: &str,
-    conversation_context: Option<&str>,
-) -> Vec<ChatMessage> {
-    let (mut retry_msgs, placeholders) =
-        messages_from_reply_template(user_text, conversation_context);
+pub(super) fn build_retry_messages(prompt: ReplyPrompt<'_>) -> Vec<ChatMessage> {
+    let (mut retry_msgs, placeholders) = messages_from_reply_template(prompt);
 
+    push_instruction(&mut retry_msgs, &PROMPTS.reply_instructions.retry_format);
     retry_msgs.push(ChatMessage {
         role: "system".into(),
-        content:
-            "2 bullets max. ≤ 60 Japanese chars each. Plain text. No URLs. Unconfirmed future dates → “未確定”."
-                .parse()
-                .unwrap(),
-    });
-    retry_msgs.push(ChatMessage {
-        role: "system".into(),
-        content: format!("CurrentTime(JST): {}", now_tokyo_rfc3339()),
+        content: current_time_instruction(now_in(prompt.timezone)),
     });
 
-    append_missing_context_and_user(&mut retry_msgs, user_text, conversation_context, placeholders);
+    append_missing_context_and_user(
+        &mut retry_msgs,
+        prompt.user_text,
+        prompt.conversation_context,
+        placeholders,
+    );
 
     retry_msgs
 }
 
-pub(super) fn build_parrot_retry_messages(
-    user_text: &str,
-    conversation_context: Option<&str>,
-) -> Vec<ChatMessage> {
-    let base_retry = base_prompt_for_reply(conversation_context);
-    let (mut retry_msgs, _placeholders) =
-        apply_placeholders(base_retry, user_text, conversation_context);
+pub(super) fn build_parrot_retry_messages(prompt: ReplyPrompt<'_>) -> Vec<ChatMessage> {
+    let (mut retry_msgs, _placeholders) = messages_from_reply_template(prompt);
 
-    retry_msgs.push(ChatMessage {
-        role: "system".into(),
-        content: "さっきの返答はユーザーの発言をそのまま繰り返してしまっていました。今度は必ず質問に答えてください。質問文を\
-そのまま返すのではなく、あなたの答えやリアクションを1〜3文で書いてください。".into(),
-    });
+    push_instruction(&mut retry_msgs, &PROMPTS.reply_instructions.parrot_retry);
 
     retry_msgs
 }
 
-fn base_prompt_for_reply(conversation_context: Option<&str>) -> Vec<ChatMessage> {
-    if conversation_context.is_some() {
-        PROMPTS.reply_with_context.clone()
-    } else {
-        PROMPTS.reply_without_context.clone()
-    }
+fn base_prompt_for_reply(persona: &str, conversation_context: Option<&str>) -> Vec<ChatMessage> {
+    PROMPTS.reply_template(persona, conversation_context.is_some()).to_vec()
 }
 
 #[derive(Clone, Copy)]
@@ -90,12 +90,9 @@ struct PlaceholderState {
     had_context: bool,
 }
 
-fn messages_from_reply_template(
-    user_text: &str,
-    conversation_context: Option<&str>,
-) -> (Vec<ChatMessage>, PlaceholderState) {
-    let base = base_prompt_for_reply(conversation_context);
-    apply_placeholders(base, user_text, conversation_context)
+fn messages_from_reply_template(prompt: ReplyPrompt<'_>) -> (Vec<ChatMessage>, PlaceholderState) {
+    let base = base_prompt_for_reply(prompt.persona, prompt.conversation_context);
+    apply_placeholders(base, prompt.user_text, prompt.conversation_context, prompt.language)
 }
 
 fn append_missing_context_and_user(
@@ -116,29 +113,21 @@ fn append_missing_context_and_user(
     }
 }
 
-fn search_mandate_instruction() -> String {
-    [
-        "When asked about versions/release notes/highlights:",
-        "• You MUST use web_search to fetch official sources.",
-        "• Output: 2 bullets max, plain text only.",
-        "• Each bullet ≤ 70 Japanese chars.",
-        "• Include the exact version and a YYYY-MM-DD (JST) date.",
-        "• Add one source domain in parentheses, e.g., (blog.rust-lang.org).",
-        "• Do NOT speculate about future releases.",
-        "• If a future date isn't confirmed by official sources, say “未確定”.",
-        "• NO URLs and NO markdown. Do not output '[' ']' '(' within URLs.",
-        "• Keep total length ≤ 180 Japanese chars.",
-        "• Perform at most one search call.",
-    ]
-    .join(" ")
+/// 空の指示は追加しない（prompts 側で無効化できるように）
+fn push_instruction(messages: &mut Vec<ChatMessage>, instruction: &str) {
+    if !instruction.trim().is_empty() {
+        messages.push(ChatMessage { role: "system".into(), content: instruction.to_string() });
+    }
 }
 
 fn apply_placeholders(
     template: Vec<ChatMessage>,
     user_text: &str,
     conversation_context: Option<&str>,
+    language: Option<&str>,
 ) -> (Vec<ChatMessage>, PlaceholderState) {
     let context_str = conversation_context.unwrap_or("");
+    let language_str = language.unwrap_or("");
     let mut had_user_placeholder = false;
     let mut had_context_placeholder = false;
 
@@ -151,8 +140,11 @@ fn apply_placeholders(
             if msg.content.contains("{{CONTEXT}}") {
                 had_context_placeholder = true;
             }
-            msg.content =
-                msg.content.replace("{{USER_TEXT}}", user_text).replace("{{CONTEXT}}", context_str);
+            msg.content = msg
+                .content
+                .replace("{{USER_TEXT}}", user_text)
+                .replace("{{CONTEXT}}", context_str)
+                .replace("{{LANGUAGE}}", language_str);
             msg
         })
         .collect();
@@ -173,14 +165,17 @@ mod tests {
 
     #[test]
     fn apply_placeholders_replaces_user_and_context_and_reports_flags() {
-        let template =
-            vec![message("system", "ctx={{CONTEXT}}"), message("user", "text={{USER_TEXT}}")];
+        let template = vec![
+            message("system", "ctx={{CONTEXT}} lang={{LANGUAGE}}"),
+            message("user", "text={{USER_TEXT}}"),
+        ];
 
-        let (messages, placeholders) = apply_placeholders(template, "hello", Some("thread"));
+        let (messages, placeholders) =
+            apply_placeholders(template, "hello", Some("thread"), Some("ja"));
 
         assert!(placeholders.had_user);
         assert!(placeholders.had_context);
-        assert_eq!(messages[0].content, "ctx=thread");
+        assert_eq!(messages[0].content, "ctx=thread lang=ja");
         assert_eq!(messages[1].content, "text=hello");
     }
 
@@ -188,7 +183,7 @@ mod tests {
     fn apply_placeholders_replaces_missing_context_with_empty_text() {
         let template = vec![message("system", "ctx={{CONTEXT}}")];
 
-        let (messages, placeholders) = apply_placeholders(template, "hello", None);
+        let (messages, placeholders) = apply_placeholders(template, "hello", None, None);
 
         assert!(!placeholders.had_user);
         assert!(placeholders.had_context);
@@ -221,4 +216,16 @@ mod tests {
 
         assert_eq!(messages.len(), 3);
     }
+
+    #[test]
+    fn push_instruction_skips_blank_instructions() {
+        let mut messages = Vec::new();
+
+        push_instruction(&mut messages, "  ");
+        push_instruction(&mut messages, "be kind");
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].role, "system");
+        assert_eq!(messages[0].content, "be kind");
+    }
 }
@@ -2,72 +2,133 @@ use anyhow::Result;
 use reqwest::Client;
 
 use crate::config::BotConfig;
+use crate::conversation_store::{ConversationStore, PreviousResponse};
+use crate::mastodon::{Status, reply_body_with_links};
 use crate::openai_api::call_config::{
     OpenAiCallConfig, build_web_search_tools as build_openai_web_search_tools,
 };
-use crate::openai_api::stream::call_responses;
+use crate::openai_api::fallback::failed_model;
+use crate::openai_api::prompts::PROMPTS;
 use crate::openai_api::types::{ResponsesResult, Tool};
+use crate::util::strip_html;
 
 use self::message_builder::{
-    build_initial_messages, build_parrot_retry_messages, build_retry_messages,
+    ReplyPrompt, build_initial_messages, build_parrot_retry_messages, build_retry_messages,
 };
 use self::parrot_check::is_parrot_reply;
-use self::search::should_force_search;
+use self::search::{SearchDecision, decide_search};
+use self::structured::{
+    append_sources, is_blank_reply, is_unsupported_text_format, mark_unsupported,
+    parse_structured_reply, reply_body, supports_structured_output,
+};
+
+pub(super) use self::structured::reply_text_format;
 
 mod message_builder;
 mod parrot_check;
 mod search;
-mod time;
+mod structured;
+
+/// 返信の出力トークンの上限（モデルの表で `max_output_tokens` を指定すると置き換わる）
+const REPLY_MAX_OUTPUT_TOKENS: u32 = 140;
+const EMPTY_RETRY_MAX_OUTPUT_TOKENS: u32 = 120;
+const PARROT_RETRY_MAX_OUTPUT_TOKENS: u32 = 1024;
 
 pub struct ReplyResult {
     pub text: String,
     pub response_id: String,
+    /// モデルが付けた CW（`REPLY_MODEL_CW=true` のときだけ）
+    pub content_warning: Option<String>,
+    /// モデルが返信不要と判断した（構造化出力のときだけ）
+    pub no_reply: bool,
+    /// 末尾に付ける出典の URL（`REPLY_SOURCE_LINKS` 件まで）
+    pub source_links: Vec<String>,
+    /// 出典の URL を 1 つも付けられなかったときに本文へ足すドメイン表記
+    pub source_domains: Vec<String>,
+    /// 返信を書いたモデル（フォールバックしたならそのモデル）
+    pub model: String,
 }
 
-const JSON_FALLBACK_REPLY: &str =
-    "短く要点＋出典ドメインでまとめられなかったみたい。もう一度聞いて！";
+impl ReplyResult {
+    /// 投稿する本文。出典の URL は `limit` に収まるだけ付け、1 つも収まらなければドメイン表記にする
+    pub fn body_with_sources(&self, limit: usize, reply_to_acct: &str) -> String {
+        let body = reply_body_with_links(limit, reply_to_acct, &self.text, &self.source_links);
+        if body != self.text || self.source_domains.is_empty() {
+            return body;
+        }
+        append_sources(&self.text, &self.source_domains)
+    }
+}
 
-fn build_web_search_tools(enable_web_search: bool, force_search: bool) -> Vec<Tool> {
-    build_openai_web_search_tools(enable_web_search || force_search, Some("low"))
+fn build_web_search_tools(enable_web_search: bool, search: Option<&SearchDecision>) -> Vec<Tool> {
+    let search_context_size = search.map_or("low", |s| s.search_context_size.as_str());
+    build_openai_web_search_tools(enable_web_search || search.is_some(), Some(search_context_size))
 }
 
 fn should_retry_empty_or_incomplete(res: &ResponsesResult) -> bool {
-    res.text.trim().is_empty() || res.status.as_deref() == Some("incomplete")
+    is_blank_reply(&res.text) || res.status.as_deref() == Some("incomplete")
 }
 
 fn should_retry_parrot(force_search: bool, user_text: &str, reply_text: &str) -> bool {
     !force_search && is_parrot_reply(user_text, reply_text)
 }
 
-fn final_reply_text(text: &str) -> String {
+fn final_reply_text(text: &str, json_fallback: &str) -> String {
     let clean = text.trim();
     if clean.starts_with('{') || clean.starts_with('[') {
-        JSON_FALLBACK_REPLY.to_string()
+        json_fallback.to_string()
     } else {
         clean.to_string()
     }
 }
 
+/// このモデルに会話を引き継げるなら `previous_response_id` を使い、
+/// 引き継げなければ Mastodon 側の会話ログを渡す
+fn continue_conversation<'p>(
+    prompt: ReplyPrompt<'p>,
+    previous: Option<&PreviousResponse>,
+    model: &str,
+) -> (ReplyPrompt<'p>, Option<String>) {
+    match previous.filter(|p| p.continues_with(model)) {
+        // 2回目以降：OpenAI 側の会話状態に任せる
+        Some(previous) => {
+            (ReplyPrompt { conversation_context: None, ..prompt }, Some(previous.id.clone()))
+        }
+        // 初回（または別のモデルに切り替えた）：会話ログをブートストラップとして渡す
+        None => (prompt, None),
+    }
+}
+
 async fn call_initial_reply(
     client: &Client,
     call_config: &OpenAiCallConfig<'_>,
-    user_text: &str,
-    conversation_context: Option<&str>,
-    force_search: bool,
-    previous_response_id: Option<String>,
+    prompt: ReplyPrompt<'_>,
+    search: Option<&SearchDecision>,
+    previous: Option<&PreviousResponse>,
     web_search_tools: &[Tool],
 ) -> Result<ResponsesResult> {
-    let messages = build_initial_messages(user_text, conversation_context, force_search);
-    let builder = call_config.build(messages, 140, previous_response_id, web_search_tools.to_vec());
-
-    call_responses(client, builder, true).await
+    let search_instructions = search.map_or(&[][..], |s| s.instructions.as_slice());
+
+    call_config
+        .call_with_fallback(client, &call_config.models(), |model| {
+            let (prompt, previous_response_id) = continue_conversation(prompt, previous, model);
+            let messages = build_initial_messages(prompt, search_instructions);
+            call_config
+                .build(
+                    messages,
+                    REPLY_MAX_OUTPUT_TOKENS,
+                    previous_response_id,
+                    web_search_tools.to_vec(),
+                )
+                .images(prompt.images.to_vec())
+        })
+        .await
 }
 
 async fn retry_empty_or_incomplete_reply(
     client: &Client,
     call_config: &OpenAiCallConfig<'_>,
-    user_text: &str,
-    conversation_context: Option<&str>,
+    prompt: ReplyPrompt<'_>,
     current: ResponsesResult,
     web_search_tools: Vec<Tool>,
 ) -> Result<ResponsesResult> {
@@ -75,9 +136,15 @@ async fn retry_empty_or_incomplete_reply(
         return Ok(current);
     }
 
-    let retry_msgs = build_retry_messages(user_text, conversation_context);
-    let retry_builder = call_config.build(retry_msgs, 120, None, web_search_tools);
-    let retry_res = call_responses(client, retry_builder, true).await?;
+    let retry_msgs = build_retry_messages(prompt);
+    let retry_builder = call_config
+        .build(retry_msgs, EMPTY_RETRY_MAX_OUTPUT_TOKENS, None, web_search_tools)
+        .images(prompt.images.to_vec());
+    let retry_res = call_config
+        .call_with_fallback(client, &call_config.retry_models(&current.model), |_| {
+            retry_builder.clone()
+        })
+        .await?;
 
     Ok(prefer_non_empty_retry(current, retry_res))
 }
@@ -85,77 +152,173 @@ async fn retry_empty_or_incomplete_reply(
 async fn retry_parrot_reply(
     client: &Client,
     call_config: &OpenAiCallConfig<'_>,
-    user_text: &str,
-    conversation_context: Option<&str>,
+    prompt: ReplyPrompt<'_>,
     force_search: bool,
     current: ResponsesResult,
 ) -> Result<ResponsesResult> {
-    if !should_retry_parrot(force_search, user_text, current.text.trim()) {
+    if !should_retry_parrot(force_search, prompt.user_text, reply_body(&current.text).trim()) {
         return Ok(current);
     }
 
-    let retry_msgs = build_parrot_retry_messages(user_text, conversation_context);
-    let retry_builder = call_config.build(retry_msgs, 1024, None, Vec::new());
-    let retry_res = call_responses(client, retry_builder, true).await?;
+    let retry_msgs = build_parrot_retry_messages(prompt);
+    let retry_builder =
+        call_config.build(retry_msgs, PARROT_RETRY_MAX_OUTPUT_TOKENS, None, Vec::new());
+    let retry_res = call_config
+        .call_with_fallback(client, &call_config.retry_models(&current.model), |_| {
+            retry_builder.clone()
+        })
+        .await?;
 
     Ok(prefer_non_empty_retry(current, retry_res))
 }
 
 fn prefer_non_empty_retry(current: ResponsesResult, retry: ResponsesResult) -> ResponsesResult {
-    if retry.text.trim().is_empty() { current } else { retry }
+    if is_blank_reply(&retry.text) { current } else { retry }
 }
 
 pub async fn generate_reply(
     client: &Client,
     cfg: &BotConfig,
-    user_text: &str,
+    conv_store: &ConversationStore,
     conversation_context: Option<&str>,
-    previous_response_id: Option<String>,
+    previous: Option<&PreviousResponse>,
+    persona: &str,
+    mention: &Status,
 ) -> Result<ReplyResult> {
-    let force_search = should_force_search(user_text);
-
-    let call_config = OpenAiCallConfig::for_reply(cfg);
+    let user_text = strip_html(&mention.content);
+    let search = decide_search(client, cfg, &user_text).await;
+    let images = mention.image_urls();
+    let prompt = ReplyPrompt {
+        user_text: &user_text,
+        conversation_context,
+        persona,
+        timezone: cfg.timezone,
+        language: mention.language.as_deref(),
+        content_warning: cfg.reply_model_cw,
+        images: &images,
+    };
+
+    let mut call_config = OpenAiCallConfig::for_reply(cfg, conv_store);
+    if !supports_structured_output(call_config.reply_model()) {
+        call_config = call_config.plain_text();
+    }
 
-    let web_search_tools = build_web_search_tools(cfg.enable_web_search, force_search);
+    let web_search_tools = build_web_search_tools(cfg.enable_web_search, search.as_ref());
 
-    let res = call_initial_reply(
+    let res = match run_reply_calls(
         client,
         &call_config,
-        user_text,
-        conversation_context,
-        force_search,
-        previous_response_id,
+        prompt,
+        search.as_ref(),
+        previous,
         &web_search_tools,
     )
-    .await?;
+    .await
+    {
+        Err(e) if call_config.is_structured() && is_unsupported_text_format(&e) => {
+            // 構造化出力に対応していないモデルなら、プレーンテキストでやり直す
+            // （フォールバックやリトライで別のモデルが失敗したこともあるので、失敗したモデルを覚える）
+            let model = failed_model(&e).unwrap_or(call_config.reply_model());
+            eprintln!(
+                "[reply] {} does not support structured output, falling back to plain text: {:?}",
+                model, e
+            );
+            mark_unsupported(model);
+            call_config = call_config.plain_text();
+            run_reply_calls(
+                client,
+                &call_config,
+                prompt,
+                search.as_ref(),
+                previous,
+                &web_search_tools,
+            )
+            .await?
+        }
+        res => res?,
+    };
+
+    let json_fallback = &PROMPTS.reply_instructions.json_fallback_reply;
+    Ok(reply_result(cfg, call_config.is_structured(), json_fallback, res))
+}
+
+/// 最初の呼び出しと、空・オウム返しだったときのリトライ
+async fn run_reply_calls(
+    client: &Client,
+    call_config: &OpenAiCallConfig<'_>,
+    prompt: ReplyPrompt<'_>,
+    search: Option<&SearchDecision>,
+    previous: Option<&PreviousResponse>,
+    web_search_tools: &[Tool],
+) -> Result<ResponsesResult> {
+    // プレーンテキストのときは content_warning 欄の説明をしない
+    let prompt = ReplyPrompt {
+        content_warning: prompt.content_warning && call_config.is_structured(),
+        ..prompt
+    };
+
+    let res =
+        call_initial_reply(client, call_config, prompt, search, previous, web_search_tools).await?;
+    // リトライにも、最初の返信と同じだけの会話ログを渡す
+    let (prompt, _) = continue_conversation(prompt, previous, &res.model);
     let res = retry_empty_or_incomplete_reply(
         client,
-        &call_config,
-        user_text,
-        conversation_context,
-        res,
-        web_search_tools,
-    )
-    .await?;
-    let res = retry_parrot_reply(
-        client,
-        &call_config,
-        user_text,
-        conversation_context,
-        force_search,
+        call_config,
+        prompt,
         res,
+        web_search_tools.to_vec(),
     )
     .await?;
 
-    let final_text = final_reply_text(&res.text);
+    retry_parrot_reply(client, call_config, prompt, search.is_some(), res).await
+}
 
-    Ok(ReplyResult { text: final_text, response_id: res.id })
+fn reply_result(
+    cfg: &BotConfig,
+    structured: bool,
+    json_fallback: &str,
+    res: ResponsesResult,
+) -> ReplyResult {
+    let source_links: Vec<String> =
+        res.citations.iter().take(cfg.reply_source_links).map(|c| c.url.clone()).collect();
+
+    let parsed = if structured { parse_structured_reply(&res.text) } else { None };
+    let Some(parsed) = parsed else {
+        // プレーンテキスト（または読めなかった構造化出力）
+        let text = final_reply_text(&res.text, json_fallback);
+        return ReplyResult {
+            text,
+            response_id: res.id,
+            content_warning: None,
+            no_reply: false,
+            source_links,
+            source_domains: Vec::new(),
+            model: res.model,
+        };
+    };
+
+    // 出典の URL を付けるなら、ドメイン表記は URL が収まらなかったときのために取っておく
+    let (text, source_domains) = if source_links.is_empty() {
+        (append_sources(&parsed.reply, &parsed.sources), Vec::new())
+    } else {
+        (parsed.reply, parsed.sources)
+    };
+
+    ReplyResult {
+        text,
+        response_id: res.id,
+        content_warning: parsed.content_warning.filter(|_| cfg.reply_model_cw),
+        no_reply: parsed.no_reply,
+        source_links,
+        source_domains,
+        model: res.model,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::openai_api::types::ChatMessage;
+    use crate::openai_api::types::{ChatMessage, Citation};
 
     fn message(role: &str, content: &str) -> ChatMessage {
         ChatMessage { role: role.to_string(), content: content.to_string() }
@@ -166,15 +329,46 @@ mod tests {
             id: "resp_1".to_string(),
             text: text.to_string(),
             status: status.map(|s| s.to_string()),
+            function_calls: Vec::new(),
+            citations: Vec::new(),
+            model: "gpt-test-reply".to_string(),
         }
     }
 
+    #[test]
+    fn sends_context_only_when_conversation_cannot_continue() {
+        let prompt = ReplyPrompt {
+            user_text: "hello",
+            conversation_context: Some("ctx"),
+            persona: "default",
+            timezone: chrono_tz::Asia::Tokyo,
+            language: None,
+            content_warning: false,
+            images: &[],
+        };
+        let previous = PreviousResponse { id: "resp_123".into(), model: Some("gpt-a".into()) };
+
+        let (first, id) = continue_conversation(prompt, None, "gpt-a");
+        assert_eq!((first.conversation_context, id), (Some("ctx"), None));
+
+        let (same, id) = continue_conversation(prompt, Some(&previous), "gpt-a");
+        assert_eq!((same.conversation_context, id.as_deref()), (None, Some("resp_123")));
+
+        // フォールバックで別のモデルになったら、会話ログから始め直す
+        let (other, id) = continue_conversation(prompt, Some(&previous), "gpt-b");
+        assert_eq!((other.conversation_context, id), (Some("ctx"), None));
+    }
+
     #[test]
     fn retries_empty_or_incomplete_responses() {
         assert!(should_retry_empty_or_incomplete(&response("  ", Some("completed"))));
         assert!(should_retry_empty_or_incomplete(&response("text", Some("incomplete"))));
         assert!(!should_retry_empty_or_incomplete(&response("text", Some("completed"))));
         assert!(!should_retry_empty_or_incomplete(&response("text", None)));
+        assert!(should_retry_empty_or_incomplete(&response(
+            r#"{"reply":"","content_warning":null}"#,
+            Some("completed")
+        )));
     }
 
     #[test]
@@ -185,21 +379,91 @@ mod tests {
 
     #[test]
     fn final_reply_trims_text_and_replaces_json_like_output() {
-        assert_eq!(final_reply_text("  hello  "), "hello");
-        assert_eq!(final_reply_text("{\"text\":\"hello\"}"), JSON_FALLBACK_REPLY);
-        assert_eq!(final_reply_text("[hello]"), JSON_FALLBACK_REPLY);
+        assert_eq!(final_reply_text("  hello  ", "fallback"), "hello");
+        assert_eq!(final_reply_text("{\"text\":\"hello\"}", "fallback"), "fallback");
+        assert_eq!(final_reply_text("[hello]", "fallback"), "fallback");
     }
 
     #[test]
-    fn web_search_tools_are_enabled_by_config_or_forced_search() {
-        assert!(build_web_search_tools(false, false).is_empty());
+    fn structured_reply_is_parsed_and_validated() {
+        let mut cfg = crate::test_support::test_config();
+        let raw = r#"{"reply":"1.80 が出たよ","content_warning":"ネタバレ","no_reply":false,"sources":["https://blog.rust-lang.org/x"]}"#;
+
+        let result = reply_result(&cfg, true, "fallback", response(raw, Some("completed")));
+        assert_eq!(result.text, "1.80 が出たよ (blog.rust-lang.org)");
+        assert_eq!(result.response_id, "resp_1");
+        // REPLY_MODEL_CW=false なら CW は使わない
+        assert_eq!(result.content_warning, None);
+        assert!(!result.no_reply);
+
+        cfg.reply_model_cw = true;
+        let result = reply_result(&cfg, true, "fallback", response(raw, Some("completed")));
+        assert_eq!(result.content_warning.as_deref(), Some("ネタバレ"));
+
+        let result =
+            reply_result(&cfg, true, "fallback", response(r#"{"reply":"","no_reply":true}"#, None));
+        assert!(result.no_reply);
+    }
 
-        for tools in [build_web_search_tools(true, false), build_web_search_tools(false, true)] {
+    #[test]
+    fn citations_replace_source_domains_when_links_are_enabled() {
+        let mut cfg = crate::test_support::test_config();
+        let raw = r#"{"reply":"1.80 が出たよ","content_warning":null,"no_reply":false,"sources":["blog.rust-lang.org"]}"#;
+        let mut res = response(raw, Some("completed"));
+        res.citations =
+            ["https://blog.rust-lang.org/a", "https://example.com/b", "https://c.example/"]
+                .iter()
+                .map(|url| Citation { url: url.to_string(), title: None })
+                .collect();
+
+        let result = reply_result(&cfg, true, "fallback", res.clone());
+        assert_eq!(result.text, "1.80 が出たよ (blog.rust-lang.org)");
+        assert!(result.source_links.is_empty());
+
+        cfg.reply_source_links = 2;
+        let result = reply_result(&cfg, true, "fallback", res);
+        assert_eq!(result.text, "1.80 が出たよ");
+        assert_eq!(
+            result.source_links,
+            vec!["https://blog.rust-lang.org/a", "https://example.com/b"]
+        );
+
+        assert_eq!(
+            result.body_with_sources(500, "alice"),
+            "1.80 が出たよ\nhttps://blog.rust-lang.org/a\nhttps://example.com/b"
+        );
+        // URL が 1 つも収まらなければドメイン表記に戻す
+        assert_eq!(result.body_with_sources(20, "alice"), "1.80 が出たよ (blog.rust-lang.org)");
+    }
+
+    #[test]
+    fn unreadable_structured_output_falls_back_to_plain_text() {
+        let cfg = crate::test_support::test_config();
+
+        let result = reply_result(&cfg, true, "fallback", response("ただのテキスト", None));
+        assert_eq!(result.text, "ただのテキスト");
+
+        let result =
+            reply_result(&cfg, true, "fallback", response(r#"{"reply":"途中で切れ"#, None));
+        assert_eq!(result.text, "fallback");
+    }
+
+    #[test]
+    fn web_search_tools_are_enabled_by_config_or_forced_search() {
+        assert!(build_web_search_tools(false, None).is_empty());
+
+        let search =
+            SearchDecision { instructions: Vec::new(), search_context_size: "medium".to_string() };
+        for (tools, size) in [
+            (build_web_search_tools(true, None), "low"),
+            (build_web_search_tools(false, Some(&search)), "medium"),
+        ] {
             assert_eq!(tools.len(), 1);
             match &tools[0] {
                 Tool::WebSearchPreview { search_context_size } => {
-                    assert_eq!(search_context_size.as_deref(), Some("low"));
+                    assert_eq!(search_context_size.as_deref(), Some(size));
                 }
+                other => panic!("unexpected tool: {other:?}"),
             }
         }
     }
@@ -211,7 +475,9 @@ mod tests {
         cfg.openai_reply_model = "reply-model".to_string();
         cfg.openai_api_key = "api-key".to_string();
         cfg.reply_temperature = 0.5;
-        let call_config = OpenAiCallConfig::for_reply(&cfg);
+        cfg.reply_structured_output = false;
+        let store = ConversationStore::new(":memory:").unwrap();
+        let call_config = OpenAiCallConfig::for_reply(&cfg, &store);
         let tools = vec![Tool::WebSearchPreview { search_context_size: Some("low".into()) }];
 
         let args =
@@ -225,5 +491,21 @@ mod tests {
         assert_eq!(args.previous_response_id.as_deref(), Some("resp_prev"));
         assert_eq!(args.messages.len(), 1);
         assert!(args.tools.is_some());
+        assert!(args.text_format.is_none());
+    }
+
+    #[test]
+    fn structured_reply_call_sets_text_format_and_room_for_json() {
+        let cfg = crate::test_support::test_config();
+        let store = ConversationStore::new(":memory:").unwrap();
+        let call_config = OpenAiCallConfig::for_reply(&cfg, &store);
+
+        let args = call_config.build(vec![message("user", "hello")], 140, None, Vec::new());
+        assert!(args.text_format.is_some());
+        assert_eq!(args.max_output_tokens, Some(180));
+
+        let args = call_config.plain_text().build(vec![], 140, None, Vec::new());
+        assert!(args.text_format.is_none());
+        assert_eq!(args.max_output_tokens, Some(140));
     }
 }
@@ -1,45 +1,206 @@
-use once_cell::sync::Lazy;
-use regex::Regex;
+use anyhow::{Result, anyhow};
+use reqwest::Client;
 
-static JP_RELEASE_QUERY_RE: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"(リリースノート|変更点|変更履歴|ハイライト|新機能|何が(新しい|変わった)|教えて)")
-        .unwrap()
-});
-static EN_RELEASE_QUERY_RE: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"(release\s*notes?|changelog|what'?s\s*new|highlights?|patch\s*notes?)").unwrap()
-});
-static VERSION_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b\d+\.\d+(\.\d+)?\b").unwrap());
+use crate::config::{BotConfig, SearchClassifier};
+use crate::openai_api::prompts::{PROMPTS, ReplyInstructions, SearchRule, search_rules};
+use crate::openai_api::stream::{CallResponsesArgs, call_responses};
+use crate::openai_api::types::ChatMessage;
 
-pub(super) fn should_force_search(user_text: &str) -> bool {
-    JP_RELEASE_QUERY_RE.is_match(user_text)
-        || EN_RELEASE_QUERY_RE.is_match(user_text)
-        || VERSION_RE.is_match(user_text)
+const DEFAULT_SEARCH_CONTEXT_SIZE: &str = "low";
+
+/// 検索を強制するときに使う指示と検索の深さ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct SearchDecision {
+    pub instructions: Vec<String>,
+    pub search_context_size: String,
+}
+
+/// ルールに一致した結果
+pub(super) struct SearchHit<'a> {
+    rule: &'a SearchRule,
+    matched: String,
+}
+
+pub(super) fn match_search_rule<'a>(
+    rules: &'a [SearchRule],
+    user_text: &str,
+) -> Option<SearchHit<'a>> {
+    rules.iter().find_map(|rule| Some(SearchHit { rule, matched: rule.find(user_text)? }))
+}
+
+/// ルールとモデルの判定から、この返信で検索を強制するかを決める
+pub(super) async fn decide_search(
+    client: &Client,
+    cfg: &BotConfig,
+    user_text: &str,
+) -> Option<SearchDecision> {
+    let hit = match_search_rule(search_rules(), user_text);
+    let instructions = &PROMPTS.reply_instructions;
+
+    let classified = match cfg.search_classifier {
+        SearchClassifier::Off => None,
+        SearchClassifier::Confirm if hit.is_none() => None,
+        SearchClassifier::Confirm | SearchClassifier::Always => {
+            let args = classifier_args(cfg, &instructions.search_classifier, user_text);
+            match classify_search(client, args).await {
+                Ok(needed) => Some(needed),
+                Err(e) => {
+                    // 判定に失敗したらルールの結果を使う
+                    eprintln!("[search] classifier failed: {:?}", e);
+                    None
+                }
+            }
+        }
+    };
+
+    let force = classified.unwrap_or(hit.is_some());
+    println!(
+        "[search] rule={} classifier={} force={}",
+        hit.as_ref().map_or("-".to_string(), |h| format!("{}({:?})", h.rule.name, h.matched)),
+        classified.map_or("-", |needed| if needed { "yes" } else { "no" }),
+        force
+    );
+
+    force.then(|| search_decision(hit.as_ref(), instructions))
+}
+
+/// 一致したルールの指示（`{{MATCH}}` を置き換え）。ルールがないか指示が空なら組み込みの指示
+fn search_decision(
+    hit: Option<&SearchHit<'_>>,
+    instructions: &ReplyInstructions,
+) -> SearchDecision {
+    let instructions = match hit {
+        Some(h) if !h.rule.instructions.is_empty() => {
+            h.rule.instructions.iter().map(|i| i.replace("{{MATCH}}", &h.matched)).collect()
+        }
+        _ => vec![instructions.search_mandate.clone(), instructions.patch_release.clone()],
+    };
+
+    let search_context_size = hit
+        .and_then(|h| h.rule.search_context_size.clone())
+        .unwrap_or_else(|| DEFAULT_SEARCH_CONTEXT_SIZE.to_string());
+
+    SearchDecision { instructions, search_context_size }
+}
+
+fn classifier_args<'a>(
+    cfg: &'a BotConfig,
+    instruction: &str,
+    user_text: &str,
+) -> CallResponsesArgs<'a> {
+    let messages = vec![
+        ChatMessage { role: "system".into(), content: instruction.to_string() },
+        ChatMessage { role: "user".into(), content: user_text.to_string() },
+    ];
+
+    CallResponsesArgs::new(
+        &cfg.search_classifier_model,
+        &cfg.search_classifier_model,
+        &cfg.openai_api_key,
+        messages,
+    )
+    .temperature(0.0)
+    .max_output_tokens(16)
+    .model_table(&cfg.model_capabilities)
+}
+
+/// モデルに yes / no で答えてもらう
+async fn classify_search(client: &Client, args: CallResponsesArgs<'_>) -> Result<bool> {
+    let res = call_responses(client, args, false).await?;
+    parse_classifier_answer(&res.text)
+        .ok_or_else(|| anyhow!("unexpected classifier answer: {:?}", res.text))
+}
+
+fn parse_classifier_answer(text: &str) -> Option<bool> {
+    let answer = text.trim().trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    if answer.starts_with("yes") {
+        Some(true)
+    } else if answer.starts_with("no") {
+        Some(false)
+    } else {
+        None
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::openai_api::prompts::SearchRuleDef;
+    use crate::test_support::{MockHttpServer, test_config};
 
-    #[test]
-    fn detects_japanese_release_queries() {
-        assert!(should_force_search("Rust 1.91.1 のリリースノート教えて"));
-        assert!(should_force_search("新機能は何が変わった？"));
+    fn rules(json: &str) -> Vec<SearchRule> {
+        let defs: Vec<SearchRuleDef> = serde_json::from_str(json).unwrap();
+        defs.iter().map(|d| SearchRule::compile(d).unwrap()).collect()
     }
 
     #[test]
-    fn detects_english_release_queries() {
-        assert!(should_force_search("what's new in Rust"));
-        assert!(should_force_search("show me the changelog"));
+    fn first_matching_rule_wins() {
+        let rules = rules(
+            r#"[
+                {"name": "weather", "keywords": ["天気"]},
+                {"name": "any", "patterns": ["."]}
+            ]"#,
+        );
+
+        let hit = match_search_rule(&rules, "明日の天気は？").unwrap();
+        assert_eq!(hit.rule.name, "weather");
+        assert_eq!(hit.matched, "天気");
+
+        assert_eq!(match_search_rule(&rules, "やあ").unwrap().rule.name, "any");
+        assert!(match_search_rule(&rules[..1], "やあ").is_none());
     }
 
     #[test]
-    fn detects_version_numbers() {
-        assert!(should_force_search("1.91"));
-        assert!(should_force_search("1.91.1"));
+    fn decision_uses_rule_instructions_or_builtin_ones() {
+        let rules = rules(
+            r#"[
+                {"name": "weather", "keywords": ["天気"], "instructions": ["{{MATCH}}を調べて"],
+                 "search_context_size": "medium"},
+                {"name": "release", "keywords": ["changelog"]}
+            ]"#,
+        );
+        let builtin = ReplyInstructions::default();
+
+        let hit = match_search_rule(&rules, "天気").unwrap();
+        let decision = search_decision(Some(&hit), &builtin);
+        assert_eq!(decision.instructions, vec!["天気を調べて"]);
+        assert_eq!(decision.search_context_size, "medium");
+
+        let hit = match_search_rule(&rules, "changelog").unwrap();
+        let decision = search_decision(Some(&hit), &builtin);
+        assert_eq!(
+            decision.instructions,
+            vec![builtin.search_mandate.clone(), builtin.patch_release.clone()]
+        );
+        assert_eq!(decision.search_context_size, "low");
+
+        assert_eq!(search_decision(None, &builtin).instructions.len(), 2);
     }
 
     #[test]
-    fn does_not_force_search_for_plain_chat() {
-        assert!(!should_force_search("今日のお昼なに食べよう"));
+    fn parses_classifier_answers() {
+        assert_eq!(parse_classifier_answer("yes"), Some(true));
+        assert_eq!(parse_classifier_answer(" Yes."), Some(true));
+        assert_eq!(parse_classifier_answer("no"), Some(false));
+        assert_eq!(parse_classifier_answer("\"No\""), Some(false));
+        assert_eq!(parse_classifier_answer("maybe"), None);
+        assert_eq!(parse_classifier_answer(""), None);
+    }
+
+    #[tokio::test]
+    async fn classifier_asks_the_configured_model() {
+        let server = MockHttpServer::respond(
+            "200 OK",
+            r#"{"id":"resp_1","status":"completed","output":[{"type":"output_text","text":"no"}]}"#,
+        );
+        let api_url = server.url("/v1/responses");
+        let mut cfg = test_config();
+        cfg.search_classifier_model = "gpt-mini".into();
+
+        let args = classifier_args(&cfg, "yes or no", "3.5時間寝た").api_url(&api_url);
+        assert_eq!(args.model, "gpt-mini");
+        assert_eq!(args.max_output_tokens, Some(16));
+
+        assert!(!classify_search(&Client::new(), args).await.unwrap());
     }
 }
@@ -0,0 +1,230 @@
+//! 返信の構造化出力（`text.format` の JSON スキーマ）
+//!
+//! 構造化出力に対応していないモデルでは、プレーンテキストの返信に切り替える。
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use crate::openai_api::fallback::openai_error_message;
+use crate::openai_api::types::TextFormat;
+
+/// 出典ドメインは多くても 3 つまで載せる
+const MAX_SOURCES: usize = 3;
+
+// 構造化出力を断られたモデル（プロセスが動いている間は聞き直さない）
+static UNSUPPORTED_MODELS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// 構造化出力で受け取る返信
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub(super) struct StructuredReply {
+    pub reply: String,
+    #[serde(default)]
+    pub content_warning: Option<String>,
+    /// 返信しなくてよい（あいさつへのお礼など）
+    #[serde(default)]
+    pub no_reply: bool,
+    /// 返信の根拠にした出典のドメイン
+    #[serde(default)]
+    pub sources: Vec<String>,
+}
+
+/// 返信の `text.format`
+pub(in crate::openai_api) fn reply_text_format() -> TextFormat {
+    TextFormat::JsonSchema {
+        name: "mastodon_reply".into(),
+        schema: json!({
+            "type": "object",
+            "properties": {
+                "reply": {
+                    "type": "string",
+                    "description": "返信の本文（プレーンテキスト、URL なし）"
+                },
+                "content_warning": {
+                    "type": ["string", "null"],
+                    "description": "センシティブな話題に触れるときだけ付ける短い CW。不要なら null"
+                },
+                "no_reply": {
+                    "type": "boolean",
+                    "description": "返信する必要がないときだけ true（そのとき reply は空でよい）"
+                },
+                "sources": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Web 検索で参照した出典のドメイン（例: blog.rust-lang.org）。なければ空"
+                }
+            },
+            "required": ["reply", "content_warning", "no_reply", "sources"],
+            "additionalProperties": false
+        }),
+        strict: true,
+    }
+}
+
+/// 構造化出力として読めなければ None（プレーンテキストとして扱う）
+pub(super) fn parse_structured_reply(text: &str) -> Option<StructuredReply> {
+    let parsed: StructuredReply = serde_json::from_str(text.trim()).ok()?;
+    let reply = parsed.reply.trim().to_string();
+    if reply.is_empty() && !parsed.no_reply {
+        return None;
+    }
+
+    let content_warning =
+        parsed.content_warning.map(|w| w.trim().to_string()).filter(|w| !w.is_empty());
+    let mut sources: Vec<String> = Vec::new();
+    for domain in parsed.sources.iter().filter_map(|s| normalize_domain(s)) {
+        if !sources.contains(&domain) && sources.len() < MAX_SOURCES {
+            sources.push(domain);
+        }
+    }
+
+    Some(StructuredReply { reply, content_warning, no_reply: parsed.no_reply, sources })
+}
+
+/// リトライの判定に使う本文（構造化出力なら `reply` だけ）
+pub(super) fn reply_body(text: &str) -> String {
+    match serde_json::from_str::<StructuredReply>(text.trim()) {
+        Ok(parsed) => parsed.reply,
+        Err(_) => text.to_string(),
+    }
+}
+
+/// 本文が空か（`no_reply` なら空でよい）
+pub(super) fn is_blank_reply(text: &str) -> bool {
+    match serde_json::from_str::<StructuredReply>(text.trim()) {
+        Ok(parsed) => !parsed.no_reply && parsed.reply.trim().is_empty(),
+        Err(_) => text.trim().is_empty(),
+    }
+}
+
+/// 本文にまだ出てこない出典ドメインを末尾に括弧書きで足す
+pub(super) fn append_sources(reply: &str, sources: &[String]) -> String {
+    let missing: Vec<&str> =
+        sources.iter().map(String::as_str).filter(|domain| !reply.contains(domain)).collect();
+    if missing.is_empty() {
+        reply.to_string()
+    } else {
+        format!("{} ({})", reply, missing.join(", "))
+    }
+}
+
+/// `https://blog.example/a` や `Blog.Example` を `blog.example` にする。ドメインでなければ None
+fn normalize_domain(source: &str) -> Option<String> {
+    let source = source.trim();
+    let without_scheme = source.split_once("://").map_or(source, |(_, rest)| rest);
+    let host = without_scheme.split(['/', '?', '#']).next()?.trim_start_matches("www.");
+    let host = host.to_lowercase();
+
+    let valid = host.contains('.')
+        && !host.starts_with('.')
+        && !host.ends_with('.')
+        && host.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '-' || c == ':');
+    valid.then_some(host)
+}
+
+/// OpenAI のエラーが「このモデルは構造化出力に対応していない」ものか
+pub(super) fn is_unsupported_text_format(err: &anyhow::Error) -> bool {
+    let message = openai_error_message(err);
+    message.starts_with("OpenAI error 400")
+        && ["text.format", "json_schema", "response_format"].iter().any(|k| message.contains(k))
+}
+
+pub(super) fn supports_structured_output(model: &str) -> bool {
+    !UNSUPPORTED_MODELS.lock().unwrap().contains(model)
+}
+
+pub(super) fn mark_unsupported(model: &str) {
+    UNSUPPORTED_MODELS.lock().unwrap().insert(model.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_reply_and_content_warning() {
+        let parsed = parse_structured_reply(
+            r#"{"reply":" つらかったね ","content_warning":"病気","no_reply":false,"sources":[]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(parsed.reply, "つらかったね");
+        assert_eq!(parsed.content_warning.as_deref(), Some("病気"));
+        assert!(!parsed.no_reply);
+    }
+
+    #[test]
+    fn blank_content_warning_is_dropped() {
+        let parsed = parse_structured_reply(r#"{"reply":"hi","content_warning":" "}"#).unwrap();
+        assert_eq!(parsed.content_warning, None);
+
+        let parsed = parse_structured_reply(r#"{"reply":"hi","content_warning":null}"#).unwrap();
+        assert_eq!(parsed.content_warning, None);
+    }
+
+    #[test]
+    fn empty_reply_is_valid_only_with_no_reply() {
+        assert_eq!(parse_structured_reply(r#"{"reply":" ","no_reply":false}"#), None);
+
+        let parsed = parse_structured_reply(r#"{"reply":"","no_reply":true}"#).unwrap();
+        assert!(parsed.no_reply);
+    }
+
+    #[test]
+    fn normalizes_and_limits_sources() {
+        let parsed = parse_structured_reply(
+            r#"{"reply":"hi","sources":["https://Blog.Rust-Lang.org/2024/x","blog.rust-lang.org","not a domain","www.a.example","b.example","c.example"]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(parsed.sources, vec!["blog.rust-lang.org", "a.example", "b.example"]);
+    }
+
+    #[test]
+    fn appends_only_missing_sources() {
+        let sources = vec!["a.example".to_string(), "b.example".to_string()];
+
+        assert_eq!(
+            append_sources("見てね (a.example)", &sources),
+            "見てね (a.example) (b.example)"
+        );
+        assert_eq!(append_sources("見てね", &sources[..1]), "見てね (a.example)");
+        assert_eq!(append_sources("見てね", &[]), "見てね");
+    }
+
+    #[test]
+    fn plain_text_is_not_structured() {
+        assert_eq!(parse_structured_reply("こんにちは"), None);
+        assert_eq!(parse_structured_reply(r#"{"text":"hi"}"#), None);
+        assert_eq!(reply_body("こんにちは"), "こんにちは");
+        assert_eq!(reply_body(r#"{"reply":"hi","content_warning":null}"#), "hi");
+    }
+
+    #[test]
+    fn blank_reply_allows_no_reply() {
+        assert!(is_blank_reply("  "));
+        assert!(is_blank_reply(r#"{"reply":" ","no_reply":false}"#));
+        assert!(!is_blank_reply(r#"{"reply":"","no_reply":true}"#));
+        assert!(!is_blank_reply("hi"));
+    }
+
+    #[test]
+    fn detects_unsupported_text_format_errors() {
+        let unsupported = anyhow::anyhow!(
+            "OpenAI error 400 Bad Request: {{\"error\":{{\"message\":\"Invalid parameter: 'text.format' of type 'json_schema' is not supported with this model.\"}}}}"
+        );
+        assert!(is_unsupported_text_format(&unsupported));
+        assert!(is_unsupported_text_format(&unsupported.context("OpenAI model gpt-b")));
+        assert!(!is_unsupported_text_format(&anyhow::anyhow!("OpenAI error 500: json_schema")));
+        assert!(!is_unsupported_text_format(&anyhow::anyhow!("OpenAI error 400 Bad Request: bad")));
+    }
+
+    #[test]
+    fn remembers_unsupported_models() {
+        assert!(supports_structured_output("structured-test-model"));
+        mark_unsupported("structured-test-model");
+        assert!(!supports_structured_output("structured-test-model"));
+    }
+}
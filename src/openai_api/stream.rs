@@ -2,11 +2,16 @@ use anyhow::{Result, anyhow};
 use reqwest::Client;
 use serde_json::Value;
 
-use crate::openai_api::types::{ChatMessage, ResponsesRequest, ResponsesResult, Tool};
+use crate::config::ModelTable;
+use crate::openai_api::types::{
+    ChatMessage, Citation, ContentPart, FunctionCall, FunctionCallOutput, InputItem,
+    ReasoningConfig, ResponsesRequest, ResponsesResult, TextConfig, TextFormat, Tool,
+};
 
 const RESPONSES_API_URL: &str = "https://api.openai.com/v1/responses";
 
 /// `call_responses` に渡す引数まとめ
+#[derive(Clone)]
 pub struct CallResponsesArgs<'a> {
     pub api_url: &'a str,
     pub model: &'a str,
@@ -17,6 +22,14 @@ pub struct CallResponsesArgs<'a> {
     pub max_output_tokens: Option<u32>,
     pub previous_response_id: Option<String>,
     pub tools: Option<Vec<Tool>>,
+    pub text_format: Option<TextFormat>,
+    /// 前の応答で頼まれた関数ツールの結果
+    pub function_outputs: Vec<FunctionCallOutput>,
+    pub tool_choice: Option<String>,
+    /// モデルの対応機能の表（temperature・推論・トークン上限などを決める）
+    pub model_table: &'a ModelTable,
+    /// 最後の user メッセージに添える画像の URL（画像に対応したモデルのときだけ送る）
+    pub images: Vec<String>,
 }
 
 impl<'a> CallResponsesArgs<'a> {
@@ -36,6 +49,11 @@ impl<'a> CallResponsesArgs<'a> {
             max_output_tokens: None,
             previous_response_id: None,
             tools: None,
+            text_format: None,
+            function_outputs: Vec::new(),
+            tool_choice: None,
+            model_table: ModelTable::builtin_ref(),
+            images: Vec::new(),
         }
     }
     #[cfg(test)]
@@ -59,6 +77,37 @@ impl<'a> CallResponsesArgs<'a> {
         self.tools = if tools.is_empty() { None } else { Some(tools) };
         self
     }
+    pub fn text_format(mut self, format: TextFormat) -> Self {
+        self.text_format = Some(format);
+        self
+    }
+    pub fn tool_choice<S: Into<String>>(mut self, choice: S) -> Self {
+        self.tool_choice = Some(choice.into());
+        self
+    }
+    /// 自由トゥート・返信のどちらの呼び出しでもこのモデルを使う（フォールバック用）
+    pub fn model(mut self, model: &'a str) -> Self {
+        self.model = model;
+        self.model_reply = model;
+        self
+    }
+    pub fn model_table(mut self, table: &'a ModelTable) -> Self {
+        self.model_table = table;
+        self
+    }
+    pub fn images(mut self, images: Vec<String>) -> Self {
+        self.images = images;
+        self
+    }
+    /// 関数ツールの結果を返して続きを頼む
+    ///
+    /// 会話は `previous_response_id` で引き継ぐので、送り直すのは system メッセージ（instructions）だけ
+    pub fn follow_up(mut self, response_id: &str, outputs: Vec<FunctionCallOutput>) -> Self {
+        self.messages.retain(|m| m.role == "system");
+        self.previous_response_id = Some(response_id.to_string());
+        self.function_outputs = outputs;
+        self
+    }
 }
 
 /// `{"type":"output_text","text":"..."}` を優先的に抽出
@@ -107,32 +156,67 @@ pub async fn call_responses(
         return Err(anyhow!("OpenAI error {}: {}", status_code, raw));
     }
 
-    parse_responses_result(&raw)
+    let mut result = parse_responses_result(&raw)?;
+    result.model = req_body.model;
+    Ok(result)
 }
 
 fn build_responses_request(
     args: CallResponsesArgs<'_>,
     is_reply: bool,
 ) -> (&str, &str, ResponsesRequest) {
-    let (instructions, input) = split_messages_for_responses(args.messages);
-
-    let model = if is_reply { args.model_reply.to_string() } else { args.model.to_string() };
-
-    let temperature = if model.contains("gpt-5") { None } else { args.temperature };
+    let model = if is_reply { args.model_reply } else { args.model };
+    let caps = args.model_table.lookup(model);
+
+    let (instructions, messages) = split_messages_for_responses(args.messages);
+    let images = if caps.vision { args.images } else { Vec::new() };
+    let input = input_with_images(messages, images)
+        .into_iter()
+        .chain(args.function_outputs.into_iter().map(InputItem::FunctionCallOutput))
+        .collect();
+
+    let (tools, tool_choice) =
+        if caps.tools { (args.tools, args.tool_choice) } else { (None, None) };
+    let text = if args.text_format.is_some() || caps.verbosity.is_some() {
+        Some(TextConfig { format: args.text_format, verbosity: caps.verbosity })
+    } else {
+        None
+    };
 
     let req_body = ResponsesRequest {
-        model,
+        model: model.to_string(),
         input,
         instructions,
-        temperature,
-        max_output_tokens: args.max_output_tokens,
+        temperature: args.temperature.filter(|_| caps.temperature),
+        max_output_tokens: caps.max_output_tokens.or(args.max_output_tokens),
         previous_response_id: args.previous_response_id,
-        tools: args.tools,
+        tools,
+        tool_choice,
+        text,
+        reasoning: caps.reasoning_effort.map(|effort| ReasoningConfig { effort }),
     };
 
     (args.api_url, args.api_key, req_body)
 }
 
+/// 画像があれば最後の user メッセージを本文と画像のパーツにする
+fn input_with_images(messages: Vec<ChatMessage>, images: Vec<String>) -> Vec<InputItem> {
+    let last_user = messages.iter().rposition(|m| m.role == "user");
+
+    messages
+        .into_iter()
+        .enumerate()
+        .map(|(i, msg)| {
+            if images.is_empty() || Some(i) != last_user {
+                return InputItem::Message(msg);
+            }
+            let mut content = vec![ContentPart::Text { text: msg.content }];
+            content.extend(images.iter().map(|url| ContentPart::Image { image_url: url.clone() }));
+            InputItem::Parts { role: msg.role, content }
+        })
+        .collect()
+}
+
 fn parse_responses_result(raw: &str) -> Result<ResponsesResult> {
     let v: Value = serde_json::from_str(raw)
         .map_err(|e| anyhow!("error decoding response body: {}\nraw: {}", e, raw))?;
@@ -148,8 +232,93 @@ fn parse_responses_result(raw: &str) -> Result<ResponsesResult> {
         // ぜんぜん拾えなかった場合は空文字のまま返し、呼び出し側でリカバリ
         // （ここで raw を返して Mastodon に貼らない）
     }
+    let function_calls = v.get("output").map(extract_function_calls).unwrap_or_default();
+    let mut citations = Vec::new();
+    if let Some(output) = v.get("output") {
+        extract_citations(output, &mut citations);
+    }
 
-    Ok(ResponsesResult { id, text, status: Some(status) })
+    Ok(ResponsesResult {
+        id,
+        text,
+        status: Some(status),
+        function_calls,
+        citations,
+        model: String::new(),
+    })
+}
+
+/// `{"type":"url_citation","url":"..."}` の注釈を出てきた順に集める（同じ URL は 1 つだけ）
+fn extract_citations(v: &Value, out: &mut Vec<Citation>) {
+    match v {
+        Value::Object(map) => {
+            if map.get("type").and_then(Value::as_str) == Some("url_citation")
+                && let Some(url) = map.get("url").and_then(Value::as_str)
+            {
+                let url = strip_tracking_param(url);
+                if !out.iter().any(|c| c.url == url) {
+                    let title = map.get("title").and_then(Value::as_str).map(str::to_string);
+                    out.push(Citation { url, title });
+                }
+            }
+
+            for vv in map.values() {
+                extract_citations(vv, out);
+            }
+        }
+        Value::Array(arr) => {
+            for vv in arr {
+                extract_citations(vv, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Web 検索の出典 URL に付く `utm_source=openai` を外す
+fn strip_tracking_param(url: &str) -> String {
+    let Some((base, query)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    let (query, fragment) = match query.split_once('#') {
+        Some((query, fragment)) => (query, Some(fragment)),
+        None => (query, None),
+    };
+
+    let kept: Vec<&str> = query.split('&').filter(|p| *p != "utm_source=openai").collect();
+    let mut url = base.to_string();
+    if !kept.is_empty() && kept != [""] {
+        url.push('?');
+        url.push_str(&kept.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        url.push('#');
+        url.push_str(fragment);
+    }
+    url
+}
+
+/// `output` 直下の `{"type":"function_call", ...}` を取り出す
+fn extract_function_calls(output: &Value) -> Vec<FunctionCall> {
+    let Some(items) = output.as_array() else {
+        return Vec::new();
+    };
+
+    items
+        .iter()
+        .filter(|item| item.get("type").and_then(Value::as_str) == Some("function_call"))
+        .filter_map(|item| {
+            Some(FunctionCall {
+                call_id: item.get("call_id")?.as_str()?.to_string(),
+                name: item.get("name")?.as_str()?.to_string(),
+                arguments: item
+                    .get("arguments")
+                    .and_then(Value::as_str)
+                    .unwrap_or("{}")
+                    .to_string(),
+            })
+        })
+        .collect()
 }
 
 fn split_messages_for_responses(messages: Vec<ChatMessage>) -> (Option<String>, Vec<ChatMessage>) {
@@ -238,7 +407,7 @@ mod tests {
     }
 
     #[test]
-    fn build_responses_request_selects_reply_model_and_omits_gpt5_temperature() {
+    fn build_responses_request_selects_reply_model_and_uses_reasoning_parameters() {
         let args = CallResponsesArgs::new(
             "gpt-4.1",
             "gpt-5-test",
@@ -257,8 +426,93 @@ mod tests {
         assert_eq!(req.instructions.as_deref(), Some("be concise"));
         assert_eq!(req.input.len(), 1);
         assert_eq!(req.temperature, None);
-        assert_eq!(req.max_output_tokens, Some(140));
+        // 推論トークンの分、組み込みの表の上限を使う
+        assert_eq!(req.max_output_tokens, Some(2048));
         assert_eq!(req.previous_response_id.as_deref(), Some("resp_prev"));
+
+        let body = serde_json::to_value(&req).unwrap();
+        assert_eq!(body["reasoning"]["effort"], "low");
+        assert_eq!(body["text"], json!({"verbosity": "low"}));
+    }
+
+    #[test]
+    fn build_responses_request_keeps_parameters_for_unknown_models() {
+        let args = CallResponsesArgs::new("my-model", "my-model", "api-key", vec![])
+            .temperature(0.7)
+            .max_output_tokens(140)
+            .tools(vec![Tool::WebSearchPreview { search_context_size: Some("low".into()) }]);
+
+        let (_, _, req) = build_responses_request(args, false);
+        let body = serde_json::to_value(&req).unwrap();
+
+        assert_eq!(req.temperature, Some(0.7));
+        assert_eq!(req.max_output_tokens, Some(140));
+        assert!(req.tools.is_some());
+        assert!(body.get("reasoning").is_none());
+        assert!(body.get("text").is_none());
+    }
+
+    #[test]
+    fn build_responses_request_follows_custom_model_table() {
+        let table =
+            ModelTable::parse(r#"[{"model": "local-*", "tools": false, "temperature": false}]"#)
+                .unwrap();
+        let args = CallResponsesArgs::new("local-llm", "local-llm", "api-key", vec![])
+            .temperature(0.7)
+            .tools(vec![Tool::WebSearchPreview { search_context_size: Some("low".into()) }])
+            .tool_choice("none")
+            .model_table(&table);
+
+        let (_, _, req) = build_responses_request(args, false);
+
+        assert_eq!(req.temperature, None);
+        assert!(req.tools.is_none());
+        assert!(req.tool_choice.is_none());
+    }
+
+    #[test]
+    fn build_responses_request_attaches_images_only_for_vision_models() {
+        let messages = || vec![message("user", "earlier"), message("user", "この写真は？")];
+        let images = vec!["https://example.com/cat.png".to_string()];
+
+        let args = CallResponsesArgs::new("gpt-4o-mini", "gpt-4o-mini", "api-key", messages())
+            .images(images.clone());
+        let (_, _, req) = build_responses_request(args, false);
+        let body = serde_json::to_value(&req).unwrap();
+
+        assert_eq!(body["input"][0]["content"], "earlier");
+        assert_eq!(
+            body["input"][1]["content"],
+            json!([
+                {"type": "input_text", "text": "この写真は？"},
+                {"type": "input_image", "image_url": "https://example.com/cat.png"}
+            ])
+        );
+
+        let args =
+            CallResponsesArgs::new("my-model", "my-model", "api-key", messages()).images(images);
+        let (_, _, req) = build_responses_request(args, false);
+        let body = serde_json::to_value(&req).unwrap();
+
+        assert_eq!(body["input"][1]["content"], "この写真は？");
+    }
+
+    #[test]
+    fn build_responses_request_sets_text_format() {
+        let format = TextFormat::JsonSchema {
+            name: "reply".into(),
+            schema: json!({"type": "object"}),
+            strict: true,
+        };
+        let args =
+            CallResponsesArgs::new("gpt-test", "gpt-test", "api-key", vec![]).text_format(format);
+
+        let (_, _, req) = build_responses_request(args, true);
+        let body = serde_json::to_value(&req).unwrap();
+
+        assert_eq!(body["text"]["format"]["type"], "json_schema");
+        assert_eq!(body["text"]["format"]["name"], "reply");
+        assert_eq!(body["text"]["format"]["strict"], true);
     }
 
     #[test]
@@ -276,6 +530,85 @@ mod tests {
         assert_eq!(result.id, "resp_1");
         assert_eq!(result.status.as_deref(), Some("completed"));
         assert_eq!(result.text, "hello");
+        assert!(result.function_calls.is_empty());
+    }
+
+    #[test]
+    fn parse_responses_result_extracts_function_calls() {
+        let raw = r#"{
+            "id": "resp_1",
+            "status": "completed",
+            "output": [
+                {"type": "function_call", "call_id": "call_1", "name": "current_time", "arguments": "{\"timezone\":\"Asia/Tokyo\"}"},
+                {"type": "function_call", "name": "broken"}
+            ]
+        }"#;
+
+        let result = parse_responses_result(raw).unwrap();
+
+        assert_eq!(
+            result.function_calls,
+            vec![FunctionCall {
+                call_id: "call_1".into(),
+                name: "current_time".into(),
+                arguments: r#"{"timezone":"Asia/Tokyo"}"#.into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_responses_result_collects_unique_citations() {
+        let raw = json!({
+            "id": "resp_1",
+            "status": "completed",
+            "output": [
+                {"type": "web_search_call", "status": "completed"},
+                {"type": "message", "content": [{
+                    "type": "output_text",
+                    "text": "Rust 1.91 が出ました",
+                    "annotations": [
+                        {"type": "url_citation", "url": "https://blog.rust-lang.org/r?utm_source=openai", "title": "Rust Blog"},
+                        {"type": "url_citation", "url": "https://blog.rust-lang.org/r"},
+                        {"type": "url_citation", "url": "https://example.com/a?id=1&utm_source=openai#top"}
+                    ]
+                }]}
+            ]
+        })
+        .to_string();
+
+        let res = parse_responses_result(&raw).unwrap();
+
+        assert_eq!(
+            res.citations,
+            vec![
+                Citation {
+                    url: "https://blog.rust-lang.org/r".into(),
+                    title: Some("Rust Blog".into())
+                },
+                Citation { url: "https://example.com/a?id=1#top".into(), title: None },
+            ]
+        );
+    }
+
+    #[test]
+    fn follow_up_sends_function_outputs_with_previous_response() {
+        let args = CallResponsesArgs::new(
+            "gpt-test",
+            "gpt-test",
+            "api-key",
+            vec![message("system", "be concise"), message("user", "what time is it?")],
+        )
+        .follow_up("resp_1", vec![FunctionCallOutput::new("call_1", "12:00".into())]);
+
+        let (_, _, req) = build_responses_request(args, true);
+        let body = serde_json::to_value(&req).unwrap();
+
+        assert_eq!(body["instructions"], "be concise");
+        assert_eq!(body["previous_response_id"], "resp_1");
+        assert_eq!(
+            body["input"],
+            serde_json::json!([{"type": "function_call_output", "call_id": "call_1", "output": "12:00"}])
+        );
     }
 
     #[tokio::test]
@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+pub(super) fn now_in(timezone: Tz) -> DateTime<Tz> {
+    Utc::now().with_timezone(&timezone)
+}
+
+/// `CurrentTime(JST): 2025-01-01T09:00:00+09:00` のような system 指示
+pub(super) fn current_time_instruction(now: DateTime<Tz>) -> String {
+    format!("CurrentTime({}): {}", now.format("%Z"), now.to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn current_time_instruction_uses_zone_abbreviation() {
+        let tokyo = chrono_tz::Asia::Tokyo.with_ymd_and_hms(2025, 1, 2, 9, 30, 0).unwrap();
+        let berlin = chrono_tz::Europe::Berlin.with_ymd_and_hms(2025, 7, 2, 9, 30, 0).unwrap();
+
+        assert_eq!(current_time_instruction(tokyo), "CurrentTime(JST): 2025-01-02T09:30:00+09:00");
+        assert_eq!(
+            current_time_instruction(berlin),
+            "CurrentTime(CEST): 2025-07-02T09:30:00+02:00"
+        );
+    }
+}
@@ -0,0 +1,454 @@
+//! 関数ツール（function calling）
+//!
+//! `FUNCTION_TOOLS` で選んだ組み込みの関数をモデルに渡し、応答に `function_call` があれば
+//! ローカルで実行して `function_call_output` を返す。これを関数を呼ばない応答が返るまで
+//! （多くても `FUNCTION_TOOL_MAX_ROUNDS` 回）繰り返す。
+
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use chrono_tz::Tz;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::config::{BotConfig, FunctionTool};
+use crate::conversation_store::ConversationStore;
+use crate::mastodon::{
+    Status, fetch_account_statuses, lookup_account, resolve_status_url, verify_credentials,
+};
+use crate::openai_api::stream::{CallResponsesArgs, call_responses};
+use crate::openai_api::types::{FunctionCall, FunctionCallOutput, ResponsesResult, Tool};
+use crate::opt_out::is_opted_out;
+use crate::util::strip_html;
+
+/// `search_own_posts` で見る自分の投稿の数と、返す件数
+const OWN_POSTS_SCAN: usize = 40;
+const OWN_POSTS_RESULTS: usize = 5;
+
+/// モデルに渡す関数の定義
+pub(super) fn function_tool_definitions(tools: &[FunctionTool]) -> Vec<Tool> {
+    tools.iter().map(|&tool| definition(tool)).collect()
+}
+
+fn definition(tool: FunctionTool) -> Tool {
+    let (description, parameters) = match tool {
+        FunctionTool::CurrentTime => (
+            "指定したタイムゾーン（IANA 名）の現在の日時を返す。null なら bot のタイムゾーン",
+            json!({
+                "type": "object",
+                "properties": {
+                    "timezone": { "type": ["string", "null"], "description": "例: Asia/Tokyo" }
+                },
+                "required": ["timezone"],
+                "additionalProperties": false
+            }),
+        ),
+        FunctionTool::LookupAccount => (
+            "Mastodon のアカウントのプロフィール（自己紹介・補足情報）を返す",
+            json!({
+                "type": "object",
+                "properties": {
+                    "acct": { "type": "string", "description": "例: alice または alice@example.com" }
+                },
+                "required": ["acct"],
+                "additionalProperties": false
+            }),
+        ),
+        FunctionTool::FetchStatus => (
+            "Mastodon の投稿の URL から、その投稿の本文と投稿者を返す",
+            json!({
+                "type": "object",
+                "properties": {
+                    "url": { "type": "string", "description": "投稿の URL" }
+                },
+                "required": ["url"],
+                "additionalProperties": false
+            }),
+        ),
+        FunctionTool::SearchOwnPosts => (
+            "bot 自身の最近の投稿から、語句を含むものを新しい順に返す",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "探す語句" }
+                },
+                "required": ["query"],
+                "additionalProperties": false
+            }),
+        ),
+    };
+
+    Tool::Function {
+        name: tool.name().to_string(),
+        description: description.to_string(),
+        parameters,
+        strict: true,
+    }
+}
+
+/// `call_responses` に、関数ツールを実行して結果を返すループを足したもの
+pub(super) async fn call_responses_with_tools(
+    client: &Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    tools: &[FunctionTool],
+    mut args: CallResponsesArgs<'_>,
+    is_reply: bool,
+) -> Result<ResponsesResult> {
+    let mut rounds = 0;
+    loop {
+        if rounds >= cfg.function_tool_max_rounds {
+            // 上限に達したら、関数を呼ばずに答えてもらう
+            args = args.tool_choice("none");
+        }
+
+        let res = call_responses(client, args.clone(), is_reply).await?;
+        if res.function_calls.is_empty() || rounds >= cfg.function_tool_max_rounds {
+            return Ok(res);
+        }
+
+        let mut outputs = Vec::new();
+        for call in &res.function_calls {
+            println!("  -> function call {}({})", call.name, call.arguments);
+            let output = run_function_call(client, cfg, conv_store, tools, call).await;
+            outputs.push(FunctionCallOutput::new(&call.call_id, output));
+        }
+
+        args = args.follow_up(&res.id, outputs);
+        rounds += 1;
+    }
+}
+
+/// 関数を実行して、モデルに返す JSON 文字列を作る。失敗も `{"error": ...}` として返す
+async fn run_function_call(
+    client: &Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    tools: &[FunctionTool],
+    call: &FunctionCall,
+) -> String {
+    let result = match tools.iter().find(|tool| tool.name() == call.name) {
+        Some(&tool) => execute(client, cfg, conv_store, tool, &call.arguments).await,
+        None => Err(anyhow!("unknown function: {}", call.name)),
+    };
+
+    match result {
+        Ok(value) => value.to_string(),
+        Err(e) => {
+            eprintln!("[tools] {} failed: {:?}", call.name, e);
+            json!({ "error": e.to_string() }).to_string()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CurrentTimeArgs {
+    timezone: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LookupAccountArgs {
+    acct: String,
+}
+
+#[derive(Deserialize)]
+struct FetchStatusArgs {
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct SearchOwnPostsArgs {
+    query: String,
+}
+
+async fn execute(
+    client: &Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    tool: FunctionTool,
+    arguments: &str,
+) -> Result<Value> {
+    let base = &cfg.mastodon_base;
+    let token = &cfg.mastodon_access_token;
+
+    match tool {
+        FunctionTool::CurrentTime => {
+            let args: CurrentTimeArgs = serde_json::from_str(arguments)?;
+            current_time(args.timezone.as_deref(), cfg.timezone)
+        }
+        FunctionTool::LookupAccount => {
+            let args: LookupAccountArgs = serde_json::from_str(arguments)?;
+            let acct = args.acct.trim().trim_start_matches('@');
+            let account = lookup_account(client, base, token, acct).await?;
+            // オプトアウトした人（`/optout`・拒否リスト・`#nobot`）の情報は渡さない
+            if is_opted_out(client, cfg, conv_store, &account).await {
+                return Err(anyhow!("@{} does not want to interact with bots", account.acct));
+            }
+
+            let fields: Vec<Value> = account
+                .fields
+                .iter()
+                .map(|f| json!({ "name": strip_html(&f.name), "value": strip_html(&f.value) }))
+                .collect();
+            Ok(json!({
+                "acct": account.acct,
+                "bot": account.bot.unwrap_or(false),
+                "note": strip_html(&account.note),
+                "fields": fields,
+            }))
+        }
+        FunctionTool::FetchStatus => {
+            let args: FetchStatusArgs = serde_json::from_str(arguments)?;
+            let status = resolve_status_url(client, base, token, args.url.trim())
+                .await?
+                .ok_or_else(|| anyhow!("status not found: {}", args.url))?;
+            // フォロワー限定や DM を公開の返信で引用しないように
+            if !is_public(&status) {
+                return Err(anyhow!("status is not public: {}", args.url));
+            }
+            if is_opted_out(client, cfg, conv_store, &status.account).await {
+                return Err(anyhow!(
+                    "@{} does not want to interact with bots",
+                    status.account.acct
+                ));
+            }
+
+            Ok(status_json(&status))
+        }
+        FunctionTool::SearchOwnPosts => {
+            let args: SearchOwnPostsArgs = serde_json::from_str(arguments)?;
+            let own = verify_credentials(client, base, token).await?;
+            let statuses =
+                fetch_account_statuses(client, base, token, &own.id, OWN_POSTS_SCAN).await?;
+
+            Ok(json!({ "posts": search_posts(&statuses, &args.query) }))
+        }
+    }
+}
+
+fn current_time(timezone: Option<&str>, default: Tz) -> Result<Value> {
+    let tz = match timezone.map(str::trim).filter(|tz| !tz.is_empty()) {
+        Some(name) => name.parse::<Tz>().map_err(|_| anyhow!("unknown timezone: {name}"))?,
+        None => default,
+    };
+    let now = Utc::now().with_timezone(&tz);
+
+    Ok(json!({
+        "timezone": tz.name(),
+        "now": now.to_rfc3339(),
+        "weekday": now.format("%A").to_string(),
+    }))
+}
+
+fn status_json(status: &Status) -> Value {
+    json!({
+        "acct": status.account.acct,
+        "created_at": status.created_at,
+        "content_warning": status.spoiler_text,
+        "content": strip_html(&status.content),
+    })
+}
+
+/// 公開・未収載の投稿か（フォロワー限定・DM なら false）
+fn is_public(status: &Status) -> bool {
+    matches!(status.visibility.as_str(), "public" | "unlisted")
+}
+
+/// 自分の投稿から `query` を含むものを探す（フォロワー限定・DM の返信は除く）
+fn search_posts(statuses: &[Status], query: &str) -> Vec<Value> {
+    let query = query.trim().to_lowercase();
+    statuses
+        .iter()
+        .filter(|s| is_public(s))
+        .filter(|s| strip_html(&s.content).to_lowercase().contains(&query))
+        .take(OWN_POSTS_RESULTS)
+        .map(|s| json!({ "created_at": s.created_at, "content": strip_html(&s.content) }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opt_out::SOURCE_ADMIN;
+    use crate::test_support::{MockHttpServer, test_config, test_status};
+
+    fn store() -> ConversationStore {
+        ConversationStore::new(":memory:").unwrap()
+    }
+
+    fn status(content: &str) -> Status {
+        Status {
+            content: content.into(),
+            created_at: "2026-01-01T00:00:00Z".into(),
+            ..test_status("1", "bot")
+        }
+    }
+
+    fn call(name: &str, arguments: &str) -> FunctionCall {
+        FunctionCall { call_id: "call_1".into(), name: name.into(), arguments: arguments.into() }
+    }
+
+    #[test]
+    fn definitions_use_strict_schemas() {
+        let tools =
+            function_tool_definitions(&[FunctionTool::CurrentTime, FunctionTool::FetchStatus]);
+        let json = serde_json::to_value(&tools).unwrap();
+
+        assert_eq!(json[0]["type"], "function");
+        assert_eq!(json[0]["name"], "current_time");
+        assert_eq!(json[0]["strict"], true);
+        assert_eq!(json[1]["parameters"]["required"], json!(["url"]));
+    }
+
+    #[test]
+    fn current_time_uses_requested_or_default_timezone() {
+        let tokyo = current_time(Some("Asia/Tokyo"), chrono_tz::UTC).unwrap();
+        assert_eq!(tokyo["timezone"], "Asia/Tokyo");
+        assert!(tokyo["now"].as_str().unwrap().ends_with("+09:00"));
+
+        let default = current_time(None, chrono_tz::Asia::Tokyo).unwrap();
+        assert_eq!(default["timezone"], "Asia/Tokyo");
+
+        assert!(current_time(Some("Mars/Olympus"), chrono_tz::UTC).is_err());
+    }
+
+    #[test]
+    fn searches_posts_case_insensitively() {
+        let statuses =
+            vec![status("<p>Rust 1.80</p>"), status("<p>おやつ</p>"), status("<p>rust night</p>")];
+
+        let found = search_posts(&statuses, "RUST");
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0]["content"], "Rust 1.80");
+    }
+
+    #[test]
+    fn search_skips_private_and_direct_posts() {
+        let statuses = vec![
+            Status { visibility: "private".into(), ..status("<p>rust secret</p>") },
+            Status { visibility: "direct".into(), ..status("<p>rust dm</p>") },
+            Status { visibility: "unlisted".into(), ..status("<p>rust quiet</p>") },
+            status("<p>rust public</p>"),
+        ];
+
+        let found = search_posts(&statuses, "rust");
+
+        let contents: Vec<&str> = found.iter().map(|p| p["content"].as_str().unwrap()).collect();
+        assert_eq!(contents, vec!["rust quiet", "rust public"]);
+    }
+
+    #[tokio::test]
+    async fn fetch_status_refuses_non_public_statuses() {
+        let server = MockHttpServer::respond(
+            "200 OK",
+            r#"{"statuses":[{"id":"9","content":"<p>secret</p>","visibility":"private","in_reply_to_id":null,"account":{"acct":"alice"}}]}"#,
+        );
+        let mut cfg = test_config();
+        cfg.mastodon_base = server.base_url().to_string();
+
+        let output = run_function_call(
+            &Client::new(),
+            &cfg,
+            &store(),
+            &[FunctionTool::FetchStatus],
+            &call("fetch_status", r#"{"url":"https://mastodon.example/@alice/9"}"#),
+        )
+        .await;
+
+        assert!(output.contains("status is not public"), "{output}");
+        assert!(!output.contains("secret"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn unknown_or_disabled_functions_return_errors() {
+        let cfg = test_config();
+        let client = Client::new();
+        let store = store();
+
+        let output = run_function_call(
+            &client,
+            &cfg,
+            &store,
+            &[FunctionTool::CurrentTime],
+            &call("fetch_status", "{}"),
+        )
+        .await;
+        assert!(output.contains("unknown function: fetch_status"), "{output}");
+
+        let output = run_function_call(
+            &client,
+            &cfg,
+            &store,
+            &[FunctionTool::CurrentTime],
+            &call("current_time", "{"),
+        )
+        .await;
+        assert!(output.starts_with(r#"{"error":"#), "{output}");
+    }
+
+    #[tokio::test]
+    async fn lookup_account_hides_nobot_profiles() {
+        let server =
+            MockHttpServer::respond("200 OK", r#"{"acct":"alice","note":"<p>#nobot</p>"}"#);
+        let mut cfg = test_config();
+        cfg.mastodon_base = server.base_url().to_string();
+
+        let output = run_function_call(
+            &Client::new(),
+            &cfg,
+            &store(),
+            &[FunctionTool::LookupAccount],
+            &call("lookup_account", r#"{"acct":"@alice"}"#),
+        )
+        .await;
+
+        assert!(output.contains("does not want to interact with bots"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn lookup_account_hides_denied_accounts() {
+        let server = MockHttpServer::respond("200 OK", r#"{"acct":"alice","note":"<p>hi</p>"}"#);
+        let mut cfg = test_config();
+        cfg.mastodon_base = server.base_url().to_string();
+        let store = store();
+        store.add_opt_out("alice", SOURCE_ADMIN).await.unwrap();
+
+        let output = run_function_call(
+            &Client::new(),
+            &cfg,
+            &store,
+            &[FunctionTool::LookupAccount],
+            &call("lookup_account", r#"{"acct":"alice"}"#),
+        )
+        .await;
+
+        assert!(output.contains("does not want to interact with bots"), "{output}");
+        assert!(!output.contains("note"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn returns_answer_without_function_calls() {
+        let server = MockHttpServer::respond(
+            "200 OK",
+            r#"{"id":"resp_1","status":"completed","output":[{"type":"output_text","text":"hi"}]}"#,
+        );
+        let api_url = server.url("/v1/responses");
+        let cfg = test_config();
+        let args =
+            CallResponsesArgs::new("gpt-test", "gpt-test", "api-key", vec![]).api_url(&api_url);
+
+        let res = call_responses_with_tools(
+            &Client::new(),
+            &cfg,
+            &store(),
+            &[FunctionTool::CurrentTime],
+            args,
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(res.text, "hi");
+    }
+}
@@ -14,12 +14,91 @@ pub enum Tool {
         #[serde(skip_serializing_if = "Option::is_none")]
         search_context_size: Option<String>, // "low" | "medium" | "high"
     },
+    /// ローカルで実行する関数（`parameters` は JSON スキーマ）
+    #[serde(rename = "function")]
+    Function { name: String, description: String, parameters: serde_json::Value, strict: bool },
+}
+
+/// `input` の要素
+#[derive(Debug, Serialize, Clone)]
+#[serde(untagged)]
+pub enum InputItem {
+    Message(ChatMessage),
+    /// 画像を含むメッセージ
+    Parts {
+        role: String,
+        content: Vec<ContentPart>,
+    },
+    FunctionCallOutput(FunctionCallOutput),
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum ContentPart {
+    #[serde(rename = "input_text")]
+    Text { text: String },
+    #[serde(rename = "input_image")]
+    Image { image_url: String },
+}
+
+/// 関数ツールの実行結果
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct FunctionCallOutput {
+    #[serde(rename = "type")]
+    pub kind: &'static str, // "function_call_output"
+    pub call_id: String,
+    pub output: String,
+}
+
+impl FunctionCallOutput {
+    pub fn new(call_id: &str, output: String) -> Self {
+        Self { kind: "function_call_output", call_id: call_id.to_string(), output }
+    }
+}
+
+/// モデルが呼び出した関数（`arguments` は JSON 文字列）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionCall {
+    pub call_id: String,
+    pub name: String,
+    pub arguments: String,
+}
+
+/// Web 検索の出典（`url_citation` の注釈）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Citation {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+/// 出力の形式（`text.format`）
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "type")]
+pub enum TextFormat {
+    /// JSON スキーマに沿った構造化出力
+    #[serde(rename = "json_schema")]
+    JsonSchema { name: String, schema: serde_json::Value, strict: bool },
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TextConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<TextFormat>,
+    /// 出力の詳しさ（low / medium / high）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verbosity: Option<String>,
+}
+
+/// 推論モデルの設定
+#[derive(Debug, Serialize, Clone)]
+pub struct ReasoningConfig {
+    pub effort: String,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ResponsesRequest {
     pub model: String,
-    pub input: Vec<ChatMessage>,
+    pub input: Vec<InputItem>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub instructions: Option<String>,
@@ -35,6 +114,16 @@ pub struct ResponsesRequest {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
+
+    /// `"none"` で関数ツールを呼ばせない
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<TextConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<ReasoningConfig>,
 }
 
 #[derive(Debug, Clone)]
@@ -42,4 +131,10 @@ pub struct ResponsesResult {
     pub id: String,
     pub text: String,
     pub status: Option<String>,
+    /// 実行を頼まれた関数ツール
+    pub function_calls: Vec<FunctionCall>,
+    /// Web 検索の出典（URL の重複は除く）
+    pub citations: Vec<Citation>,
+    /// 呼び出したモデル（設定に書いた名前）
+    pub model: String,
 }
@@ -0,0 +1,189 @@
+//! オプトアウトの判定
+//!
+//! 次のどれかに当てはまるアカウントには返信せず、会話の文脈にも含めない。
+//!
+//! - `/optout` コマンドを送った（`opt_outs` の `command`）
+//! - 管理者が拒否リストに入れた（`opt_outs` の `admin`、`mast_gpt_bot deny` で管理）
+//! - プロフィールや補足情報に `NOBOT_TAGS`（既定: `#nobot` / `#noai`）がある
+//!   （アカウント API で確かめ、`account_profiles` に `NOBOT_CACHE_SECS` の間キャッシュ）
+
+use chrono::Utc;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use crate::config::BotConfig;
+use crate::conversation_store::ConversationStore;
+use crate::mastodon::{Account, Status, lookup_account};
+use crate::util::strip_html;
+
+/// `/optout` で記録したもの
+pub const SOURCE_COMMAND: &str = "command";
+/// 管理者の拒否リスト
+pub const SOURCE_ADMIN: &str = "admin";
+
+/// 記録に使う acct（先頭の `@` を外して小文字に）
+pub fn normalize_acct(acct: &str) -> String {
+    acct.trim().trim_start_matches('@').to_lowercase()
+}
+
+/// オプトアウトしているか。確かめられなかったときは返信する側に倒す
+pub async fn is_opted_out(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    account: &Account,
+) -> bool {
+    let acct = normalize_acct(&account.acct);
+    match conv_store.opt_out_sources(&acct).await {
+        Ok(sources) if !sources.is_empty() => return true,
+        Ok(_) => {}
+        Err(e) => eprintln!("[opt-out] Failed to load opt-outs for @{acct}: {:?}", e),
+    }
+
+    has_nobot_profile(client, cfg, conv_store, account).await
+}
+
+/// オプトアウトしているアカウントの投稿を取り除く
+pub async fn remove_opted_out(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    statuses: &mut Vec<Status>,
+) {
+    let mut opted_out: HashMap<String, bool> = HashMap::new();
+    for status in statuses.iter() {
+        let acct = normalize_acct(&status.account.acct);
+        if let Entry::Vacant(entry) = opted_out.entry(acct) {
+            entry.insert(is_opted_out(client, cfg, conv_store, &status.account).await);
+        }
+    }
+
+    statuses.retain(|s| !opted_out[&normalize_acct(&s.account.acct)]);
+}
+
+async fn has_nobot_profile(
+    client: &reqwest::Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    account: &Account,
+) -> bool {
+    if cfg.nobot_tags.is_empty() {
+        return false;
+    }
+
+    let acct = normalize_acct(&account.acct);
+    let checked_after = Utc::now().timestamp() - cfg.nobot_cache_ttl.as_secs() as i64;
+    match conv_store.get_cached_nobot(&acct, checked_after).await {
+        Ok(Some(nobot)) => return nobot,
+        Ok(None) => {}
+        Err(e) => eprintln!("[opt-out] Failed to load profile cache for @{acct}: {:?}", e),
+    }
+
+    let nobot =
+        match lookup_account(client, &cfg.mastodon_base, &cfg.mastodon_access_token, &account.acct)
+            .await
+        {
+            Ok(fetched) => has_nobot_tag(&fetched, &cfg.nobot_tags),
+            Err(e) => {
+                // 取れなければ通知に含まれていたプロフィールで判断する（キャッシュはしない）
+                eprintln!("[opt-out] Failed to look up @{acct}: {:?}", e);
+                return has_nobot_tag(account, &cfg.nobot_tags);
+            }
+        };
+
+    if let Err(e) = conv_store.cache_nobot(&acct, nobot).await {
+        eprintln!("[opt-out] Failed to cache profile for @{acct}: {:?}", e);
+    }
+    nobot
+}
+
+/// プロフィール本文か補足情報にタグがあるか（`#nobots` のような長いタグには一致しない）
+pub fn has_nobot_tag(account: &Account, tags: &[String]) -> bool {
+    find_nobot_tag(account, tags).is_some()
+}
+
+/// プロフィールにある最初のタグ
+pub fn find_nobot_tag<'a>(account: &Account, tags: &'a [String]) -> Option<&'a str> {
+    let mut texts = vec![strip_html(&account.note)];
+    for field in &account.fields {
+        texts.push(strip_html(&field.name));
+        texts.push(strip_html(&field.value));
+    }
+    let texts: Vec<String> = texts.iter().map(|text| text.to_lowercase()).collect();
+
+    tags.iter().find(|tag| texts.iter().any(|text| contains_tag(text, tag))).map(String::as_str)
+}
+
+fn contains_tag(text: &str, tag: &str) -> bool {
+    text.match_indices(tag).any(|(i, _)| {
+        text[i + tag.len()..].chars().next().is_none_or(|c| !(c.is_alphanumeric() || c == '_'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mastodon::AccountField;
+    use crate::test_support::{MockHttpServer, closed_local_url, test_config, test_status};
+
+    fn tags() -> Vec<String> {
+        vec!["#nobot".into(), "#noai".into()]
+    }
+
+    fn account(acct: &str, note: &str) -> Account {
+        Account { acct: acct.into(), note: note.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn finds_tags_in_note_and_fields() {
+        let linked = r##"<p>hi <a href="https://m.example/tags/NoBot" class="mention hashtag">#<span>NoBot</span></a></p>"##;
+        assert!(has_nobot_tag(&account("a", linked), &tags()));
+        assert!(!has_nobot_tag(&account("a", "<p>#nobots welcome</p>"), &tags()));
+        assert!(!has_nobot_tag(&account("a", "hello"), &tags()));
+
+        let mut with_field = account("a", "");
+        with_field.fields.push(AccountField { name: "AI".into(), value: "#noai".into() });
+        assert!(has_nobot_tag(&with_field, &tags()));
+        assert_eq!(find_nobot_tag(&with_field, &tags()), Some("#noai"));
+    }
+
+    #[test]
+    fn normalizes_acct() {
+        assert_eq!(normalize_acct(" @Alice@Example.com"), "alice@example.com");
+    }
+
+    #[tokio::test]
+    async fn caches_profile_lookups() {
+        let server =
+            MockHttpServer::respond("200 OK", r#"{"acct":"alice","note":"<p>#nobot</p>"}"#);
+        let mut cfg = test_config();
+        cfg.mastodon_base = server.base_url().to_string();
+        let store = ConversationStore::new(":memory:").unwrap();
+        let client = reqwest::Client::new();
+
+        assert!(is_opted_out(&client, &cfg, &store, &account("alice", "")).await);
+
+        // 2 回目は API を呼ばずにキャッシュを使う
+        cfg.mastodon_base = closed_local_url("");
+        assert!(is_opted_out(&client, &cfg, &store, &account("Alice", "")).await);
+    }
+
+    #[tokio::test]
+    async fn removes_statuses_from_opted_out_accounts() {
+        let mut cfg = test_config();
+        cfg.mastodon_base = closed_local_url("");
+        let store = ConversationStore::new(":memory:").unwrap();
+        store.add_opt_out("bob", SOURCE_ADMIN).await.unwrap();
+        let status = |id: &str, acct: &str, note: &str| Status {
+            account: account(acct, note),
+            ..test_status(id, acct)
+        };
+        let mut statuses =
+            vec![status("1", "alice", ""), status("2", "bob", ""), status("3", "carol", "#noai")];
+
+        remove_opted_out(&reqwest::Client::new(), &cfg, &store, &mut statuses).await;
+
+        let ids: Vec<&str> = statuses.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, vec!["1"]);
+    }
+}
@@ -0,0 +1,264 @@
+//! 投稿の出口
+//!
+//! 返信・通常投稿はここを通す。`DRY_RUN=true` のときは Mastodon に送らず、
+//! 送るはずだった本文をログに出して SQLite の `dry_run_posts` に記録する。
+
+use anyhow::Result;
+use reqwest::Client;
+
+use crate::config::{BotConfig, CwMode};
+use crate::conversation_store::ConversationStore;
+use crate::mastodon::{
+    ReplyWarning, Status, post_direct_status, post_reply, post_status, post_status_with_link,
+    reply_status_text, status_text_for_post,
+};
+
+/// メンションへの返信（CW は `REPLY_CW_MODE` に従って元の投稿から引き継ぐ）
+pub async fn publish_reply(
+    client: &Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    reply_to: &Status,
+    reply_to_acct: &str,
+    body: &str,
+) -> Result<()> {
+    publish_reply_with_warning(client, cfg, conv_store, reply_to, reply_to_acct, body, None).await
+}
+
+/// メンションへの返信。元の投稿から引き継ぐ CW がなければ `model_warning` を付ける
+pub async fn publish_reply_with_warning(
+    client: &Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    reply_to: &Status,
+    reply_to_acct: &str,
+    body: &str,
+    model_warning: Option<&str>,
+) -> Result<()> {
+    let warning = reply_warning(cfg.reply_cw_mode, reply_to, model_warning);
+
+    if cfg.dry_run {
+        let text = reply_status_text(reply_to_acct, body);
+        let text = match warning.spoiler_text.as_deref() {
+            Some(spoiler) => format!("[CW: {spoiler}] {text}"),
+            None => text,
+        };
+        return record_dry_run(
+            conv_store,
+            "reply",
+            Some(&reply_to.id),
+            &reply_to.visibility,
+            &text,
+        )
+        .await;
+    }
+
+    post_reply(
+        client,
+        &cfg.mastodon_base,
+        &cfg.mastodon_access_token,
+        reply_to,
+        reply_to_acct,
+        body,
+        &warning,
+    )
+    .await
+}
+
+/// 返信の本文（宛先込み）に使える文字数。Mastodon は CW も文字数に数える
+pub fn reply_char_budget(cfg: &BotConfig, reply_to: &Status, model_warning: Option<&str>) -> usize {
+    let warning = reply_warning(cfg.reply_cw_mode, reply_to, model_warning);
+    let cw_chars = warning.spoiler_text.as_deref().map_or(0, |s| s.chars().count());
+    cfg.mastodon_char_limit.saturating_sub(cw_chars)
+}
+
+/// 返信に付ける CW を決める
+fn reply_warning(mode: CwMode, reply_to: &Status, model_warning: Option<&str>) -> ReplyWarning {
+    let original = reply_to.spoiler_text.trim();
+    let inherited = match mode {
+        _ if original.is_empty() => None,
+        CwMode::Inherit => Some(original.to_string()),
+        CwMode::Prefix if original.to_lowercase().starts_with("re:") => Some(original.to_string()),
+        CwMode::Prefix => Some(format!("re: {original}")),
+        CwMode::Never => None,
+    };
+    let spoiler_text = inherited
+        .or_else(|| model_warning.map(str::trim).filter(|w| !w.is_empty()).map(str::to_string));
+
+    let sensitive = spoiler_text.is_some() || (mode != CwMode::Never && reply_to.sensitive);
+    ReplyWarning { spoiler_text, sensitive }
+}
+
+/// 通常投稿（`link` があればドメイン表記にせず末尾に付ける）
+pub async fn publish_status(
+    client: &Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    text: &str,
+    link: Option<&str>,
+) -> Result<()> {
+    if cfg.dry_run {
+        let text = status_text_for_post(cfg, text, link)?;
+        return record_dry_run(conv_store, "status", None, &cfg.visibility.to_string(), &text)
+            .await;
+    }
+
+    match link {
+        Some(link) => post_status_with_link(client, cfg, text, link).await,
+        None => post_status(client, cfg, text).await,
+    }
+}
+
+/// 宛先だけに見える（direct）投稿。送った status id を返す（ドライランなら None）
+pub async fn publish_direct(
+    client: &Client,
+    cfg: &BotConfig,
+    conv_store: &ConversationStore,
+    text: &str,
+) -> Result<Option<String>> {
+    if cfg.dry_run {
+        record_dry_run(conv_store, "direct", None, "direct", text.trim()).await?;
+        return Ok(None);
+    }
+
+    post_direct_status(client, cfg, text).await.map(Some)
+}
+
+async fn record_dry_run(
+    conv_store: &ConversationStore,
+    kind: &str,
+    in_reply_to_id: Option<&str>,
+    visibility: &str,
+    text: &str,
+) -> Result<()> {
+    println!(
+        "[dry run] would post {kind} ({visibility}{}): {text}",
+        in_reply_to_id.map(|id| format!(", in reply to {id}")).unwrap_or_default()
+    );
+
+    conv_store.record_dry_run_post(kind, in_reply_to_id, visibility, text).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{closed_local_url, test_config, test_status};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_db_path(name: &str) -> std::path::PathBuf {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        std::env::temp_dir().join(format!("mast_gpt_bot_{name}_{nanos}.sqlite"))
+    }
+
+    /// (kind, in_reply_to_id, visibility, text) を記録順に
+    fn recorded_posts(path: &std::path::Path) -> Vec<(String, Option<String>, String, String)> {
+        let conn = rusqlite::Connection::open(path).unwrap();
+        let mut stmt = conn
+            .prepare("SELECT kind, in_reply_to_id, visibility, text FROM dry_run_posts ORDER BY id")
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    fn status() -> Status {
+        Status { visibility: "private".into(), ..test_status("status-1", "alice") }
+    }
+
+    #[tokio::test]
+    async fn dry_run_records_posts_without_sending() {
+        let mut cfg = test_config();
+        cfg.dry_run = true;
+        // 送信しようとすれば接続エラーになる
+        cfg.mastodon_base = closed_local_url("");
+        let path = temp_db_path("dry_run");
+        let store = ConversationStore::new(&path).unwrap();
+        let client = Client::new();
+
+        publish_reply(&client, &cfg, &store, &status(), "alice", "hello").await.unwrap();
+        publish_reply_with_warning(&client, &cfg, &store, &status(), "alice", "hm", Some("病気"))
+            .await
+            .unwrap();
+        publish_status(&client, &cfg, &store, "news", Some("https://blog.example/a"))
+            .await
+            .unwrap();
+        let review_id = publish_direct(&client, &cfg, &store, "@mod check").await.unwrap();
+
+        assert_eq!(
+            recorded_posts(&path),
+            vec![
+                ("reply".into(), Some("status-1".into()), "private".into(), "@alice hello".into()),
+                (
+                    "reply".into(),
+                    Some("status-1".into()),
+                    "private".into(),
+                    "[CW: 病気] @alice hm".into()
+                ),
+                ("status".into(), None, "unlisted".into(), "news\nhttps://blog.example/a".into()),
+                ("direct".into(), None, "direct".into(), "@mod check".into()),
+            ]
+        );
+        assert_eq!(review_id, None);
+        drop(store);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn reply_warning_follows_cw_mode() {
+        let mut cw = status();
+        cw.spoiler_text = "ネタバレ".into();
+        cw.sensitive = true;
+        let spoiler = |mode, reply_to: &Status, model| {
+            reply_warning(mode, reply_to, model).spoiler_text.unwrap_or_default()
+        };
+
+        assert_eq!(spoiler(CwMode::Inherit, &cw, None), "ネタバレ");
+        assert_eq!(spoiler(CwMode::Prefix, &cw, None), "re: ネタバレ");
+        assert_eq!(reply_warning(CwMode::Never, &cw, None), ReplyWarning::default());
+
+        cw.spoiler_text = "Re: ネタバレ".into();
+        assert_eq!(spoiler(CwMode::Prefix, &cw, None), "Re: ネタバレ");
+    }
+
+    #[test]
+    fn model_warning_is_used_when_nothing_is_inherited() {
+        let mut cw = status();
+        cw.spoiler_text = "ネタバレ".into();
+
+        assert_eq!(
+            reply_warning(CwMode::Inherit, &status(), Some(" 病気の話 ")),
+            ReplyWarning { spoiler_text: Some("病気の話".into()), sensitive: true }
+        );
+        assert_eq!(
+            reply_warning(CwMode::Inherit, &cw, Some("病気の話")).spoiler_text.as_deref(),
+            Some("ネタバレ")
+        );
+        assert_eq!(reply_warning(CwMode::Inherit, &status(), Some("  ")), ReplyWarning::default());
+    }
+
+    #[test]
+    fn reply_budget_leaves_room_for_the_cw() {
+        let mut cfg = test_config();
+        cfg.reply_cw_mode = CwMode::Prefix;
+        let mut cw = status();
+        cw.spoiler_text = "ネタバレ".into();
+
+        assert_eq!(reply_char_budget(&cfg, &status(), None), 500);
+        assert_eq!(reply_char_budget(&cfg, &status(), Some("病気の話")), 496);
+        assert_eq!(reply_char_budget(&cfg, &cw, None), 492);
+    }
+
+    #[tokio::test]
+    async fn live_mode_sends_to_mastodon() {
+        let mut cfg = test_config();
+        cfg.mastodon_base = closed_local_url("");
+        let path = temp_db_path("live");
+        let store = ConversationStore::new(&path).unwrap();
+
+        assert!(publish_status(&Client::new(), &cfg, &store, "news", None).await.is_err());
+        assert!(recorded_posts(&path).is_empty());
+        drop(store);
+        let _ = std::fs::remove_file(path);
+    }
+}
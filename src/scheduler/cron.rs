@@ -0,0 +1,177 @@
+//! 5 フィールド（分 時 日 月 曜日）の cron 式
+
+use anyhow::{Result, anyhow, bail};
+use chrono::{Datelike, NaiveDate, NaiveTime};
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronSpec {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days_of_month: Vec<u32>,
+    months: Vec<u32>,
+    /// 0 = 日曜
+    days_of_week: Vec<u32>,
+    /// `*` 以外が指定されたか（日と曜日が両方指定されたときはどちらかに一致すればよい）
+    dom_restricted: bool,
+    dow_restricted: bool,
+}
+
+impl CronSpec {
+    /// その日に実行する時刻（昇順）
+    pub fn times_on(&self, date: NaiveDate) -> Vec<NaiveTime> {
+        if !self.matches_date(date) {
+            return Vec::new();
+        }
+
+        self.hours
+            .iter()
+            .flat_map(|h| {
+                self.minutes.iter().filter_map(move |m| NaiveTime::from_hms_opt(*h, *m, 0))
+            })
+            .collect()
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if !self.months.contains(&date.month()) {
+            return false;
+        }
+
+        let dom = self.days_of_month.contains(&date.day());
+        let dow = self.days_of_week.contains(&date.weekday().num_days_from_sunday());
+        match (self.dom_restricted, self.dow_restricted) {
+            (true, true) => dom || dow,
+            _ => dom && dow,
+        }
+    }
+}
+
+impl FromStr for CronSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields.as_slice() else {
+            bail!("cron expression needs 5 fields (min hour day month weekday): {s:?}");
+        };
+
+        let mut days_of_week = parse_field(dow, 0, 7, "weekday")?;
+        // 7 も日曜として扱う
+        for d in days_of_week.iter_mut() {
+            if *d == 7 {
+                *d = 0;
+            }
+        }
+        days_of_week.sort_unstable();
+        days_of_week.dedup();
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, "minute")?,
+            hours: parse_field(hour, 0, 23, "hour")?,
+            days_of_month: parse_field(dom, 1, 31, "day of month")?,
+            months: parse_field(month, 1, 12, "month")?,
+            days_of_week,
+            dom_restricted: *dom != "*",
+            dow_restricted: *dow != "*",
+        })
+    }
+}
+
+/// `*` / `5` / `1-5` / `*/15` / `10-20/5` / それらのカンマ区切り
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<Vec<u32>> {
+    let mut values = Vec::new();
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 =
+                    step.parse().map_err(|_| anyhow!("invalid {name} step {step:?}"))?;
+                if step == 0 {
+                    bail!("{name} step must be positive");
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, min, max, name)?, parse_value(b, min, max, name)?)
+        } else {
+            let v = parse_value(range, min, max, name)?;
+            // `5/10` は 5 から最大値まで 10 刻み
+            if step > 1 { (v, max) } else { (v, v) }
+        };
+        if start > end {
+            bail!("invalid {name} range {range:?}");
+        }
+
+        values.extend((start..=end).step_by(step as usize));
+    }
+
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+fn parse_value(s: &str, min: u32, max: u32, name: &str) -> Result<u32> {
+    let v: u32 = s.parse().map_err(|_| anyhow!("invalid {name} {s:?}"))?;
+    if !(min..=max).contains(&v) {
+        bail!("{name} {v} is out of range {min}-{max}");
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn hm(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_lists_ranges_and_steps() {
+        let spec: CronSpec = "0,30 8-9 * * *".parse().unwrap();
+
+        assert_eq!(spec.times_on(date(2025, 1, 1)), vec![hm(8, 0), hm(8, 30), hm(9, 0), hm(9, 30)]);
+
+        let spec: CronSpec = "*/20 12 * * *".parse().unwrap();
+        assert_eq!(spec.times_on(date(2025, 1, 1)), vec![hm(12, 0), hm(12, 20), hm(12, 40)]);
+    }
+
+    #[test]
+    fn matches_weekdays_and_months() {
+        // 2025-01-04 は土曜、2025-01-06 は月曜
+        let weekdays: CronSpec = "0 9 * * 1-5".parse().unwrap();
+        assert!(weekdays.times_on(date(2025, 1, 4)).is_empty());
+        assert_eq!(weekdays.times_on(date(2025, 1, 6)), vec![hm(9, 0)]);
+
+        let sunday: CronSpec = "0 9 * 12 7".parse().unwrap();
+        assert_eq!(sunday.times_on(date(2025, 12, 7)), vec![hm(9, 0)]);
+        assert!(sunday.times_on(date(2025, 1, 5)).is_empty());
+    }
+
+    #[test]
+    fn day_of_month_and_weekday_are_ored_when_both_restricted() {
+        let spec: CronSpec = "0 9 1 * 1".parse().unwrap();
+
+        assert!(!spec.times_on(date(2025, 1, 1)).is_empty()); // 1日（水曜）
+        assert!(!spec.times_on(date(2025, 1, 6)).is_empty()); // 月曜
+        assert!(spec.times_on(date(2025, 1, 7)).is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert!("0 9 * *".parse::<CronSpec>().is_err());
+        assert!("60 9 * * *".parse::<CronSpec>().is_err());
+        assert!("0 9-7 * * *".parse::<CronSpec>().is_err());
+        assert!("*/0 9 * * *".parse::<CronSpec>().is_err());
+        assert!("a 9 * * *".parse::<CronSpec>().is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! 自由トゥートの実行タイミング計算
+//!
+//! - `FREE_TOOT_SCHEDULE` なし: 前回実行から `FREE_TOOT_INTERVAL_SECS` ごと
+//! - cron 式（`0 8,12,19 * * *`）または 1 日の時刻リスト（`08:00,12:30,19:00`）
+//!
+//! どちらも `BOT_TIMEZONE` の現地時刻で評価し、静かな時間帯を避け、ランダムな遅延を足す。
+
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use rand::Rng;
+use std::{str::FromStr, time::Duration};
+
+use crate::config::BotConfig;
+
+mod cron;
+
+pub use cron::CronSpec;
+
+/// 停止中に過ぎた予定時刻をこの時間以内なら取り戻す（それより古い予定は飛ばす）
+const MISSED_RUN_GRACE: TimeDelta = TimeDelta::minutes(10);
+/// 次の予定を探す日数の上限（2/29 のような予定も見つかるように数年分）
+const MAX_SEARCH_DAYS: u64 = 366 * 8;
+/// 静かな時間帯を避ける繰り返しの上限
+const MAX_QUIET_SKIPS: usize = 8;
+
+/// `FREE_TOOT_SCHEDULE`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FreeTootSchedule {
+    Cron(CronSpec),
+    /// 毎日この時刻に実行（昇順）
+    DailyTimes(Vec<NaiveTime>),
+}
+
+impl FreeTootSchedule {
+    fn times_on(&self, date: NaiveDate) -> Vec<NaiveTime> {
+        match self {
+            Self::Cron(spec) => spec.times_on(date),
+            Self::DailyTimes(times) => times.clone(),
+        }
+    }
+}
+
+impl FromStr for FreeTootSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.split_whitespace().count() == 5 {
+            return Ok(Self::Cron(s.parse()?));
+        }
+
+        let mut times = s
+            .split(',')
+            .map(|t| parse_hh_mm(t.trim()))
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| anyhow!("expected a cron expression or HH:MM list: {e}"))?;
+        times.sort_unstable();
+        times.dedup();
+
+        Ok(Self::DailyTimes(times))
+    }
+}
+
+/// `FREE_TOOT_QUIET_HOURS`（`23-7` や `22:30-06:45`）。開始を含み終了を含まない
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl QuietHours {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for QuietHours {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((start, end)) = s.split_once('-') else {
+            bail!("quiet hours must look like 23-7 or 22:30-06:45: {s:?}");
+        };
+
+        Ok(Self {
+            start: parse_hour_or_hh_mm(start.trim())?,
+            end: parse_hour_or_hh_mm(end.trim())?,
+        })
+    }
+}
+
+fn parse_hh_mm(s: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|_| anyhow!("invalid time {s:?}"))
+}
+
+fn parse_hour_or_hh_mm(s: &str) -> Result<NaiveTime> {
+    if s.contains(':') {
+        return parse_hh_mm(s);
+    }
+
+    s.parse::<u32>()
+        .ok()
+        .and_then(|h| NaiveTime::from_hms_opt(h, 0, 0))
+        .ok_or_else(|| anyhow!("invalid hour {s:?}"))
+}
+
+/// 設定から次の実行時刻を決める
+pub struct Scheduler<'a> {
+    schedule: Option<&'a FreeTootSchedule>,
+    interval: Duration,
+    quiet_hours: Option<QuietHours>,
+    jitter: Duration,
+    timezone: Tz,
+}
+
+impl<'a> Scheduler<'a> {
+    pub fn from_config(cfg: &'a BotConfig) -> Self {
+        Self {
+            schedule: cfg.free_toot_schedule.as_ref(),
+            interval: cfg.free_toot_interval,
+            quiet_hours: cfg.free_toot_quiet_hours,
+            jitter: cfg.free_toot_jitter,
+            timezone: cfg.timezone,
+        }
+    }
+
+    /// 予定時刻にランダムな遅延を足す。遅延は待ち時間だけに使い、`next_run` の起点には予定時刻を渡す
+    pub fn with_jitter(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        if self.jitter.is_zero() {
+            return at;
+        }
+
+        let jitter_secs = rand::rng().random_range(0..=self.jitter.as_secs());
+        let jittered = at + TimeDelta::seconds(jitter_secs as i64);
+        // 遅延で静かな時間帯に入るなら遅延なしにする
+        if self.is_quiet(jittered) { at } else { jittered }
+    }
+
+    /// 次の実行時刻（ランダムな遅延なし）
+    pub fn next_run(&self, last_run: Option<DateTime<Utc>>, now: DateTime<Utc>) -> DateTime<Utc> {
+        let candidate = match self.schedule {
+            Some(schedule) => self.next_scheduled(schedule, last_run, now),
+            None => None,
+        };
+        let candidate = candidate.unwrap_or_else(|| self.next_by_interval(last_run, now));
+
+        self.skip_quiet_hours(candidate)
+    }
+
+    fn next_by_interval(
+        &self,
+        last_run: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> DateTime<Utc> {
+        let interval = TimeDelta::from_std(self.interval).unwrap_or(TimeDelta::hours(1));
+        // 前回実行が不明なら従来どおり起動から 1 間隔待つ
+        let base = last_run.map_or(now + interval, |last| last + interval);
+        base.max(now)
+    }
+
+    fn next_scheduled(
+        &self,
+        schedule: &FreeTootSchedule,
+        last_run: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let Some(last) = last_run else {
+            return self.next_occurrence(schedule, now);
+        };
+
+        let next = self.next_occurrence(schedule, last + TimeDelta::seconds(1))?;
+        if next >= now {
+            Some(next)
+        } else if now - next <= MISSED_RUN_GRACE {
+            // 再起動で少しだけ過ぎた予定はすぐ実行
+            Some(now)
+        } else {
+            self.next_occurrence(schedule, now)
+        }
+    }
+
+    /// `from` 以降で最初の予定時刻
+    fn next_occurrence(
+        &self,
+        schedule: &FreeTootSchedule,
+        from: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let start_date = from.with_timezone(&self.timezone).date_naive();
+
+        (0..=MAX_SEARCH_DAYS)
+            .filter_map(|offset| start_date.checked_add_days(Days::new(offset)))
+            .find_map(|date| {
+                schedule
+                    .times_on(date)
+                    .into_iter()
+                    .map(|time| local_to_utc(self.timezone, date.and_time(time)))
+                    .find(|at| *at >= from)
+            })
+    }
+
+    fn skip_quiet_hours(&self, mut at: DateTime<Utc>) -> DateTime<Utc> {
+        let Some(quiet) = self.quiet_hours else {
+            return at;
+        };
+
+        for _ in 0..MAX_QUIET_SKIPS {
+            if !self.is_quiet(at) {
+                break;
+            }
+
+            let quiet_end = self.quiet_end_after(quiet, at);
+            at = match self.schedule {
+                Some(schedule) => self.next_occurrence(schedule, quiet_end).unwrap_or(quiet_end),
+                None => quiet_end,
+            };
+        }
+
+        at
+    }
+
+    fn is_quiet(&self, at: DateTime<Utc>) -> bool {
+        self.quiet_hours.is_some_and(|q| q.contains(at.with_timezone(&self.timezone).time()))
+    }
+
+    fn quiet_end_after(&self, quiet: QuietHours, at: DateTime<Utc>) -> DateTime<Utc> {
+        let local = at.with_timezone(&self.timezone).naive_local();
+        let mut end = local.date().and_time(quiet.end);
+        if end <= local {
+            end += TimeDelta::days(1);
+        }
+        local_to_utc(self.timezone, end)
+    }
+}
+
+/// 夏時間の切り替えで存在しない時刻は、存在する時刻まで進める
+fn local_to_utc(timezone: Tz, naive: NaiveDateTime) -> DateTime<Utc> {
+    let mut naive = naive;
+    for _ in 0..4 {
+        if let Some(at) = timezone.from_local_datetime(&naive).earliest() {
+            return at.with_timezone(&Utc);
+        }
+        naive += TimeDelta::minutes(30);
+    }
+    Utc.from_utc_datetime(&naive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Asia::Tokyo;
+
+    fn jst(d: u32, h: u32, m: u32) -> DateTime<Utc> {
+        Tokyo.with_ymd_and_hms(2025, 1, d, h, m, 0).unwrap().with_timezone(&Utc)
+    }
+
+    fn scheduler<'a>(
+        schedule: Option<&'a FreeTootSchedule>,
+        quiet_hours: Option<&str>,
+    ) -> Scheduler<'a> {
+        Scheduler {
+            schedule,
+            interval: Duration::from_secs(3600),
+            quiet_hours: quiet_hours.map(|q| q.parse().unwrap()),
+            jitter: Duration::ZERO,
+            timezone: Tokyo,
+        }
+    }
+
+    #[test]
+    fn parses_schedule_variants() {
+        assert!(matches!(
+            "0 8 * * *".parse::<FreeTootSchedule>().unwrap(),
+            FreeTootSchedule::Cron(_)
+        ));
+        assert_eq!(
+            "19:00, 08:30".parse::<FreeTootSchedule>().unwrap(),
+            FreeTootSchedule::DailyTimes(vec![
+                NaiveTime::from_hms_opt(8, 30, 0).unwrap(),
+                NaiveTime::from_hms_opt(19, 0, 0).unwrap(),
+            ])
+        );
+        assert!("25:00".parse::<FreeTootSchedule>().is_err());
+        assert!("23-7".parse::<QuietHours>().is_ok());
+        assert!("22:30-06:45".parse::<QuietHours>().is_ok());
+        assert!("late".parse::<QuietHours>().is_err());
+    }
+
+    #[test]
+    fn interval_mode_continues_from_last_run() {
+        let s = scheduler(None, None);
+        let now = jst(1, 12, 0);
+
+        assert_eq!(s.next_run(None, now), jst(1, 13, 0));
+        assert_eq!(s.next_run(Some(jst(1, 11, 30)), now), jst(1, 12, 30));
+        // 停止中に予定を過ぎていたらすぐ
+        assert_eq!(s.next_run(Some(jst(1, 9, 0)), now), now);
+    }
+
+    #[test]
+    fn daily_times_use_configured_timezone() {
+        let schedule: FreeTootSchedule = "08:00,19:00".parse().unwrap();
+        let s = scheduler(Some(&schedule), None);
+
+        assert_eq!(s.next_run(None, jst(1, 12, 0)), jst(1, 19, 0));
+        assert_eq!(s.next_run(None, jst(1, 20, 0)), jst(2, 8, 0));
+    }
+
+    #[test]
+    fn restart_neither_repeats_nor_skips_scheduled_runs() {
+        let schedule: FreeTootSchedule = "08:00,19:00".parse().unwrap();
+        let s = scheduler(Some(&schedule), None);
+
+        // 08:00 に投稿済みで 08:05 に再起動 → 次は 19:00
+        assert_eq!(s.next_run(Some(jst(1, 8, 0)), jst(1, 8, 5)), jst(1, 19, 0));
+        // 19:00 の予定を 19:05 に再起動して逃した → すぐ実行
+        assert_eq!(s.next_run(Some(jst(1, 8, 0)), jst(1, 19, 5)), jst(1, 19, 5));
+        // 長時間止まっていた → 古い予定は飛ばして次へ
+        assert_eq!(s.next_run(Some(jst(1, 8, 0)), jst(1, 23, 0)), jst(2, 8, 0));
+    }
+
+    #[test]
+    fn quiet_hours_push_runs_to_next_allowed_time() {
+        let s = scheduler(None, Some("23-7"));
+        assert_eq!(s.next_run(Some(jst(1, 22, 30)), jst(1, 22, 45)), jst(2, 7, 0));
+
+        let schedule: FreeTootSchedule = "0 * * * *".parse().unwrap();
+        let s = scheduler(Some(&schedule), Some("23-7"));
+        assert_eq!(s.next_run(None, jst(1, 22, 30)), jst(2, 7, 0));
+        assert_eq!(s.next_run(None, jst(1, 6, 30)), jst(1, 7, 0));
+    }
+
+    #[test]
+    fn jitter_only_delays_within_bound() {
+        let mut s = scheduler(None, None);
+        s.jitter = Duration::from_secs(300);
+        let now = jst(1, 12, 0);
+
+        for _ in 0..20 {
+            let at = s.with_jitter(s.next_run(Some(now), now));
+            assert!(at >= jst(1, 13, 0));
+            assert!(at <= jst(1, 13, 5));
+        }
+    }
+}
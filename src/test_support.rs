@@ -1,4 +1,5 @@
-use crate::config::{BotConfig, Visibility};
+use crate::config::{AccessMode, BotConfig, CwMode, ModelTable, SearchClassifier, Visibility};
+use crate::mastodon::{Account, Status};
 use std::io::{Read, Write};
 use std::net::TcpListener;
 use std::thread;
@@ -10,17 +11,75 @@ pub(crate) fn test_config() -> BotConfig {
         mastodon_access_token: "mastodon-token".to_string(),
         openai_model: "gpt-test".to_string(),
         openai_reply_model: "gpt-test-reply".to_string(),
+        reply_fallback_models: Vec::new(),
+        free_toot_fallback_models: Vec::new(),
+        retry_fallback_models: Vec::new(),
+        model_capabilities: ModelTable::builtin(),
         openai_api_key: "openai-token".to_string(),
         streaming_base_url: "wss://mastodon.example/api/v1/streaming".to_string(),
         prompts_path: "config/prompts.json".to_string(),
         bot_db_path: ":memory:".to_string(),
+        timezone: chrono_tz::Asia::Tokyo,
         free_toot_interval: Duration::from_secs(3600),
+        free_toot_schedule: None,
+        free_toot_quiet_hours: None,
+        free_toot_jitter: Duration::ZERO,
+        free_toot_history_size: 20,
+        free_toot_similarity_threshold: 0.6,
+        free_toot_max_attempts: 3,
+        free_toot_avoid_recent_in_prompt: 0,
+        topics_path: None,
+        feed_urls: Vec::new(),
+        feed_poll_interval: Duration::from_secs(1800),
+        feed_max_posts_per_poll: 1,
+        feed_keep_links: true,
         reply_temperature: 0.7,
         free_toot_temperature: 0.8,
         visibility: Visibility::Unlisted,
         mastodon_char_limit: 500,
         reply_min_interval: Duration::from_millis(0),
+        conversation_max_age: Duration::from_secs(2_592_000),
         enable_web_search: false,
+        search_classifier: SearchClassifier::Off,
+        search_classifier_model: "gpt-test".into(),
+        function_tools: Vec::new(),
+        function_tool_max_rounds: 4,
+        reply_cw_mode: CwMode::Inherit,
+        reply_structured_output: true,
+        reply_model_cw: false,
+        reply_source_links: 0,
+        access_mode: AccessMode::Open,
+        allowlist_path: None,
+        allowlist_list_id: None,
+        allowlist_refresh: Duration::from_secs(600),
+        access_denied_notice: false,
+        loop_max_thread_replies: 20,
+        loop_max_account_replies: 10,
+        loop_account_window: Duration::from_secs(600),
+        loop_closing_notice: false,
+        nobot_tags: vec!["#nobot".into(), "#noai".into()],
+        nobot_cache_ttl: Duration::from_secs(86400),
+        free_toot_approval: false,
+        approval_moderators: Vec::new(),
+        approval_expiry: Duration::from_secs(86400),
+        approval_regenerate_on_reject: false,
+        dry_run: false,
+    }
+}
+
+/// `@acct` の公開投稿（本文は `hi`）
+pub(crate) fn test_status(id: &str, acct: &str) -> Status {
+    Status {
+        id: id.into(),
+        content: "<p>hi</p>".into(),
+        visibility: "public".into(),
+        spoiler_text: String::new(),
+        sensitive: false,
+        language: None,
+        in_reply_to_id: None,
+        created_at: String::new(),
+        media_attachments: Vec::new(),
+        account: Account { acct: acct.into(), ..Default::default() },
     }
 }
 
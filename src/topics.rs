@@ -0,0 +1,226 @@
+//! 自由トゥートで使う話題のキュー（`TOPICS_PATH` のファイル）
+//!
+//! 自由トゥートのたびに読み直すので、ボットを止めずに編集できる。
+//!
+//! - JSON: `["話題", {"text": "話題", "date": "2025-04-01", "id": "maintenance"}]`
+//! - CSV: `text,date,id`（`date` と `id` は省略可、1 行目がヘッダーなら読み飛ばす）
+//!
+//! `id` を省略すると本文そのものを ID として扱う。
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::{collections::HashSet, fs, path::Path};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Topic {
+    /// 使用済みの記録に使う ID
+    pub id: String,
+    pub text: String,
+    /// 指定があればその日だけ使う
+    pub date: Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TopicEntry {
+    Text(String),
+    Full {
+        text: String,
+        #[serde(default)]
+        date: Option<NaiveDate>,
+        #[serde(default)]
+        id: Option<String>,
+    },
+}
+
+impl Topic {
+    fn new(text: &str, date: Option<NaiveDate>, id: Option<&str>) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        let id = id.map(str::trim).filter(|id| !id.is_empty()).unwrap_or(text);
+        Some(Self { id: id.to_string(), text: text.to_string(), date })
+    }
+}
+
+pub fn load_topics(path: &str) -> Result<Vec<Topic>> {
+    let data = fs::read_to_string(path).with_context(|| format!("failed to read topics {path}"))?;
+
+    let is_csv = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+    if is_csv {
+        parse_csv_topics(&data).with_context(|| format!("failed to parse topics CSV {path}"))
+    } else {
+        parse_json_topics(&data).with_context(|| format!("failed to parse topics JSON {path}"))
+    }
+}
+
+fn parse_json_topics(data: &str) -> Result<Vec<Topic>> {
+    let entries: Vec<TopicEntry> = serde_json::from_str(data)?;
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| match entry {
+            TopicEntry::Text(text) => Topic::new(&text, None, None),
+            TopicEntry::Full { text, date, id } => Topic::new(&text, date, id.as_deref()),
+        })
+        .collect())
+}
+
+fn parse_csv_topics(data: &str) -> Result<Vec<Topic>> {
+    let mut topics = Vec::new();
+
+    for (i, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = split_csv_line(line).map_err(|e| anyhow!("line {}: {e}", i + 1))?;
+        if i == 0 && fields.first().is_some_and(|f| f.trim().eq_ignore_ascii_case("text")) {
+            continue;
+        }
+
+        let date = match fields.get(1).map(|d| d.trim()).filter(|d| !d.is_empty()) {
+            Some(d) => Some(
+                NaiveDate::parse_from_str(d, "%Y-%m-%d")
+                    .map_err(|e| anyhow!("line {}: invalid date {d:?}: {e}", i + 1))?,
+            ),
+            None => None,
+        };
+        topics.extend(Topic::new(&fields[0], date, fields.get(2).map(String::as_str)));
+    }
+
+    Ok(topics)
+}
+
+/// ダブルクォート（`""` でエスケープ）に対応した 1 行分の分割
+fn split_csv_line(line: &str) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+            }
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        bail!("unterminated quote");
+    }
+    fields.push(field);
+
+    Ok(fields)
+}
+
+/// 次に使う話題
+///
+/// 今日の日付が指定された話題を優先し、なければ日付なしの話題をファイルの順に使う。
+/// 使用済みの話題と、日付が今日でない話題は使わない。
+pub fn next_topic<'a>(
+    topics: &'a [Topic],
+    today: NaiveDate,
+    done: &HashSet<String>,
+) -> Option<&'a Topic> {
+    let pending = || topics.iter().filter(|t| !done.contains(&t.id));
+
+    pending().find(|t| t.date == Some(today)).or_else(|| pending().find(|t| t.date.is_none()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn parses_json_strings_and_objects() {
+        let topics = parse_json_topics(
+            r#"[
+                "週末の予定を聞く",
+                {"text": "今日はメンテナンス日", "date": "2025-04-01", "id": "maint"},
+                "  "
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            topics,
+            vec![
+                Topic {
+                    id: "週末の予定を聞く".into(),
+                    text: "週末の予定を聞く".into(),
+                    date: None
+                },
+                Topic {
+                    id: "maint".into(),
+                    text: "今日はメンテナンス日".into(),
+                    date: Some(date(2025, 4, 1))
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_csv_with_header_quotes_and_optional_columns() {
+        let topics = parse_csv_topics(
+            "text,date,id\n\
+             週末の予定を聞く\n\
+             \"メンテナンス日です, \"\"告知\"\" を見てね\",2025-04-01,maint\n\
+             \n\
+             お花見,,hanami\n",
+        )
+        .unwrap();
+
+        assert_eq!(topics.len(), 3);
+        assert_eq!(topics[0].id, "週末の予定を聞く");
+        assert_eq!(topics[1].text, "メンテナンス日です, \"告知\" を見てね");
+        assert_eq!(topics[1].date, Some(date(2025, 4, 1)));
+        assert_eq!(topics[2].id, "hanami");
+        assert_eq!(topics[2].date, None);
+    }
+
+    #[test]
+    fn rejects_bad_csv_dates_and_quotes() {
+        assert!(parse_csv_topics("話題,04/01\n").unwrap_err().to_string().contains("line 1"));
+        assert!(parse_csv_topics("\"open\n").is_err());
+    }
+
+    #[test]
+    fn prefers_topics_dated_today_then_undated_ones() {
+        let topics = parse_json_topics(
+            r#"[
+                "a",
+                {"text": "past", "date": "2025-03-31"},
+                {"text": "today", "date": "2025-04-01"},
+                "b"
+            ]"#,
+        )
+        .unwrap();
+        let today = date(2025, 4, 1);
+        let mut done = HashSet::new();
+
+        assert_eq!(next_topic(&topics, today, &done).unwrap().text, "today");
+        done.insert("today".to_string());
+        assert_eq!(next_topic(&topics, today, &done).unwrap().text, "a");
+        done.insert("a".to_string());
+        assert_eq!(next_topic(&topics, today, &done).unwrap().text, "b");
+        done.insert("b".to_string());
+        assert!(next_topic(&topics, today, &done).is_none());
+    }
+}
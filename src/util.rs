@@ -106,6 +106,39 @@ pub fn fit_for_mastodon_plain(input: &str, limit: usize) -> String {
     out
 }
 
+/// 文字 n-gram（n=2）の重なりで 0.0〜1.0 の類似度を出す（Dice 係数）
+///
+/// 空白・記号・大文字小文字の違いは無視する。どちらかが短すぎるときは完全一致だけ 1.0
+pub fn text_similarity(a: &str, b: &str) -> f32 {
+    let a = normalize_for_similarity(a);
+    let b = normalize_for_similarity(b);
+    if a.len() < 2 || b.len() < 2 {
+        return if !a.is_empty() && a == b { 1.0 } else { 0.0 };
+    }
+
+    let grams_a = bigrams(&a);
+    let mut grams_b = bigrams(&b);
+    let total = grams_a.len() + grams_b.len();
+
+    let mut shared = 0;
+    for gram in &grams_a {
+        if let Some(pos) = grams_b.iter().position(|g| g == gram) {
+            grams_b.swap_remove(pos);
+            shared += 1;
+        }
+    }
+
+    (2 * shared) as f32 / total as f32
+}
+
+fn normalize_for_similarity(input: &str) -> Vec<char> {
+    input.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+fn bigrams(chars: &[char]) -> Vec<(char, char)> {
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,4 +183,25 @@ mod tests {
         assert!(got.chars().count() <= 10);
         assert!(got.ends_with('…'));
     }
+
+    #[test]
+    fn similarity_ignores_spacing_and_punctuation() {
+        let a = "おはよう！今日もいい天気だね。";
+        let b = "おはよう 今日もいい天気だね";
+        assert_eq!(text_similarity(a, b), 1.0);
+
+        let c = "夜のラーメンは罪の味がする";
+        assert!(text_similarity(a, c) < 0.2);
+
+        let d = "おはよう！今日もいい天気だから散歩しよう。";
+        let sim = text_similarity(a, d);
+        assert!(sim > 0.5 && sim < 1.0, "{sim}");
+    }
+
+    #[test]
+    fn similarity_of_tiny_texts() {
+        assert_eq!(text_similarity("", ""), 0.0);
+        assert_eq!(text_similarity("あ", "あ。"), 1.0);
+        assert_eq!(text_similarity("あ", "い"), 0.0);
+    }
 }